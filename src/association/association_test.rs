@@ -1,4 +1,7 @@
 use super::*;
+use crate::chunk::chunk_type::{ChunkType, CT_RECONFIG};
+use crate::chunk::PROTOCOL_VIOLATION;
+use crate::queue::reassembly_queue::MessagePayload;
 
 const ACCEPT_CH_SIZE: usize = 16;
 
@@ -10,6 +13,8 @@ fn create_association(config: TransportConfig) -> Association {
         0,
         SocketAddr::from_str("0.0.0.0:0").unwrap(),
         None,
+        5000,
+        5000,
         Instant::now(),
     )
 }
@@ -276,7 +281,10 @@ fn test_handle_forward_tsn_dup_forward_tsn_chunk_should_generate_sack() -> Resul
 
 #[test]
 fn test_assoc_create_new_stream() -> Result<()> {
-    let mut a = Association::default();
+    let mut a = Association {
+        my_max_num_inbound_streams: u16::MAX,
+        ..Default::default()
+    };
 
     for i in 0..ACCEPT_CH_SIZE {
         let stream_identifier =
@@ -327,7 +335,7 @@ fn handle_init_test(name: &str, initial_state: AssociationState, expect_err: boo
         advertised_receiver_window_credit: 512 * 1024,
         ..Default::default()
     };
-    init.set_supported_extensions();
+    init.set_supported_extensions(DEFAULT_SUPPORTED_EXTENSIONS.chunk_types());
 
     let result = a.handle_init(&pkt, &init);
     if expect_err {
@@ -346,8 +354,8 @@ fn handle_init_test(name: &str, initial_state: AssociationState, expect_err: boo
         "{} should match",
         name
     );
-    assert_eq!(1001, a.my_max_num_outbound_streams, "{} should match", name);
-    assert_eq!(1002, a.my_max_num_inbound_streams, "{} should match", name);
+    assert_eq!(1002, a.my_max_num_outbound_streams, "{} should match", name);
+    assert_eq!(1001, a.my_max_num_inbound_streams, "{} should match", name);
     assert_eq!(5678, a.peer_verification_tag, "{} should match", name);
     assert_eq!(
         pkt.common_header.source_port, a.destination_port,
@@ -399,10 +407,79 @@ fn test_assoc_handle_init() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_assoc_negotiation_closes_out_of_range_streams() -> Result<()> {
+    let mut a = create_association(TransportConfig::default());
+    a.set_state(AssociationState::CookieWait);
+
+    // Streams opened and written to during the handshake
+    let ppi = PayloadProtocolIdentifier::Binary;
+    let kept = a.open_stream(1, ppi)?.write(b"ABC")?;
+    let mut s = a.open_stream(3, ppi)?;
+    let dropped = s.write(b"DEF")?;
+    s.write_partial(b"GHI", ppi, false)?;
+    let held_back = a.open_stream(2, ppi)?.write(b"JKL")?;
+    assert_eq!(2, a.pending_queue.len());
+    assert_eq!(
+        1,
+        a.deferred_payload.len(),
+        "held back by the partial write"
+    );
+    while a.poll().is_some() {}
+
+    // The peer only accepts two inbound streams
+    a.negotiate_num_streams(&ChunkInit {
+        num_outbound_streams: 10,
+        num_inbound_streams: 2,
+        ..Default::default()
+    });
+    assert_eq!(2, a.my_max_num_outbound_streams);
+    assert!(a.streams.contains_key(&1), "stream 1 should stay open");
+    assert!(!a.streams.contains_key(&2), "stream 2 should be closed");
+    assert!(!a.streams.contains_key(&3), "stream 3 should be closed");
+    assert!(a.partial_write_stream.is_none());
+    assert!(a.deferred_payload.is_empty());
+    assert_eq!(1, a.pending_queue.len(), "only stream 1 should be queued");
+    assert_eq!(Some(kept), a.pending_queue.peek().unwrap().message_handle);
+
+    let mut stopped = vec![];
+    let mut failed = vec![];
+    while let Some(e) = a.poll() {
+        match e {
+            Event::Stream(StreamEvent::Stopped { id, error_code }) => {
+                assert_eq!(INVALID_STREAM_IDENTIFIER, error_code);
+                stopped.push(id);
+            }
+            Event::SendFailed { messages } => failed.extend(messages),
+            ev => panic!("unexpected event {:?}", ev),
+        }
+    }
+    assert_eq!(vec![2, 3], stopped);
+    let failed: Vec<(StreamId, &[u8], Option<MessageHandle>)> = failed
+        .iter()
+        .map(|m| (m.stream_identifier, &m.payload[..], m.handle))
+        .collect();
+    assert_eq!(
+        vec![
+            (3, &b"DEF"[..], Some(dropped)),
+            (2, &b"JKL"[..], Some(held_back))
+        ],
+        failed,
+        "complete messages of the closed streams should be reported"
+    );
+    assert!(a.stream(3).is_err(), "stream 3 should not be writable");
+
+    Ok(())
+}
+
 #[test]
 fn test_assoc_max_message_size_default() -> Result<()> {
     let mut a = create_association(TransportConfig::default());
     assert_eq!(65536, a.max_message_size, "should match");
+    assert_eq!(
+        0, a.max_receive_message_size,
+        "inbound messages should not be limited"
+    );
 
     let ppi = PayloadProtocolIdentifier::Unknown;
     let stream = a.create_stream(1, false, ppi);
@@ -411,15 +488,11 @@ fn test_assoc_max_message_size_default() -> Result<()> {
     if let Some(mut s) = stream {
         let p = Bytes::from(vec![0u8; 65537]);
 
-        if let Err(err) = s.write_sctp(&p.slice(..65536), ppi) {
-            assert_ne!(
-                Error::ErrOutboundPacketTooLarge,
-                err,
-                "should be not Error::ErrOutboundPacketTooLarge"
-            );
-        } else {
-            panic!("should be error");
-        }
+        // Queued as early data while the handshake is in progress
+        assert!(
+            s.write_sctp(&p.slice(..65536), ppi).is_ok(),
+            "should be not Error::ErrOutboundPacketTooLarge"
+        );
 
         if let Err(err) = s.write_sctp(&p.slice(..65537), ppi) {
             assert_eq!(
@@ -448,15 +521,11 @@ fn test_assoc_max_message_size_explicit() -> Result<()> {
     if let Some(mut s) = stream {
         let p = Bytes::from(vec![0u8; 30001]);
 
-        if let Err(err) = s.write_sctp(&p.slice(..30000), ppi) {
-            assert_ne!(
-                Error::ErrOutboundPacketTooLarge,
-                err,
-                "should be not Error::ErrOutboundPacketTooLarge"
-            );
-        } else {
-            panic!("should be error");
-        }
+        // Queued as early data while the handshake is in progress
+        assert!(
+            s.write_sctp(&p.slice(..30000), ppi).is_ok(),
+            "should be not Error::ErrOutboundPacketTooLarge"
+        );
 
         if let Err(err) = s.write_sctp(&p.slice(..30001), ppi) {
             assert_eq!(
@@ -471,3 +540,2169 @@ fn test_assoc_max_message_size_explicit() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_assoc_receiver_sws_avoidance() -> Result<()> {
+    let mut a = Association {
+        mtu: 1228,
+        max_receive_buffer_size: 64 * 1024,
+        advertised_rwnd: 0,
+        ..Default::default()
+    };
+
+    // Fully open window is always announced
+    assert_eq!(64 * 1024, a.get_advertised_receiver_window_credit());
+
+    a.create_stream(1, true, PayloadProtocolIdentifier::Unknown);
+    if let Some(s) = a.streams.get_mut(&1) {
+        s.reassembly_queue.push(ChunkPayloadData {
+            beginning_fragment: true,
+            ending_fragment: true,
+            tsn: 1,
+            stream_identifier: 1,
+            user_data: Bytes::from(vec![0u8; 1000]),
+            ..Default::default()
+        });
+    }
+
+    // A shrinking window is reported as is
+    assert_eq!(
+        64 * 1024 - 1000,
+        a.get_advertised_receiver_window_credit(),
+        "shrinking window should be advertised"
+    );
+
+    // A small increase is held back
+    a.advertised_rwnd = 64 * 1024 - 2000;
+    assert_eq!(
+        64 * 1024 - 2000,
+        a.get_advertised_receiver_window_credit(),
+        "increase smaller than MTU should not be advertised"
+    );
+
+    // A meaningful increase is announced
+    a.advertised_rwnd = 64 * 1024 - 5000;
+    assert_eq!(
+        64 * 1024 - 1000,
+        a.get_advertised_receiver_window_credit(),
+        "increase larger than MTU should be advertised"
+    );
+
+    // Window updates after reads use the same threshold
+    a.set_state(AssociationState::Established);
+    a.advertised_rwnd = 64 * 1024 - 2000;
+    a.on_receive_window_opened(1);
+    assert_eq!(AckState::Idle, a.ack_state, "increase smaller than MTU");
+    a.advertised_rwnd = 64 * 1024 - 5000;
+    a.on_receive_window_opened(1);
+    assert_eq!(
+        AckState::Immediate,
+        a.ack_state,
+        "increase larger than MTU should send a window update"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_zero_window_probe_with_data_in_flight() -> Result<()> {
+    let now = Instant::now();
+    let mut a = Association {
+        mtu: 1228,
+        cwnd: 4 * 1228,
+        cumulative_tsn_ack_point: 9,
+        timers: TimerTable::new(),
+        rto_mgr: RtoManager::new(),
+        ..Default::default()
+    };
+    a.set_state(AssociationState::Established);
+    a.create_stream(1, false, PayloadProtocolIdentifier::Unknown);
+
+    a.inflight_queue.push_no_check(ChunkPayloadData {
+        beginning_fragment: true,
+        ending_fragment: true,
+        tsn: 10,
+        stream_identifier: 1,
+        user_data: Bytes::from_static(b"ABC"),
+        nsent: 1,
+        ..Default::default()
+    });
+
+    // Peer window closed with data in flight
+    a.rwnd = 0;
+    a.update_zero_window_probe(now);
+    assert!(a.is_zero_window_probing(), "should start probing");
+    assert!(a.timers.get(Timer::T3RTX).is_none(), "T3-rtx should be off");
+
+    // First probe after one RTO
+    let rto = Duration::from_millis(a.rto_mgr.get_rto());
+    a.handle_timeout(now + rto);
+    let (packets, _) = a.gather_outbound(now + rto);
+    assert_eq!(1, packets.len(), "should send one probe");
+    let p = Packet::unmarshal(&packets[0])?;
+    let c = p.chunks[0]
+        .as_any()
+        .downcast_ref::<ChunkPayloadData>()
+        .expect("probe should be DATA");
+    assert_eq!(10, c.tsn, "probe should be the oldest outstanding chunk");
+    assert_eq!(1, a.stats.get_num_zero_window_probes());
+
+    // Next probe is backed off
+    let next = a.timers.get(Timer::ZeroWindowProbe).unwrap();
+    assert_eq!(now + rto + rto * 2, next, "probe timer should back off");
+
+    // Window reopens
+    a.rwnd = 4096;
+    a.update_zero_window_probe(now + rto);
+    assert!(!a.is_zero_window_probing(), "should stop probing");
+    assert!(a.timers.get(Timer::T3RTX).is_some(), "T3-rtx should be on");
+    assert!(
+        a.inflight_queue.get(10).unwrap().retransmit,
+        "outstanding data should be retransmitted"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_zero_window_probe_skips_abandoned() -> Result<()> {
+    let now = Instant::now();
+    let mut a = Association {
+        mtu: 1228,
+        cwnd: 4 * 1228,
+        cumulative_tsn_ack_point: 9,
+        use_forward_tsn: true,
+        timers: TimerTable::new(),
+        rto_mgr: RtoManager::new(),
+        ..Default::default()
+    };
+    a.set_state(AssociationState::Established);
+    a.create_stream(1, false, PayloadProtocolIdentifier::Unknown);
+    a.create_stream(2, false, PayloadProtocolIdentifier::Unknown);
+    if let Some(s) = a.streams.get_mut(&1) {
+        s.reliability_type = ReliabilityType::Rexmit;
+        s.reliability_value = 1;
+    }
+
+    for (tsn, stream_identifier) in [(10, 1), (11, 2)] {
+        a.inflight_queue.push_no_check(ChunkPayloadData {
+            beginning_fragment: true,
+            ending_fragment: true,
+            tsn,
+            stream_identifier,
+            user_data: Bytes::from_static(b"ABC"),
+            nsent: 1,
+            all_inflight: true,
+            ..Default::default()
+        });
+    }
+
+    // The oldest chunk used up its retransmissions, so the next one probes
+    let probe = a
+        .get_zero_window_probe_chunk(now)
+        .expect("should send a probe");
+    assert_eq!(11, probe.tsn, "probe should skip the abandoned chunk");
+    assert!(a.inflight_queue.get(10).unwrap().abandoned());
+    assert_eq!(1, a.inflight_queue.get(10).unwrap().nsent, "not resent");
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_max_burst() -> Result<()> {
+    let now = Instant::now();
+    let mut a = Association {
+        mtu: 1228,
+        cwnd: 64 * 1024,
+        rwnd: 64 * 1024,
+        max_burst: 4,
+        my_next_tsn: 1,
+        timers: TimerTable::new(),
+        rto_mgr: RtoManager::new(),
+        ..Default::default()
+    };
+    a.set_state(AssociationState::Established);
+
+    for i in 0..10u16 {
+        a.pending_queue.push(ChunkPayloadData {
+            beginning_fragment: true,
+            ending_fragment: true,
+            stream_identifier: 1,
+            stream_sequence_number: i,
+            user_data: Bytes::from(vec![0u8; 1000]),
+            ..Default::default()
+        });
+    }
+
+    let (packets, _) = a.gather_outbound(now);
+    assert_eq!(4, packets.len(), "should send up to Max.Burst packets");
+    assert_eq!(6, a.pending_queue.len(), "the rest should stay pending");
+
+    // Polling again does not renew the budget, a SACK does
+    let (packets, _) = a.gather_outbound(now);
+    assert!(packets.is_empty(), "should wait for a SACK");
+
+    let sack = |cumulative_tsn_ack| ChunkSelectiveAck {
+        cumulative_tsn_ack,
+        advertised_receiver_window_credit: 64 * 1024,
+        gap_ack_blocks: vec![],
+        duplicate_tsn: vec![],
+    };
+    a.handle_sack(&sack(2), now)?;
+    let (packets, _) = a.gather_outbound(now);
+    assert_eq!(4, packets.len(), "should send up to Max.Burst packets");
+
+    a.handle_sack(&sack(4), now)?;
+    let (packets, _) = a.gather_outbound(now);
+    assert_eq!(2, packets.len(), "should send remaining packets");
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_bundles_data_in_tsn_order() -> Result<()> {
+    let now = Instant::now();
+    let mut a = Association {
+        mtu: 1228,
+        cwnd: 64 * 1024,
+        rwnd: 64 * 1024,
+        cumulative_tsn_ack_point: 9,
+        my_next_tsn: 11,
+        timers: TimerTable::new(),
+        rto_mgr: RtoManager::new(),
+        ..Default::default()
+    };
+    a.set_state(AssociationState::Established);
+    a.create_stream(1, false, PayloadProtocolIdentifier::Unknown);
+
+    // TSN 10 was reported missing three times
+    a.inflight_queue.push_no_check(ChunkPayloadData {
+        beginning_fragment: true,
+        ending_fragment: true,
+        tsn: 10,
+        stream_identifier: 1,
+        user_data: Bytes::from_static(b"ABC"),
+        nsent: 1,
+        miss_indicator: 3,
+        ..Default::default()
+    });
+    a.will_retransmit_fast = true;
+
+    a.pending_queue.push(ChunkPayloadData {
+        beginning_fragment: true,
+        ending_fragment: true,
+        stream_identifier: 1,
+        stream_sequence_number: 1,
+        user_data: Bytes::from_static(b"DEF"),
+        ..Default::default()
+    });
+
+    let (packets, _) = a.gather_outbound(now);
+    assert_eq!(1, packets.len(), "should bundle into one packet");
+    let p = Packet::unmarshal(&packets[0])?;
+    let tsns: Vec<u32> = p
+        .chunks
+        .iter()
+        .filter_map(|c| c.as_any().downcast_ref::<ChunkPayloadData>())
+        .map(|c| c.tsn)
+        .collect();
+    assert_eq!(vec![10, 11], tsns, "DATA should be in increasing TSN order");
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_cwnd_decay_after_idle() -> Result<()> {
+    let now = Instant::now();
+    let mut a = Association {
+        mtu: 1228,
+        cwnd: 100_000,
+        rto_mgr: RtoManager::new(),
+        ..Default::default()
+    };
+    let rto = Duration::from_millis(a.rto_mgr.get_rto());
+
+    // Never sent anything: no decay
+    a.validate_cwnd_after_idle(now);
+    assert_eq!(100_000, a.cwnd);
+
+    // Less than one RTO idle: no decay
+    a.last_data_sent = Some(now);
+    a.validate_cwnd_after_idle(now + rto / 2);
+    assert_eq!(100_000, a.cwnd);
+
+    // Three RTOs idle: halved three times
+    a.validate_cwnd_after_idle(now + rto * 3);
+    assert_eq!(12_500, a.cwnd);
+
+    // Repeated calls do not decay again for the same idle period
+    a.validate_cwnd_after_idle(now + rto * 3);
+    assert_eq!(12_500, a.cwnd);
+
+    // Never below 4*MTU
+    a.validate_cwnd_after_idle(now + rto * 20);
+    assert_eq!(4 * 1228, a.cwnd);
+
+    // A long idle decays at once and keeps the partial RTO
+    a.cwnd = u32::MAX;
+    let later = now + rto * 20 + Duration::from_secs(365 * 24 * 3600) + rto / 2;
+    a.validate_cwnd_after_idle(later);
+    assert_eq!(4 * 1228, a.cwnd);
+    assert_eq!(Some(later - rto / 2), a.last_data_sent);
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_pacing() -> Result<()> {
+    let now = Instant::now();
+    let mut a = Association {
+        mtu: 1228,
+        cwnd: 10 * 1228,
+        pacing: true,
+        rto_mgr: RtoManager::new(),
+        timers: TimerTable::new(),
+        ..Default::default()
+    };
+    a.rto_mgr.set_new_rtt(100);
+
+    for _ in 0..5 {
+        a.paced_packets.push_back(Bytes::from(vec![0u8; 1228]));
+    }
+
+    // A burst of two packets goes out right away
+    let t = a.poll_transmit(now).expect("should release a burst");
+    assert_eq!(Some(now), t.earliest_send, "burst should be sent now");
+    if let Payload::RawEncode(contents) = t.payload {
+        assert_eq!(2, contents.len(), "burst should be 2 packets");
+    } else {
+        panic!("unexpected payload");
+    }
+
+    // The next packet is released ahead, stamped with its slot: 1228 bytes at
+    // cwnd/srtt = 122.8 bytes/msec
+    let slot = now + Duration::from_millis(10);
+    let t = a.poll_transmit(now).expect("should release the next slot");
+    assert_eq!(Some(slot), t.earliest_send, "should be sent at next slot");
+    if let Payload::RawEncode(contents) = t.payload {
+        assert_eq!(1, contents.len(), "should release one packet");
+    } else {
+        panic!("unexpected payload");
+    }
+
+    // Nothing more until that slot has come
+    assert!(a.poll_transmit(now).is_none(), "should be paced");
+    assert_eq!(Some(slot), a.poll_timeout(), "should wake up at next slot");
+
+    let t = a.poll_transmit(slot).expect("should release at the slot");
+    assert_eq!(Some(slot + Duration::from_millis(10)), t.earliest_send);
+    if let Payload::RawEncode(contents) = t.payload {
+        assert_eq!(1, contents.len(), "should release one packet");
+    } else {
+        panic!("unexpected payload");
+    }
+    assert_eq!(1, a.paced_packets.len());
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_pacing_skips_control_packets() -> Result<()> {
+    let now = Instant::now();
+    let mut a = Association {
+        mtu: 1228,
+        cwnd: 10 * 1228,
+        pacing: true,
+        rto_mgr: RtoManager::new(),
+        timers: TimerTable::new(),
+        ..Default::default()
+    };
+    a.rto_mgr.set_new_rtt(100);
+
+    for _ in 0..5 {
+        a.paced_packets.push_back(Bytes::from(vec![0u8; 1228]));
+    }
+    let _ = a.poll_transmit(now).expect("should release a burst");
+    let _ = a.poll_transmit(now).expect("should release the next slot");
+    assert!(a.poll_transmit(now).is_none(), "should be paced");
+
+    // A SACK is not held behind the paced DATA
+    let sack = a.create_selective_ack_chunk();
+    let p = a.create_packet(vec![Box::new(sack)]);
+    a.control_queue.push_back(p);
+    let t = a.poll_transmit(now).expect("should send the SACK");
+    assert_eq!(None, t.earliest_send, "control packets should not be paced");
+    if let Payload::RawEncode(contents) = t.payload {
+        assert_eq!(1, contents.len(), "should send the SACK only");
+    } else {
+        panic!("unexpected payload");
+    }
+    assert_eq!(2, a.paced_packets.len(), "paced DATA should stay queued");
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_close_drops_paced_packets() -> Result<()> {
+    let now = Instant::now();
+    let mut a = Association {
+        mtu: 1228,
+        cwnd: 10 * 1228,
+        pacing: true,
+        rto_mgr: RtoManager::new(),
+        timers: TimerTable::new(),
+        ..Default::default()
+    };
+    a.set_state(AssociationState::Established);
+    a.rto_mgr.set_new_rtt(100);
+
+    for _ in 0..5 {
+        a.paced_packets.push_back(Bytes::from(vec![0u8; 1228]));
+    }
+    let _ = a.poll_transmit(now).expect("should release a burst");
+
+    a.close()?;
+    assert!(a.paced_packets.is_empty());
+    assert!(
+        a.poll_transmit(now + Duration::from_secs(1)).is_none(),
+        "nothing should be sent after close"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_sack_reports_duplicate_tsns() -> Result<()> {
+    let mut a = Association {
+        mtu: 1228,
+        peer_last_tsn: 10,
+        max_receive_buffer_size: 64 * 1024,
+        ..Default::default()
+    };
+
+    a.handle_chunk_start();
+    a.handle_data(&ChunkPayloadData {
+        beginning_fragment: true,
+        ending_fragment: true,
+        tsn: 9,
+        stream_identifier: 1,
+        user_data: Bytes::from_static(b"ABC"),
+        ..Default::default()
+    })?;
+    assert!(a.immediate_ack_triggered, "duplicate should trigger SACK");
+    assert_eq!(1, a.stats.get_num_duplicate_tsns());
+
+    let sack = a.create_selective_ack_chunk();
+    assert_eq!(vec![9], sack.duplicate_tsn, "duplicate should be reported");
+
+    let sack = a.create_selective_ack_chunk();
+    assert!(
+        sack.duplicate_tsn.is_empty(),
+        "duplicate reported only once"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_sack_fits_mtu() -> Result<()> {
+    let mtu = 100;
+    let mut a = Association {
+        mtu,
+        peer_last_tsn: 10,
+        ..Default::default()
+    };
+
+    // 30 gap ack blocks, one for every other TSN
+    for i in 1..=30 {
+        a.payload_queue.push_no_check(ChunkPayloadData {
+            tsn: 10 + 2 * i,
+            user_data: Bytes::from_static(b"ABC"),
+            ..Default::default()
+        });
+    }
+    a.payload_queue.push(
+        ChunkPayloadData {
+            tsn: 12,
+            ..Default::default()
+        },
+        10,
+    );
+
+    let sack = a.create_selective_ack_chunk();
+    assert_eq!(
+        18,
+        sack.gap_ack_blocks.len(),
+        "gap ack blocks should be capped"
+    );
+    assert_eq!(
+        2, sack.gap_ack_blocks[0].start,
+        "lowest blocks should be kept"
+    );
+    assert!(sack.duplicate_tsn.is_empty(), "no room left for duplicates");
+
+    let raw = a.create_packet(vec![Box::new(sack)]).marshal()?;
+    assert!(raw.len() <= mtu as usize, "SACK should fit the MTU");
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_send_buffer_limit() -> Result<()> {
+    let mut a = create_association(TransportConfig::default().with_max_send_buffer_size(100));
+
+    let ppi = PayloadProtocolIdentifier::Binary;
+    let mut s = a.open_stream(1, ppi)?;
+    let p = Bytes::from(vec![0u8; 60]);
+
+    assert_eq!(
+        60,
+        s.write_sctp(&p, ppi)?,
+        "should be queued while handshaking"
+    );
+    assert_eq!(
+        Err(Error::ErrSendBufferFull),
+        s.write_sctp(&p, ppi),
+        "should exceed the send buffer"
+    );
+    assert_eq!(40, s.write_sctp(&p.slice(..40), ppi)?, "should fit exactly");
+    assert_eq!(100, a.buffered_amount(), "incorrect bufferedAmount");
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_handshake_failure_reports_queued_messages() -> Result<()> {
+    let mut a = create_association(TransportConfig::default());
+    assert_eq!(AssociationState::CookieWait, a.state());
+
+    let mut s = a.open_stream(1, PayloadProtocolIdentifier::Binary)?;
+    s.set_reliability_params(true, ReliabilityType::Rexmit, 0)?;
+    s.write_sctp(
+        &Bytes::from_static(b"ABC"),
+        PayloadProtocolIdentifier::String,
+    )?;
+    let long_msg = Bytes::from(vec![7u8; 3000]);
+    s.write_sctp(&long_msg, PayloadProtocolIdentifier::Binary)?;
+
+    let mut n_timeouts = 0;
+    while let Some(t) = a.poll_timeout() {
+        a.handle_timeout(t);
+        n_timeouts += 1;
+        assert!(n_timeouts < 20, "handshake should have failed");
+    }
+
+    let messages = match a.poll() {
+        Some(Event::SendFailed { messages }) => messages,
+        ev => panic!("expected SendFailed, got {:?}", ev),
+    };
+    assert_eq!(2, messages.len(), "both messages should be reported");
+    assert_eq!(1, messages[0].stream_identifier);
+    assert_eq!(PayloadProtocolIdentifier::String, messages[0].ppi);
+    assert_eq!(Bytes::from_static(b"ABC"), messages[0].payload);
+    assert!(!messages[0].sent, "should not have been sent");
+    assert_eq!(long_msg, messages[1].payload, "fragments should be joined");
+    assert_eq!(0, a.buffered_amount(), "queues should be empty");
+
+    assert!(matches!(
+        a.poll(),
+        Some(Event::AssociationLost {
+            reason: AssociationError::HandshakeFailed(Error::ErrHandshakeInitAck)
+        })
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_unrecognized_chunk_actions() -> Result<()> {
+    let tests = vec![
+        // (chunk type, data processed, reported)
+        (0x3f, false, false),
+        (0x7f, false, true),
+        (0xbf, true, false),
+        (0xff, true, true),
+    ];
+
+    for (typ, data_processed, reported) in tests {
+        let mut a = create_association(TransportConfig::default());
+        a.set_state(AssociationState::Established);
+        a.peer_verification_tag = 1234;
+        a.peer_last_tsn = 9;
+        a.control_queue.clear();
+
+        let unknown = ChunkUnknown {
+            typ: ChunkType(typ),
+            flags: 0,
+            value: Bytes::from_static(b"XYZ"),
+        };
+        let p = Packet {
+            common_header: CommonHeader {
+                source_port: 5000,
+                destination_port: 5000,
+                verification_tag: a.my_verification_tag,
+            },
+            chunks: vec![
+                Box::new(unknown.clone()),
+                Box::new(ChunkPayloadData {
+                    beginning_fragment: true,
+                    ending_fragment: true,
+                    tsn: 10,
+                    stream_identifier: 1,
+                    user_data: Bytes::from_static(b"ABC"),
+                    ..Default::default()
+                }),
+            ],
+        };
+        a.handle_inbound(p, Instant::now())?;
+
+        assert_eq!(
+            data_processed,
+            a.peer_last_tsn == 10,
+            "type {:#x}: unexpected DATA processing",
+            typ
+        );
+        let errors: Vec<ChunkError> = a
+            .control_queue
+            .iter()
+            .flat_map(|p| p.chunks.iter())
+            .filter_map(|c| c.as_any().downcast_ref::<ChunkError>().cloned())
+            .collect();
+        if reported {
+            assert_eq!(1, errors.len(), "type {:#x}: should be reported", typ);
+            assert_eq!(
+                ErrorCause::UnrecognizedChunkType {
+                    chunk: unknown.marshal()?
+                },
+                errors[0].error_causes[0],
+                "should carry the chunk"
+            );
+        } else {
+            assert!(errors.is_empty(), "type {:#x}: should not be reported", typ);
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_handle_init_unrecognized_params() -> Result<()> {
+    fn unknown_param(typ: u16) -> Box<dyn Param + Send + Sync> {
+        let [t0, t1] = typ.to_be_bytes();
+        let raw = Bytes::from(vec![t0, t1, 0x00, 0x06, 0xab, 0xcd]);
+        Box::new(ParamUnknown::unmarshal(&raw).unwrap())
+    }
+    let supported_extensions: Box<dyn Param + Send + Sync> = Box::new(ParamSupportedExtensions {
+        chunk_types: vec![CT_RECONFIG, CT_FORWARD_TSN],
+    });
+
+    let tests = vec![
+        // skipped, then processing continues
+        (
+            vec![
+                unknown_param(0xc100),
+                unknown_param(0x8101),
+                supported_extensions.clone(),
+            ],
+            vec![0xc100],
+            true,
+        ),
+        // processing stops at the parameter
+        (
+            vec![
+                unknown_param(0x8102),
+                unknown_param(0x4103),
+                supported_extensions.clone(),
+            ],
+            vec![0x4103],
+            false,
+        ),
+        (
+            vec![unknown_param(0x0104), supported_extensions.clone()],
+            vec![],
+            false,
+        ),
+        // known parameter types are not reported
+        (
+            vec![unknown_param(0x0005), supported_extensions.clone()],
+            vec![],
+            true,
+        ),
+    ];
+
+    for (params, reported, use_forward_tsn) in tests {
+        let mut a = create_association(TransportConfig::default());
+        a.set_state(AssociationState::Closed);
+        let pkt = Packet {
+            common_header: CommonHeader {
+                source_port: 5001,
+                destination_port: 5002,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let init = ChunkInit {
+            initial_tsn: 1234,
+            num_outbound_streams: 1001,
+            num_inbound_streams: 1002,
+            initiate_tag: 5678,
+            advertised_receiver_window_credit: 512 * 1024,
+            params,
+            ..Default::default()
+        };
+
+        let packets = a.handle_init(&pkt, &init)?;
+        assert_eq!(use_forward_tsn, a.use_forward_tsn, "{:x?}", reported);
+
+        let init_ack = packets[0].chunks[0]
+            .as_any()
+            .downcast_ref::<ChunkInit>()
+            .unwrap();
+        let reported_types: Vec<u16> = init_ack
+            .params
+            .iter()
+            .filter_map(|p| p.as_any().downcast_ref::<ParamUnrecognized>())
+            .map(|p| u16::from_be_bytes([p.raw_param[0], p.raw_param[1]]))
+            .collect();
+        assert_eq!(reported, reported_types, "unexpected unrecognized params");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_handle_init_ack_reports_unrecognized_params() -> Result<()> {
+    let mut a = create_association(TransportConfig::default());
+    assert_eq!(AssociationState::CookieWait, a.state());
+    a.control_queue.clear(); // INIT
+
+    let pkt = Packet {
+        common_header: CommonHeader {
+            source_port: a.destination_port,
+            destination_port: a.source_port,
+            verification_tag: a.my_verification_tag,
+        },
+        ..Default::default()
+    };
+    let unknown = Bytes::from_static(&[0xc1, 0x05, 0x00, 0x05, 0xee]);
+    let init_ack = ChunkInit {
+        is_ack: true,
+        initial_tsn: 1234,
+        num_outbound_streams: 1001,
+        num_inbound_streams: 1002,
+        initiate_tag: 5678,
+        advertised_receiver_window_credit: 512 * 1024,
+        params: vec![
+            Box::new(ParamStateCookie::new()),
+            Box::new(ParamUnknown::unmarshal(&unknown)?),
+        ],
+    };
+    a.handle_init_ack(&pkt, &init_ack, Instant::now())?;
+    assert_eq!(AssociationState::CookieEchoed, a.state());
+
+    let (raw_packets, _) = a.gather_outbound(Instant::now());
+    assert_eq!(1, raw_packets.len());
+    let p = Packet::unmarshal(&raw_packets[0])?;
+    assert!(
+        p.chunks[0]
+            .as_any()
+            .downcast_ref::<ChunkCookieEcho>()
+            .is_some(),
+        "COOKIE-ECHO should come first"
+    );
+    let cerr = p.chunks[1]
+        .as_any()
+        .downcast_ref::<ChunkError>()
+        .expect("ERROR should be bundled with COOKIE-ECHO");
+    assert_eq!(
+        ErrorCause::UnrecognizedParameters { params: unknown },
+        cerr.error_causes[0],
+        "should carry the TLV"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_error_chunk_is_not_fatal() -> Result<()> {
+    let mut a = create_association(TransportConfig::default());
+    a.set_state(AssociationState::Established);
+    a.peer_verification_tag = 1234;
+    while a.poll().is_some() {}
+
+    let causes = vec![
+        ErrorCause::InvalidStreamIdentifier {
+            stream_identifier: 7,
+        },
+        ErrorCause::UnrecognizedChunkType {
+            chunk: Bytes::from_static(&[0xc0, 0x00, 0x00, 0x05, 0xee]),
+        },
+    ];
+    let p = Packet {
+        common_header: CommonHeader {
+            source_port: 5000,
+            destination_port: 5000,
+            verification_tag: a.my_verification_tag,
+        },
+        chunks: vec![Box::new(ChunkError {
+            error_causes: causes.clone(),
+        })],
+    };
+    a.handle_inbound(Packet::unmarshal(&p.marshal()?)?, Instant::now())?;
+
+    assert_eq!(AssociationState::Established, a.state());
+    match a.poll() {
+        Some(Event::RemoteError { causes: received }) => assert_eq!(causes, received),
+        other => panic!("expected RemoteError, got {:?}", other),
+    }
+    assert!(a.poll().is_none(), "the association should not be lost");
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_data_without_user_data_aborts() -> Result<()> {
+    let mut a = create_association(TransportConfig::default());
+    a.set_state(AssociationState::Established);
+    a.peer_verification_tag = 1234;
+    a.peer_last_tsn = 9;
+    a.control_queue.clear();
+
+    let result = a.handle_data(&ChunkPayloadData {
+        beginning_fragment: true,
+        ending_fragment: true,
+        tsn: 10,
+        stream_identifier: 1,
+        ..Default::default()
+    });
+    assert_eq!(Err(Error::ErrNoUserData), result.map(|_| ()));
+
+    let abort = a.control_queue[0].chunks[0]
+        .as_any()
+        .downcast_ref::<ChunkAbort>()
+        .expect("should send ABORT");
+    assert_eq!(vec![ErrorCause::NoUserData { tsn: 10 }], abort.error_causes);
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_handle_init_ack_without_cookie_aborts() -> Result<()> {
+    let mut a = create_association(TransportConfig::default());
+    a.control_queue.clear(); // INIT
+
+    let pkt = Packet {
+        common_header: CommonHeader {
+            source_port: a.destination_port,
+            destination_port: a.source_port,
+            verification_tag: a.my_verification_tag,
+        },
+        ..Default::default()
+    };
+    let init_ack = ChunkInit {
+        is_ack: true,
+        initial_tsn: 1234,
+        num_outbound_streams: 1001,
+        num_inbound_streams: 1002,
+        initiate_tag: 5678,
+        advertised_receiver_window_credit: 512 * 1024,
+        params: vec![],
+    };
+    let result = a.handle_init_ack(&pkt, &init_ack, Instant::now());
+    assert_eq!(Err(Error::ErrInitAckNoCookie), result.map(|_| ()));
+
+    let p = &a.control_queue[0];
+    assert_eq!(5678, p.common_header.verification_tag);
+    let abort = p.chunks[0]
+        .as_any()
+        .downcast_ref::<ChunkAbort>()
+        .expect("should send ABORT");
+    assert_eq!(
+        vec![ErrorCause::MissingMandatoryParameter {
+            missing_params: vec![7]
+        }],
+        abort.error_causes
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_cookie_echo_while_shutting_down() -> Result<()> {
+    let mut a = create_association(TransportConfig::default());
+    let cookie = ParamStateCookie::new();
+    a.my_cookie = Some(cookie.clone());
+    a.peer_verification_tag = 1234;
+    a.set_state(AssociationState::ShutdownAckSent);
+    a.control_queue.clear();
+
+    let packets = a.handle_cookie_echo(&ChunkCookieEcho {
+        cookie: cookie.cookie.clone(),
+    })?;
+    assert_eq!(AssociationState::ShutdownAckSent, a.state());
+    assert!(a.will_send_shutdown_ack, "should retransmit SHUTDOWN-ACK");
+    let cerr = packets[0].chunks[0]
+        .as_any()
+        .downcast_ref::<ChunkError>()
+        .expect("should send ERROR");
+    assert_eq!(
+        vec![ErrorCause::CookieReceivedWhileShuttingDown],
+        cerr.error_causes
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_data_for_invalid_stream() -> Result<()> {
+    let mut a = create_association(TransportConfig::default());
+    a.set_state(AssociationState::Established);
+    a.peer_verification_tag = 1234;
+    a.peer_last_tsn = 9;
+    a.my_max_num_inbound_streams = 2;
+
+    a.handle_chunk_start();
+    let packets = a.handle_data(&ChunkPayloadData {
+        beginning_fragment: true,
+        ending_fragment: true,
+        tsn: 10,
+        stream_identifier: 2,
+        user_data: Bytes::from_static(b"ABC"),
+        ..Default::default()
+    })?;
+
+    let cerr = packets[0].chunks[0]
+        .as_any()
+        .downcast_ref::<ChunkError>()
+        .expect("should send ERROR");
+    assert_eq!(
+        vec![ErrorCause::InvalidStreamIdentifier {
+            stream_identifier: 2
+        }],
+        cerr.error_causes
+    );
+    assert_eq!(10, a.peer_last_tsn, "DATA should be acknowledged");
+    assert!(a.streams.is_empty(), "no stream should be created");
+    assert!(a.accept_stream().is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_open_stream_beyond_outbound_streams() -> Result<()> {
+    let mut a = create_association(TransportConfig::default().with_max_num_outbound_streams(2));
+
+    assert!(a.open_stream(1, PayloadProtocolIdentifier::Binary).is_ok());
+    assert_eq!(
+        Some(Error::ErrStreamIdentifierOutOfRange),
+        a.open_stream(2, PayloadProtocolIdentifier::Binary).err()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_negotiate_num_streams() -> Result<()> {
+    let mut a = create_association(
+        TransportConfig::default()
+            .with_max_num_outbound_streams(10)
+            .with_max_num_inbound_streams(20),
+    );
+
+    // Peer's OS=30, MIS=5
+    a.negotiate_num_streams(&ChunkInit {
+        num_outbound_streams: 30,
+        num_inbound_streams: 5,
+        ..Default::default()
+    });
+    assert_eq!(5, a.my_max_num_outbound_streams);
+    assert_eq!(20, a.my_max_num_inbound_streams);
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_inbound_message_too_large() -> Result<()> {
+    let mut a = create_association(TransportConfig::default().with_max_receive_message_size(5));
+    a.set_state(AssociationState::Established);
+    a.peer_verification_tag = 1234;
+    a.peer_last_tsn = 9;
+    a.control_queue.clear();
+
+    a.handle_data(&ChunkPayloadData {
+        beginning_fragment: true,
+        tsn: 10,
+        stream_identifier: 1,
+        user_data: Bytes::from_static(b"ABC"),
+        ..Default::default()
+    })?;
+    let result = a.handle_data(&ChunkPayloadData {
+        tsn: 11,
+        stream_identifier: 1,
+        user_data: Bytes::from_static(b"DEF"),
+        ..Default::default()
+    });
+    assert_eq!(Err(Error::ErrInboundMessageTooLarge), result.map(|_| ()));
+
+    let abort = a.control_queue[0].chunks[0]
+        .as_any()
+        .downcast_ref::<ChunkAbort>()
+        .expect("should send ABORT");
+    assert_eq!(PROTOCOL_VIOLATION, abort.error_causes[0].code());
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_inbound_message_too_large_across_partial_reads() -> Result<()> {
+    for unordered in [false, true] {
+        let mut a = create_association(
+            TransportConfig::default()
+                .with_max_receive_message_size(5)
+                .with_partial_delivery_point(3),
+        );
+        a.set_state(AssociationState::Established);
+        a.peer_verification_tag = 1234;
+        a.peer_last_tsn = 9;
+        a.control_queue.clear();
+
+        a.handle_data(&ChunkPayloadData {
+            unordered,
+            beginning_fragment: true,
+            tsn: 10,
+            stream_identifier: 1,
+            user_data: Bytes::from_static(b"ABC"),
+            ..Default::default()
+        })?;
+        let p = a.stream(1)?.read_partial()?.expect("should read a piece");
+        assert_eq!(3, p.chunks.len());
+
+        let result = a.handle_data(&ChunkPayloadData {
+            unordered,
+            tsn: 11,
+            stream_identifier: 1,
+            user_data: Bytes::from_static(b"DEF"),
+            ..Default::default()
+        });
+        assert_eq!(
+            Err(Error::ErrInboundMessageTooLarge),
+            result.map(|_| ()),
+            "unordered: {}",
+            unordered
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_too_many_partial_messages() -> Result<()> {
+    let mut a = create_association(TransportConfig::default().with_max_num_partial_messages(2));
+    a.set_state(AssociationState::Established);
+    a.peer_verification_tag = 1234;
+    a.peer_last_tsn = 9;
+    a.control_queue.clear();
+
+    // Complete messages do not count
+    a.handle_data(&ChunkPayloadData {
+        beginning_fragment: true,
+        ending_fragment: true,
+        tsn: 10,
+        stream_identifier: 0,
+        user_data: Bytes::from_static(b"ABC"),
+        ..Default::default()
+    })?;
+    for (i, stream_identifier) in [1, 2].into_iter().enumerate() {
+        a.handle_data(&ChunkPayloadData {
+            beginning_fragment: true,
+            tsn: 11 + i as u32,
+            stream_identifier,
+            user_data: Bytes::from_static(b"ABC"),
+            ..Default::default()
+        })?;
+    }
+    assert!(a.control_queue.is_empty());
+
+    let result = a.handle_data(&ChunkPayloadData {
+        beginning_fragment: true,
+        tsn: 13,
+        stream_identifier: 3,
+        user_data: Bytes::from_static(b"ABC"),
+        ..Default::default()
+    });
+    assert_eq!(Err(Error::ErrTooManyPartialMessages), result.map(|_| ()));
+
+    let abort = a.control_queue[0].chunks[0]
+        .as_any()
+        .downcast_ref::<ChunkAbort>()
+        .expect("should send ABORT");
+    assert_eq!(PROTOCOL_VIOLATION, abort.error_causes[0].code());
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_partial_messages_dropped() -> Result<()> {
+    let mut a = create_association(TransportConfig::default());
+    a.set_state(AssociationState::Established);
+    a.peer_verification_tag = 1234;
+    a.peer_last_tsn = 9;
+
+    for (tsn, stream_identifier) in [(10, 1), (11, 2), (14, 3)] {
+        a.handle_data(&ChunkPayloadData {
+            beginning_fragment: true,
+            tsn,
+            stream_identifier,
+            user_data: Bytes::from_static(b"ABC"),
+            ..Default::default()
+        })?;
+    }
+    assert_eq!(3, a.num_partial_messages);
+
+    a.use_forward_tsn = true;
+    a.handle_forward_tsn(&ChunkForwardTsn {
+        new_cumulative_tsn: 12,
+        streams: vec![ChunkForwardTsnStream {
+            identifier: 1,
+            sequence: 0,
+        }],
+    })?;
+    assert_eq!(2, a.num_partial_messages);
+
+    a.unregister_stream(3);
+    assert_eq!(1, a.num_partial_messages);
+    a.unregister_stream(3);
+    assert_eq!(1, a.num_partial_messages);
+    a.unregister_stream(2);
+    assert_eq!(0, a.num_partial_messages);
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_partial_delivery() -> Result<()> {
+    let mut a = create_association(TransportConfig::default().with_partial_delivery_point(4));
+    a.set_state(AssociationState::Established);
+    a.peer_verification_tag = 1234;
+    a.peer_last_tsn = 9;
+    while a.poll().is_some() {}
+
+    a.handle_data(&ChunkPayloadData {
+        beginning_fragment: true,
+        tsn: 10,
+        stream_identifier: 1,
+        user_data: Bytes::from_static(b"ABC"),
+        ..Default::default()
+    })?;
+    assert!(a.stream(1)?.read_partial()?.is_none(), "below the point");
+
+    a.handle_data(&ChunkPayloadData {
+        tsn: 11,
+        stream_identifier: 1,
+        user_data: Bytes::from_static(b"DEF"),
+        ..Default::default()
+    })?;
+    let mut started = false;
+    while let Some(e) = a.poll() {
+        if let Event::Stream(StreamEvent::PartialDeliveryStarted { id }) = e {
+            assert_eq!(1, id);
+            started = true;
+        }
+    }
+    assert!(started, "should report partial delivery");
+
+    let p = a.stream(1)?.read_partial()?.expect("should read a piece");
+    assert!(!p.end_of_record, "message is not complete");
+    let mut buf = [0u8; 16];
+    let n = p.chunks.read(&mut buf)?;
+    assert_eq!(&buf[..n], b"ABCDEF");
+
+    a.handle_data(&ChunkPayloadData {
+        ending_fragment: true,
+        tsn: 12,
+        stream_identifier: 1,
+        user_data: Bytes::from_static(b"G"),
+        ..Default::default()
+    })?;
+    let p = a
+        .stream(1)?
+        .read_partial()?
+        .expect("should read the last piece");
+    assert!(p.end_of_record, "message is complete");
+    let mut buf = [0u8; 16];
+    let n = p.chunks.read(&mut buf)?;
+    assert_eq!(&buf[..n], b"G");
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_partial_delivery_default_point() {
+    let a = create_association(TransportConfig::default().with_max_receive_buffer_size(1000));
+    assert_eq!(500, a.partial_delivery_point);
+
+    let a = create_association(TransportConfig::default().with_partial_delivery_point(0));
+    assert_eq!(0, a.partial_delivery_point);
+}
+
+#[test]
+fn test_assoc_partial_delivery_after_read() -> Result<()> {
+    let mut a = create_association(TransportConfig::default().with_partial_delivery_point(4));
+    a.set_state(AssociationState::Established);
+    a.peer_verification_tag = 1234;
+    a.peer_last_tsn = 9;
+
+    // The second message is large enough but waits for the first one
+    for (tsn, ssn, beginning, ending, data) in [
+        (10, 0, true, false, &b"AB"[..]),
+        (12, 1, true, false, &b"DEFG"[..]),
+        (11, 0, false, true, &b"C"[..]),
+    ] {
+        a.handle_data(&ChunkPayloadData {
+            beginning_fragment: beginning,
+            ending_fragment: ending,
+            tsn,
+            stream_identifier: 1,
+            stream_sequence_number: ssn,
+            user_data: Bytes::from_static(data),
+            ..Default::default()
+        })?;
+    }
+    while a.poll().is_some() {}
+
+    let chunks = a.stream(1)?.read()?.expect("should read the first message");
+    assert_eq!(3, chunks.len());
+    let mut started = false;
+    while let Some(e) = a.poll() {
+        if let Event::Stream(StreamEvent::PartialDeliveryStarted { id }) = e {
+            assert_eq!(1, id);
+            started = true;
+        }
+    }
+    assert!(started, "should start partial delivery without more DATA");
+
+    let p = a.stream(1)?.read_partial()?.expect("should read a piece");
+    assert!(!p.end_of_record, "message is not complete");
+    assert_eq!(4, p.chunks.len());
+
+    a.handle_data(&ChunkPayloadData {
+        ending_fragment: true,
+        tsn: 13,
+        stream_identifier: 1,
+        stream_sequence_number: 1,
+        user_data: Bytes::from_static(b"H"),
+        ..Default::default()
+    })?;
+    assert_eq!(
+        Err(Error::ErrPartialDeliveryInProgress),
+        a.stream(1)?.read().map(|_| ())
+    );
+    let p = a
+        .stream(1)?
+        .read_partial()?
+        .expect("should read the last piece");
+    assert!(p.end_of_record, "message is complete");
+    assert_eq!(1, p.chunks.len());
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_partial_delivery_read_whole() -> Result<()> {
+    let mut a = create_association(TransportConfig::default().with_partial_delivery_point(4));
+    a.set_state(AssociationState::Established);
+    a.peer_verification_tag = 1234;
+    a.peer_last_tsn = 9;
+
+    for (tsn, beginning, ending, data) in [(10, true, false, &b"ABCD"[..]), (11, false, true, b"E")]
+    {
+        a.handle_data(&ChunkPayloadData {
+            beginning_fragment: beginning,
+            ending_fragment: ending,
+            tsn,
+            stream_identifier: 1,
+            user_data: Bytes::from_static(data),
+            ..Default::default()
+        })?;
+    }
+    assert!(a.streams[&1].reassembly_queue.partial.is_some());
+
+    // Nothing has been read in pieces yet, so the message can be read whole
+    let chunks = a.stream(1)?.read()?.expect("should read the message");
+    assert_eq!(5, chunks.len());
+    assert!(a.streams[&1].reassembly_queue.partial.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_write_partial() -> Result<()> {
+    let mut a = create_association(TransportConfig::default().with_max_message_size(4));
+    a.max_payload_size = 4;
+
+    let ppi = PayloadProtocolIdentifier::Binary;
+    a.open_stream(2, ppi)?;
+    let mut s = a.open_stream(1, ppi)?;
+
+    let handle = s.write_partial(b"ABCDEF", ppi, false)?;
+    assert_eq!(
+        Err(Error::ErrMessageInProgress),
+        s.write_sctp(&Bytes::from_static(b"XY"), ppi),
+        "the stream is busy with the message"
+    );
+    assert_eq!(1, a.pending_queue.len(), "last fragment is held back");
+
+    a.stream(2)?.write_sctp(&Bytes::from_static(b"XY"), ppi)?;
+    assert_eq!(1, a.pending_queue.len(), "other messages are held back");
+    assert_eq!(6, a.buffered_amount(), "incorrect bufferedAmount");
+
+    let mut s = a.stream(1)?;
+    assert_eq!(handle, s.write_partial(b"GH", ppi, true)?, "same message");
+    assert_eq!(3, a.pending_queue.len(), "message should end");
+
+    let expected: [(&[u8], StreamId, bool, bool); 3] = [
+        (b"ABCD", 1, true, false),
+        (b"EFGH", 1, false, true),
+        (b"XY", 2, true, true),
+    ];
+    for (data, stream_identifier, beginning_fragment, ending_fragment) in expected {
+        let c = a
+            .pending_queue
+            .pop(beginning_fragment, false)
+            .expect("should have a chunk");
+        assert_eq!(data, &c.user_data[..]);
+        assert_eq!(stream_identifier, c.stream_identifier);
+        assert_eq!(beginning_fragment, c.beginning_fragment);
+        assert_eq!(ending_fragment, c.ending_fragment);
+        assert_eq!(0, c.stream_sequence_number);
+        if stream_identifier == 1 {
+            assert_eq!(Some(handle), c.message_handle);
+        }
+    }
+    assert_eq!(1, a.streams[&1].sequence_number, "SSN should advance");
+
+    // Empty messages send nothing
+    assert_ne!(handle, a.stream(1)?.write_partial(b"", ppi, true)?);
+    assert!(a.pending_queue.is_empty());
+    assert_eq!(1, a.streams[&1].sequence_number);
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_write_partial_two_streams() -> Result<()> {
+    let mut a = create_association(TransportConfig::default().with_max_message_size(4));
+    a.max_payload_size = 4;
+
+    let ppi = PayloadProtocolIdentifier::Binary;
+    a.open_stream(1, ppi)?;
+    a.open_stream(2, ppi)?;
+
+    a.stream(1)?.write_partial(b"ABCDEF", ppi, false)?;
+    assert_eq!(
+        Err(Error::ErrMessageInProgress),
+        a.stream(2)?.write_partial(b"XY", ppi, false),
+        "another stream is writing a message in pieces"
+    );
+    assert_eq!(
+        Err(Error::ErrMessageInProgress),
+        a.stream(2)?.write_partial(b"XY", ppi, true),
+        "ending a message on another stream must not release the held back chunks"
+    );
+    assert!(
+        a.streams[&2].partial_message.is_none(),
+        "stream 2 untouched"
+    );
+    assert_eq!(Some(1), a.partial_write_stream);
+
+    a.stream(1)?.write_partial(b"GH", ppi, true)?;
+    assert_eq!(None, a.partial_write_stream);
+    a.stream(2)?.write_partial(b"XY", ppi, true)?;
+
+    let expected: [(&[u8], StreamId); 3] = [(b"ABCD", 1), (b"EFGH", 1), (b"XY", 2)];
+    for (data, stream_identifier) in expected {
+        let c = a
+            .pending_queue
+            .pop(stream_identifier == 2 || data == b"ABCD", false)
+            .expect("should have a chunk");
+        assert_eq!(data, &c.user_data[..]);
+        assert_eq!(stream_identifier, c.stream_identifier);
+    }
+    assert!(a.pending_queue.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_write_partial_holds_back_same_ordering() -> Result<()> {
+    let mut a = create_association(
+        TransportConfig::default()
+            .with_max_message_size(4)
+            .with_max_send_buffer_size(10),
+    );
+    a.max_payload_size = 4;
+
+    let ppi = PayloadProtocolIdentifier::Binary;
+    a.open_stream(1, ppi)?;
+    a.open_stream(2, ppi)?
+        .set_reliability_params(true, ReliabilityType::Reliable, 0)?;
+    a.open_stream(3, ppi)?;
+
+    a.stream(1)?.write_partial(b"ABCDEF", ppi, false)?;
+    a.stream(2)?.write(b"XY")?;
+    assert!(a.deferred_payload.is_empty(), "unordered is not held back");
+    a.stream(3)?.write(b"ZW")?;
+    assert_eq!(1, a.deferred_payload.len(), "ordered is held back");
+
+    // Held back chunks use the send buffer
+    assert_eq!(8, a.buffered_amount());
+    assert_eq!(
+        Err(Error::ErrSendBufferFull),
+        a.stream(3)?.write(b"UVW").map(|_| ())
+    );
+
+    // The unordered message is sent before the partial one starts
+    let c = a
+        .pending_queue
+        .pop(true, true)
+        .expect("should have a chunk");
+    assert_eq!(&b"XY"[..], &c.user_data[..]);
+    assert_eq!(2, c.stream_identifier);
+
+    a.stream(1)?.write_partial(b"", ppi, true)?;
+    assert!(a.deferred_payload.is_empty());
+    let expected: [(&[u8], StreamId); 3] = [(b"ABCD", 1), (b"EF", 1), (b"ZW", 3)];
+    for (data, stream_identifier) in expected {
+        let c = a
+            .pending_queue
+            .pop(data != b"EF", false)
+            .expect("should have a chunk");
+        assert_eq!(data, &c.user_data[..]);
+        assert_eq!(stream_identifier, c.stream_identifier);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_write_bytes_without_copy() -> Result<()> {
+    let mut a = create_association(TransportConfig::default().with_max_message_size(4));
+
+    let mut s = a.open_stream(1, PayloadProtocolIdentifier::Binary)?;
+    let p = Bytes::from_static(b"ABCD");
+    let first = s.write_bytes(p.clone())?;
+    assert_eq!(
+        Err(Error::ErrOutboundPacketTooLarge),
+        s.write_messages(vec![
+            Bytes::from_static(b"EF"),
+            Bytes::from_static(b"GHIJK")
+        ]),
+        "should reject all the messages"
+    );
+    let handles = s.write_messages(vec![Bytes::from_static(b"EF"), Bytes::from_static(b"GHI")])?;
+    assert_eq!(2, handles.len(), "one handle per message");
+    assert!(!handles.contains(&first) && handles[0] != handles[1]);
+    assert_eq!(3, a.pending_queue.len(), "should queue all the messages");
+
+    let c = a
+        .pending_queue
+        .pop(true, false)
+        .expect("should have a chunk");
+    assert_eq!(p.as_ptr(), c.user_data.as_ptr(), "should share the buffer");
+    assert_eq!(Some(first), c.message_handle);
+    for ((data, ssn), handle) in [(&b"EF"[..], 1), (&b"GHI"[..], 2)].into_iter().zip(handles) {
+        let c = a
+            .pending_queue
+            .pop(true, false)
+            .expect("should have a chunk");
+        assert_eq!(data, &c.user_data[..]);
+        assert_eq!(ssn, c.stream_sequence_number);
+        assert_eq!(Some(handle), c.message_handle, "should be tracked");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_read_bytes() -> Result<()> {
+    let mut a = create_association(TransportConfig::default());
+    a.set_state(AssociationState::Established);
+    a.peer_verification_tag = 1234;
+    a.peer_last_tsn = 9;
+
+    let p = Bytes::from_static(b"ABC");
+    a.handle_data(&ChunkPayloadData {
+        beginning_fragment: true,
+        ending_fragment: true,
+        tsn: 10,
+        stream_identifier: 1,
+        payload_type: PayloadProtocolIdentifier::String,
+        user_data: p.clone(),
+        ..Default::default()
+    })?;
+
+    let (ppi, payload) = a.stream(1)?.read_bytes()?.expect("should read a message");
+    assert_eq!(PayloadProtocolIdentifier::String, ppi);
+    match payload {
+        MessagePayload::Bytes(b) => assert_eq!(p.as_ptr(), b.as_ptr(), "should share the buffer"),
+        MessagePayload::Fragments(_) => panic!("should not be fragmented"),
+    }
+    assert!(a.stream(1)?.read_bytes()?.is_none(), "nothing more to read");
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_read_any() -> Result<()> {
+    let mut a = create_association(TransportConfig::default());
+    a.set_state(AssociationState::Established);
+    a.peer_verification_tag = 1234;
+    a.peer_last_tsn = 9;
+
+    let messages = [(1, 0, false), (1, 1, false), (2, 0, true), (3, 0, false)];
+    for (i, (stream_identifier, ssn, unordered)) in messages.into_iter().enumerate() {
+        a.handle_data(&ChunkPayloadData {
+            unordered,
+            beginning_fragment: true,
+            ending_fragment: true,
+            tsn: 10 + i as u32,
+            stream_identifier,
+            stream_sequence_number: ssn,
+            payload_type: PayloadProtocolIdentifier::String,
+            user_data: Bytes::from_static(b"ABC"),
+            ..Default::default()
+        })?;
+    }
+
+    // Streams take turns
+    for (stream_identifier, ssn, unordered, tsn) in [
+        (1, 0, false, 10),
+        (2, 0, true, 12),
+        (3, 0, false, 13),
+        (1, 1, false, 11),
+    ] {
+        let m = a.read_any().expect("should read a message");
+        assert_eq!(stream_identifier, m.stream_identifier);
+        assert_eq!(PayloadProtocolIdentifier::String, m.ppi);
+        assert_eq!(unordered, m.unordered);
+        assert_eq!(tsn, m.tsn);
+        if !unordered {
+            assert_eq!(ssn, m.ssn);
+        }
+        assert_eq!(13, m.cumulative_tsn);
+        assert_eq!(3, m.chunks.len());
+    }
+    assert!(a.read_any().is_none(), "nothing more to read");
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_message_acked_and_abandoned() -> Result<()> {
+    let mut a = create_association(TransportConfig::default());
+    a.set_state(AssociationState::Established);
+    a.peer_verification_tag = 1234;
+    a.use_forward_tsn = true;
+    a.max_payload_size = 2;
+
+    let ppi = PayloadProtocolIdentifier::Binary;
+    let mut s = a.open_stream(1, ppi)?;
+    let acked = s.write(b"ABC")?;
+    let mut s = a.open_stream(2, ppi)?;
+    s.set_reliability_params(false, ReliabilityType::Rexmit, 0)?;
+    let abandoned = s.write(b"DEF")?;
+    assert_ne!(acked, abandoned, "handles should be unique");
+    while a.poll().is_some() {}
+
+    let now = Instant::now();
+    for beginning_fragment in [true, false, true, false] {
+        a.move_pending_data_chunk_to_inflight_queue(beginning_fragment, false, now)
+            .expect("should have a chunk");
+    }
+    match a.poll() {
+        Some(Event::MessageAbandoned { stream, handle }) => {
+            assert_eq!(2, stream);
+            assert_eq!(abandoned, handle);
+        }
+        ev => panic!("expected MessageAbandoned, got {:?}", ev),
+    }
+    assert!(a.poll().is_none(), "should report the message once");
+
+    // Acking the first fragment does not ack the message
+    let sack = |cumulative_tsn_ack| ChunkSelectiveAck {
+        cumulative_tsn_ack,
+        advertised_receiver_window_credit: 1500,
+        gap_ack_blocks: vec![],
+        duplicate_tsn: vec![],
+    };
+    let first_tsn = a.cumulative_tsn_ack_point.wrapping_add(1);
+    a.handle_sack(&sack(first_tsn), now)?;
+    assert!(
+        !std::iter::from_fn(|| a.poll()).any(|e| matches!(e, Event::MessageAcked { .. })),
+        "message is not fully acked"
+    );
+    assert!(
+        a.abandoned_messages.contains(&abandoned),
+        "kept until the FORWARD TSN is acked"
+    );
+
+    a.handle_sack(&sack(first_tsn.wrapping_add(3)), now)?;
+    let acks: Vec<(StreamId, MessageHandle)> = std::iter::from_fn(|| a.poll())
+        .filter_map(|e| match e {
+            Event::MessageAcked { stream, handle } => Some((stream, handle)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        vec![(1, acked)],
+        acks,
+        "only the delivered message is acked"
+    );
+    assert!(a.abandoned_messages.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_cancel_and_discard_pending() -> Result<()> {
+    let mut a = create_association(TransportConfig::default());
+    a.set_state(AssociationState::Established);
+    a.peer_verification_tag = 1234;
+
+    let ppi = PayloadProtocolIdentifier::Binary;
+    let mut s = a.open_stream(1, ppi)?;
+    let first = s.write(b"ABC")?;
+    let second = s.write(b"DEF")?;
+    s.write(b"GHI")?;
+    let now = Instant::now();
+    a.move_pending_data_chunk_to_inflight_queue(true, false, now)
+        .expect("should send the first message");
+    assert_eq!(9, a.streams[&1].buffered_amount);
+
+    let mut s = a.stream(1)?;
+    assert!(!s.cancel(first)?, "already sent");
+    assert!(s.cancel(second)?, "should cancel");
+    assert!(!s.cancel(second)?, "already cancelled");
+    assert_eq!(6, a.streams[&1].buffered_amount);
+    assert_eq!(2, a.streams[&1].sequence_number);
+
+    let c = a.pending_queue.peek().expect("should have a message");
+    assert_eq!(&b"GHI"[..], &c.user_data[..]);
+    assert_eq!(1, c.stream_sequence_number, "should take the freed SSN");
+
+    let mut s = a.stream(1)?;
+    s.write(b"JKL")?;
+    assert_eq!(2, s.discard_pending()?);
+    assert!(a.pending_queue.is_empty());
+    assert_eq!(3, a.streams[&1].buffered_amount);
+    assert_eq!(1, a.streams[&1].sequence_number);
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_max_queue_age() -> Result<()> {
+    let mut a = create_association(TransportConfig::default());
+    a.set_state(AssociationState::Established);
+    a.peer_verification_tag = 1234;
+    let ppi = PayloadProtocolIdentifier::Binary;
+    let mut s = a.open_stream(1, ppi)?;
+    s.set_max_queue_age(Some(Duration::from_millis(100)))?;
+    let stale = s.write(b"ABC")?;
+    a.open_stream(2, ppi)?.write(b"DEF")?;
+    while a.poll().is_some() {}
+
+    let now = Instant::now();
+    a.expire_pending_messages(now);
+    assert_eq!(2, a.pending_queue.len(), "should not expire yet");
+    let expiry = now + Duration::from_millis(100);
+    assert_eq!(Some(expiry), a.next_queue_expiry);
+    assert_eq!(Some(expiry), a.poll_timeout(), "should wake up to expire");
+
+    // Later messages do not move the expiry of the stale one
+    let later = now + Duration::from_millis(50);
+    let mut s = a.stream(1)?;
+    s.write(b"GHI")?;
+    a.expire_pending_messages(later);
+    assert_eq!(Some(expiry), a.next_queue_expiry);
+    while a.poll().is_some() {}
+
+    a.handle_timeout(expiry);
+    assert_eq!(2, a.pending_queue.len(), "should expire the stale message");
+    assert_eq!(
+        Some(later + Duration::from_millis(100)),
+        a.next_queue_expiry,
+        "should wait for the next message"
+    );
+    assert_eq!(2, a.pending_queue.peek().unwrap().stream_identifier);
+    let abandoned: Vec<(StreamId, MessageHandle)> = std::iter::from_fn(|| a.poll())
+        .filter_map(|e| match e {
+            Event::MessageAbandoned { stream, handle } => Some((stream, handle)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(vec![(1, stale)], abandoned);
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_close_reports_unacked_messages() -> Result<()> {
+    let mut a = create_association(TransportConfig::default());
+    a.set_state(AssociationState::Established);
+    a.peer_verification_tag = 1234;
+
+    a.max_payload_size = 3;
+
+    let ppi = PayloadProtocolIdentifier::Binary;
+    let gap_acked = a.open_stream(2, ppi)?.write(b"JKLMNO")?;
+    let mut s = a.open_stream(1, ppi)?;
+    let sent = s.write(b"ABC")?;
+    let queued = s.write(b"DEF")?;
+    let zero_copy = s.write_bytes(Bytes::from_static(b"GHI"))?;
+    for beginning_fragment in [true, false, true] {
+        a.move_pending_data_chunk_to_inflight_queue(beginning_fragment, false, Instant::now())
+            .expect("should send the first messages");
+    }
+    // Only the last fragment of the first message got through
+    a.inflight_queue
+        .mark_as_acked(a.cumulative_tsn_ack_point.wrapping_add(2));
+    while a.poll().is_some() {}
+
+    a.close()?;
+    let messages = match a.poll() {
+        Some(Event::SendFailed { messages }) => messages,
+        ev => panic!("expected SendFailed, got {:?}", ev),
+    };
+    let expected = [
+        (2, &b"JKLMNO"[..], true, Some(gap_acked)),
+        (1, &b"ABC"[..], true, Some(sent)),
+        (1, &b"DEF"[..], false, Some(queued)),
+        (1, &b"GHI"[..], false, Some(zero_copy)),
+    ];
+    assert_eq!(expected.len(), messages.len());
+    for (m, (stream_identifier, payload, was_sent, handle)) in messages.iter().zip(expected) {
+        assert_eq!(stream_identifier, m.stream_identifier);
+        assert_eq!(ppi, m.ppi);
+        assert_eq!(payload, &m.payload[..]);
+        assert_eq!(was_sent, m.sent);
+        assert_eq!(handle, m.handle);
+    }
+    assert_eq!(0, a.buffered_amount(), "queues should be empty");
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_close_skips_partly_acked_messages() -> Result<()> {
+    let mut a = create_association(TransportConfig::default());
+    a.set_state(AssociationState::Established);
+    a.peer_verification_tag = 1234;
+
+    a.max_payload_size = 3;
+
+    let ppi = PayloadProtocolIdentifier::Binary;
+    let mut s = a.open_stream(1, ppi)?;
+    s.write(b"ABCDEF")?;
+    let unacked = s.write(b"GHI")?;
+    let now = Instant::now();
+    for beginning_fragment in [true, false, true] {
+        a.move_pending_data_chunk_to_inflight_queue(beginning_fragment, false, now)
+            .expect("should send the messages");
+    }
+
+    // The first fragment of the first message is cumulatively acked
+    let first_tsn = a.cumulative_tsn_ack_point.wrapping_add(1);
+    a.handle_sack(
+        &ChunkSelectiveAck {
+            cumulative_tsn_ack: first_tsn,
+            advertised_receiver_window_credit: 1500,
+            gap_ack_blocks: vec![],
+            duplicate_tsn: vec![],
+        },
+        now,
+    )?;
+    while a.poll().is_some() {}
+
+    a.close()?;
+    let messages = match a.poll() {
+        Some(Event::SendFailed { messages }) => messages,
+        ev => panic!("expected SendFailed, got {:?}", ev),
+    };
+    assert_eq!(
+        1,
+        messages.len(),
+        "the partly acked message is not reported"
+    );
+    assert_eq!(&b"GHI"[..], &messages[0].payload[..]);
+    assert_eq!(Some(unacked), messages[0].handle);
+
+    Ok(())
+}
+
+/// Returns the first packet sent by `a`
+fn poll_transmit_packet(a: &mut Association, now: Instant) -> Result<Packet> {
+    let t = a.poll_transmit(now).expect("should send a packet");
+    match t.payload {
+        Payload::RawEncode(contents) => Packet::unmarshal(&contents[0]),
+        _ => panic!("expected a raw packet"),
+    }
+}
+
+#[test]
+fn test_assoc_graceful_shutdown() -> Result<()> {
+    let mut a = create_association(TransportConfig::default());
+    a.set_state(AssociationState::Established);
+    a.peer_verification_tag = 1234;
+    a.control_queue.clear();
+
+    let mut s = a.open_stream(1, PayloadProtocolIdentifier::Binary)?;
+    s.write(b"ABC")?;
+    while a.poll().is_some() {}
+
+    let now = Instant::now();
+    a.move_pending_data_chunk_to_inflight_queue(true, false, now)
+        .expect("should send the message");
+    a.shutdown()?;
+    assert_eq!(AssociationState::ShutdownPending, a.state());
+    assert!(
+        a.poll_endpoint_event().is_none(),
+        "should not be drained before the SHUTDOWN exchange"
+    );
+
+    // SHUTDOWN goes out once the outstanding data is acked
+    a.handle_sack(
+        &ChunkSelectiveAck {
+            cumulative_tsn_ack: a.cumulative_tsn_ack_point.wrapping_add(1),
+            advertised_receiver_window_credit: 1500,
+            gap_ack_blocks: vec![],
+            duplicate_tsn: vec![],
+        },
+        now,
+    )?;
+    assert_eq!(AssociationState::ShutdownSent, a.state());
+    let chunks = poll_transmit_packet(&mut a, now)?.chunks;
+    let shutdown = chunks[0]
+        .as_any()
+        .downcast_ref::<ChunkShutdown>()
+        .expect("should send SHUTDOWN");
+    assert_eq!(a.peer_last_tsn, shutdown.cumulative_tsn_ack);
+    assert!(a.timers.get(Timer::T2Shutdown).is_some());
+    assert!(a.timers.get(Timer::T5ShutdownGuard).is_some());
+    while a.poll().is_some() {}
+
+    a.handle_shutdown_ack(&ChunkShutdownAck {})?;
+    let chunks = poll_transmit_packet(&mut a, now)?.chunks;
+    assert!(chunks[0]
+        .as_any()
+        .downcast_ref::<ChunkShutdownComplete>()
+        .is_some());
+    assert!(matches!(a.poll(), Some(Event::ShutdownComplete)));
+    assert!(a.is_closed());
+    assert!(a
+        .poll_endpoint_event()
+        .expect("should be drained")
+        .is_drained());
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_shutdown_received_acks_outstanding_data() -> Result<()> {
+    let mut a = create_association(TransportConfig::default());
+    a.set_state(AssociationState::Established);
+    a.peer_verification_tag = 1234;
+    a.control_queue.clear();
+
+    let mut s = a.open_stream(1, PayloadProtocolIdentifier::Binary)?;
+    let handle = s.write(b"ABC")?;
+    while a.poll().is_some() {}
+
+    let now = Instant::now();
+    a.move_pending_data_chunk_to_inflight_queue(true, false, now)
+        .expect("should send the message");
+    let tsn = a.cumulative_tsn_ack_point.wrapping_add(1);
+
+    // A SHUTDOWN that does not cover the data waits for it to be acked
+    a.handle_shutdown(
+        &ChunkShutdown {
+            cumulative_tsn_ack: a.cumulative_tsn_ack_point,
+        },
+        now,
+    )?;
+    assert_eq!(AssociationState::ShutdownReceived, a.state());
+
+    a.handle_shutdown(
+        &ChunkShutdown {
+            cumulative_tsn_ack: tsn,
+        },
+        now,
+    )?;
+    assert_eq!(AssociationState::ShutdownAckSent, a.state());
+    assert_eq!(tsn, a.cumulative_tsn_ack_point);
+    assert!(a.inflight_queue.is_empty());
+    let events: Vec<Event> = std::iter::from_fn(|| a.poll()).collect();
+    assert!(events
+        .iter()
+        .any(|e| matches!(e, Event::MessageAcked { stream: 1, handle: h } if *h == handle)));
+
+    let chunks = poll_transmit_packet(&mut a, now)?.chunks;
+    assert!(chunks[0]
+        .as_any()
+        .downcast_ref::<ChunkShutdownAck>()
+        .is_some());
+
+    a.handle_shutdown_complete(&ChunkShutdownComplete {})?;
+    assert!(matches!(a.poll(), Some(Event::ShutdownComplete)));
+    assert!(a.is_closed());
+    assert!(a
+        .poll_endpoint_event()
+        .expect("should be drained")
+        .is_drained());
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_shutdown_guard_timer() -> Result<()> {
+    let mut a = create_association(TransportConfig::default());
+    a.set_state(AssociationState::Established);
+    a.peer_verification_tag = 1234;
+    a.control_queue.clear();
+
+    a.timers.stop(Timer::T1Init);
+
+    let now = Instant::now();
+    a.shutdown()?;
+    assert_eq!(AssociationState::ShutdownSent, a.state());
+    poll_transmit_packet(&mut a, now)?;
+
+    // The peer never answers the SHUTDOWN
+    a.handle_timeout(now + Duration::from_millis(T5_SHUTDOWN_GUARD));
+    assert!(a.is_closed());
+    let chunks = poll_transmit_packet(&mut a, now)?.chunks;
+    assert!(chunks[0].as_any().downcast_ref::<ChunkAbort>().is_some());
+    assert!(matches!(
+        a.poll(),
+        Some(Event::AssociationLost {
+            reason: AssociationError::TimedOut
+        })
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_linger_after_close() -> Result<()> {
+    let linger = Duration::from_secs(1);
+    let mut a = create_association(TransportConfig::default().with_linger_timeout(linger));
+    a.set_state(AssociationState::Established);
+    a.peer_verification_tag = 1234;
+    a.control_queue.clear();
+    a.timers.stop(Timer::T1Init);
+
+    let now = Instant::now();
+    a.close()?;
+    assert!(a.poll_transmit(now).is_none());
+    assert!(
+        a.poll_endpoint_event().is_none(),
+        "should not be drained while lingering"
+    );
+    assert_eq!(Some(now + linger), a.poll_timeout());
+
+    let inbound = |a: &Association, chunk: Box<dyn Chunk + Send + Sync>| -> Result<Packet> {
+        let p = Packet {
+            common_header: CommonHeader {
+                source_port: 5000,
+                destination_port: 5000,
+                verification_tag: a.my_verification_tag,
+            },
+            chunks: vec![chunk],
+        };
+        Packet::unmarshal(&p.marshal()?)
+    };
+
+    // A retransmitted SHUTDOWN-ACK is answered with SHUTDOWN-COMPLETE
+    a.handle_inbound(inbound(&a, Box::new(ChunkShutdownAck {}))?, now)?;
+    let chunks = poll_transmit_packet(&mut a, now)?.chunks;
+    assert!(chunks[0]
+        .as_any()
+        .downcast_ref::<ChunkShutdownComplete>()
+        .is_some());
+
+    // A retransmitted DATA chunk is answered with ABORT
+    let data = ChunkPayloadData {
+        beginning_fragment: true,
+        ending_fragment: true,
+        tsn: a.peer_last_tsn.wrapping_add(1),
+        stream_identifier: 1,
+        user_data: Bytes::from_static(b"ABC"),
+        ..Default::default()
+    };
+    a.handle_inbound(inbound(&a, Box::new(data))?, now)?;
+    let chunks = poll_transmit_packet(&mut a, now)?.chunks;
+    assert!(chunks[0].as_any().downcast_ref::<ChunkAbort>().is_some());
+
+    // An ABORT is not answered
+    a.handle_inbound(
+        inbound(
+            &a,
+            Box::new(ChunkAbort {
+                error_causes: vec![],
+            }),
+        )?,
+        now,
+    )?;
+    assert!(a.poll_transmit(now).is_none());
+
+    a.handle_timeout(now + linger);
+    assert!(a
+        .poll_endpoint_event()
+        .expect("should be drained once the linger expires")
+        .is_drained());
+    assert!(a.poll_timeout().is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_negotiated_parameters() -> Result<()> {
+    let mut a = create_association(TransportConfig::default().with_max_num_inbound_streams(20));
+    assert!(a.negotiated().is_none());
+
+    // The peer does not list FORWARD TSN
+    let init = ChunkInit {
+        initial_tsn: 100,
+        num_outbound_streams: 30,
+        num_inbound_streams: 5,
+        initiate_tag: 1234,
+        advertised_receiver_window_credit: 1500,
+        params: vec![Box::new(ParamSupportedExtensions {
+            chunk_types: vec![CT_RECONFIG],
+        })],
+        ..Default::default()
+    };
+    let p = Packet {
+        common_header: CommonHeader {
+            source_port: 5001,
+            destination_port: 5002,
+            verification_tag: 0,
+        },
+        chunks: vec![Box::new(init.clone())],
+    };
+    a.handle_init(&p, &init)?;
+
+    let expected = NegotiatedParameters {
+        use_forward_tsn: false,
+        use_reconfig: true,
+        num_outbound_streams: 5,
+        num_inbound_streams: 20,
+        peer_receiver_window: 1500,
+        peer_supported_extensions: SupportedExtensions {
+            reconfig: true,
+            ..Default::default()
+        },
+        local_port: 5002,
+        peer_port: 5001,
+        local_verification_tag: a.my_verification_tag,
+        peer_verification_tag: 1234,
+    };
+    assert_eq!(Some(&expected), a.negotiated());
+
+    let mut s = a.open_stream(1, PayloadProtocolIdentifier::Binary)?;
+    assert_eq!(
+        Err(Error::ErrForwardTsnNotSupported),
+        s.set_reliability_params(false, ReliabilityType::Rexmit, 0)
+    );
+    s.set_reliability_params(true, ReliabilityType::Reliable, 0)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_supported_extensions() -> Result<()> {
+    let mut a = create_association(
+        TransportConfig::default()
+            .with_forward_tsn(false)
+            .with_reconfig(false),
+    );
+    a.set_state(AssociationState::Established);
+    a.peer_verification_tag = 1234;
+
+    // Nothing is advertised when every extension is turned off
+    let init = a.stored_init.as_ref().expect("should have sent INIT");
+    assert!(
+        !init
+            .params
+            .iter()
+            .any(|p| p.as_any().is::<ParamSupportedExtensions>()),
+        "should not list supported extensions"
+    );
+
+    // RECONFIG is not used unless both sides support it
+    let packets = a.handle_reconfig(&ChunkReconfig::default())?;
+    assert!(packets[0].chunks[0]
+        .as_any()
+        .downcast_ref::<ChunkError>()
+        .is_some());
+
+    let mut s = a.open_stream(1, PayloadProtocolIdentifier::Binary)?;
+    assert_eq!(Err(Error::ErrReconfigNotSupported), s.stop());
+    assert_eq!(
+        Err(Error::ErrForwardTsnNotSupported),
+        s.set_reliability_params(false, ReliabilityType::Timed, 10)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_partial_reliability_without_forward_tsn() -> Result<()> {
+    let mut a = create_association(TransportConfig::default());
+    a.set_state(AssociationState::CookieWait);
+
+    // Partial reliability is allowed until the peer's INIT shows otherwise
+    let ppi = PayloadProtocolIdentifier::Binary;
+    a.open_stream(1, ppi)?
+        .set_reliability_params(true, ReliabilityType::Rexmit, 0)?;
+    a.open_stream(2, ppi)?;
+    a.open_stream(3, ppi)?
+        .set_reliability_params(false, ReliabilityType::Timed, 10)?;
+    while a.poll().is_some() {}
+
+    // The peer does not list FORWARD TSN
+    let pkt = Packet {
+        common_header: CommonHeader {
+            source_port: 5000,
+            destination_port: 5000,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let init = ChunkInit {
+        initial_tsn: 1234,
+        num_outbound_streams: 10,
+        num_inbound_streams: 10,
+        initiate_tag: 5678,
+        advertised_receiver_window_credit: 512 * 1024,
+        ..Default::default()
+    };
+    a.handle_init(&pkt, &init)?;
+    assert!(!a.use_forward_tsn);
+
+    let disabled: Vec<StreamId> = std::iter::from_fn(|| a.poll())
+        .filter_map(|e| match e {
+            Event::Stream(StreamEvent::PartialReliabilityDisabled { id }) => Some(id),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(vec![1, 3], disabled, "should report the PR-SCTP streams");
+    for si in [1, 3] {
+        let s = &a.streams[&si];
+        assert_eq!(ReliabilityType::Reliable, s.reliability_type);
+        assert_eq!(0, s.reliability_value);
+    }
+    assert!(a.streams[&1].unordered, "ordering should be kept");
+
+    Ok(())
+}
@@ -3,37 +3,63 @@ use crate::association::{
     stats::AssociationStats,
 };
 use crate::chunk::{
-    chunk_abort::ChunkAbort, chunk_cookie_ack::ChunkCookieAck, chunk_cookie_echo::ChunkCookieEcho,
-    chunk_error::ChunkError, chunk_forward_tsn::ChunkForwardTsn,
-    chunk_forward_tsn::ChunkForwardTsnStream, chunk_heartbeat::ChunkHeartbeat,
-    chunk_heartbeat_ack::ChunkHeartbeatAck, chunk_init::ChunkInit, chunk_init::ChunkInitAck,
-    chunk_payload_data::ChunkPayloadData, chunk_payload_data::PayloadProtocolIdentifier,
-    chunk_reconfig::ChunkReconfig, chunk_selective_ack::ChunkSelectiveAck,
-    chunk_shutdown::ChunkShutdown, chunk_shutdown_ack::ChunkShutdownAck,
-    chunk_shutdown_complete::ChunkShutdownComplete, chunk_type::CT_FORWARD_TSN, Chunk,
-    ErrorCauseUnrecognizedChunkType, USER_INITIATED_ABORT,
+    chunk_abort::ChunkAbort,
+    chunk_cookie_ack::ChunkCookieAck,
+    chunk_cookie_echo::ChunkCookieEcho,
+    chunk_error::ChunkError,
+    chunk_forward_tsn::ChunkForwardTsn,
+    chunk_forward_tsn::ChunkForwardTsnStream,
+    chunk_header::CHUNK_HEADER_SIZE,
+    chunk_heartbeat::ChunkHeartbeat,
+    chunk_heartbeat_ack::ChunkHeartbeatAck,
+    chunk_init::ChunkInit,
+    chunk_init::ChunkInitAck,
+    chunk_payload_data::ChunkPayloadData,
+    chunk_payload_data::PayloadProtocolIdentifier,
+    chunk_reconfig::ChunkReconfig,
+    chunk_selective_ack::{ChunkSelectiveAck, SELECTIVE_ACK_HEADER_SIZE},
+    chunk_shutdown::ChunkShutdown,
+    chunk_shutdown_ack::ChunkShutdownAck,
+    chunk_shutdown_complete::ChunkShutdownComplete,
+    chunk_type::{
+        ChunkType, CT_ASCONF, CT_ASCONF_ACK, CT_AUTH, CT_FORWARD_TSN, CT_I_DATA, CT_PAYLOAD_DATA,
+        CT_RECONFIG,
+    },
+    chunk_unknown::ChunkUnknown,
+    Chunk, ErrorCause, INVALID_STREAM_IDENTIFIER,
+};
+use crate::config::{
+    ServerConfig, TransportConfig, COMMON_HEADER_SIZE, DATA_CHUNK_HEADER_SIZE,
+    DEFAULT_SUPPORTED_EXTENSIONS,
 };
-use crate::config::{ServerConfig, TransportConfig, COMMON_HEADER_SIZE, DATA_CHUNK_HEADER_SIZE};
 use crate::error::{Error, Result};
-use crate::packet::{CommonHeader, Packet};
+use crate::packet::{CommonHeader, Packet, PacketBuilder};
 use crate::param::{
     param_heartbeat_info::ParamHeartbeatInfo,
     param_outgoing_reset_request::ParamOutgoingResetRequest,
     param_reconfig_response::{ParamReconfigResponse, ReconfigResult},
     param_state_cookie::ParamStateCookie,
     param_supported_extensions::ParamSupportedExtensions,
+    param_type::ParamType,
+    param_uknown::ParamUnknown,
+    param_unrecognized::ParamUnrecognized,
     Param,
 };
-use crate::queue::{payload_queue::PayloadQueue, pending_queue::PendingQueue};
+use crate::queue::{
+    payload_queue::PayloadQueue,
+    pending_queue::{remove_messages, PendingBaseQueue, PendingQueue},
+    reassembly_queue::Chunks,
+};
 use crate::shared::{AssociationEventInner, AssociationId, EndpointEvent, EndpointEventInner};
-use crate::util::{sna16lt, sna32gt, sna32gte, sna32lt, sna32lte};
+use crate::util::{get_padding_size, sna16lt, sna32gt, sna32gte, sna32lt, sna32lte};
 use crate::{AssociationEvent, Payload, Side, Transmit};
-use stream::{ReliabilityType, Stream, StreamEvent, StreamId, StreamState};
-use timer::{RtoManager, Timer, TimerTable, ACK_INTERVAL};
+use pacer::Pacer;
+use stream::{MessageHandle, ReliabilityType, Stream, StreamEvent, StreamId, StreamState};
+use timer::{RtoManager, Timer, TimerTable, ACK_INTERVAL, T5_SHUTDOWN_GUARD};
 
 use crate::association::stream::RecvSendState;
-use bytes::Bytes;
-use fxhash::FxHashMap;
+use bytes::{Bytes, BytesMut};
+use fxhash::{FxHashMap, FxHashSet};
 use log::{debug, error, trace, warn};
 use rand::random;
 use std::collections::{HashMap, VecDeque};
@@ -43,6 +69,7 @@ use std::sync::Arc;
 use std::time::{Duration, Instant};
 use thiserror::Error;
 
+mod pacer;
 pub(crate) mod state;
 pub(crate) mod stats;
 pub(crate) mod stream;
@@ -96,6 +123,144 @@ pub enum Event {
     Stream(StreamEvent),
     /// One or more application datagrams have been received
     DatagramReceived,
+    /// The peer reported errors in an ERROR chunk. These are not fatal to the
+    /// association.
+    RemoteError {
+        /// The error causes carried by the chunk
+        causes: Vec<ErrorCause>,
+    },
+    /// Messages could not be delivered, as the handshake failed or the
+    /// association was closed or aborted before they were acknowledged
+    SendFailed {
+        /// The messages, in the order they were written on each stream
+        messages: Vec<FailedMessage>,
+    },
+    /// All the fragments of a message were acknowledged by the peer
+    MessageAcked {
+        /// Stream the message was written to
+        stream: StreamId,
+        /// Handle returned when the message was written
+        handle: MessageHandle,
+    },
+    /// A message was abandoned under partial reliability, so the peer may not
+    /// receive it
+    MessageAbandoned {
+        /// Stream the message was written to
+        stream: StreamId,
+        /// Handle returned when the message was written
+        handle: MessageHandle,
+    },
+    /// The SHUTDOWN exchange completed, after all outstanding data was
+    /// acknowledged. The association is closed.
+    ShutdownComplete,
+}
+
+/// A message that could not be delivered to the peer
+#[derive(Debug, Clone, PartialEq)]
+pub struct FailedMessage {
+    /// Stream the message was written to
+    pub stream_identifier: StreamId,
+    /// Payload protocol identifier the message was written with
+    pub ppi: PayloadProtocolIdentifier,
+    /// The message payload
+    pub payload: Bytes,
+    /// Whether any fragment of the message was put on the wire
+    pub sent: bool,
+    /// Handle returned when the message was written
+    pub handle: Option<MessageHandle>,
+}
+
+/// A message read with `Association::read_any`, along with the information
+/// `recvmsg` reports in `sctp_rcvinfo` (RFC 6458 sec 5.3.5)
+#[derive(Debug)]
+pub struct ReceivedMessage {
+    /// Stream the message was received on
+    pub stream_identifier: StreamId,
+    /// Payload protocol identifier of the message
+    pub ppi: PayloadProtocolIdentifier,
+    /// Whether the message was sent unordered
+    pub unordered: bool,
+    /// Stream sequence number of the message, meaningless if unordered
+    pub ssn: u16,
+    /// TSN of the first fragment of the message
+    pub tsn: u32,
+    /// Cumulative TSN received from the peer when the message was read
+    pub cumulative_tsn: u32,
+    /// The message payload
+    pub chunks: Chunks,
+}
+
+/// Protocol extensions listed in a Supported Extensions parameter (RFC 5061 sec 4.2.7)
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct SupportedExtensions {
+    /// Partial reliability with the FORWARD TSN chunk (RFC 3758)
+    pub forward_tsn: bool,
+    /// Stream reconfiguration with the RE-CONFIG chunk (RFC 6525)
+    pub reconfig: bool,
+    /// User message interleaving with the I-DATA chunk (RFC 8260)
+    pub i_data: bool,
+    /// Authenticated chunks with the AUTH chunk (RFC 4895)
+    pub auth: bool,
+    /// Dynamic address reconfiguration with the ASCONF and ASCONF-ACK chunks (RFC 5061)
+    pub asconf: bool,
+}
+
+impl SupportedExtensions {
+    pub(crate) fn from_chunk_types(chunk_types: &[ChunkType]) -> Self {
+        SupportedExtensions {
+            forward_tsn: chunk_types.contains(&CT_FORWARD_TSN),
+            reconfig: chunk_types.contains(&CT_RECONFIG),
+            i_data: chunk_types.contains(&CT_I_DATA),
+            auth: chunk_types.contains(&CT_AUTH),
+            asconf: chunk_types.contains(&CT_ASCONF) && chunk_types.contains(&CT_ASCONF_ACK),
+        }
+    }
+
+    pub(crate) fn chunk_types(&self) -> Vec<ChunkType> {
+        let mut chunk_types = vec![];
+        if self.reconfig {
+            chunk_types.push(CT_RECONFIG);
+        }
+        if self.forward_tsn {
+            chunk_types.push(CT_FORWARD_TSN);
+        }
+        if self.i_data {
+            chunk_types.push(CT_I_DATA);
+        }
+        if self.auth {
+            chunk_types.push(CT_AUTH);
+        }
+        if self.asconf {
+            chunk_types.extend([CT_ASCONF, CT_ASCONF_ACK]);
+        }
+        chunk_types
+    }
+}
+
+/// Parameters of an association as negotiated with the peer through the INIT
+/// and INIT ACK chunks
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct NegotiatedParameters {
+    /// Whether partial reliability is used, as both sides support FORWARD TSN
+    pub use_forward_tsn: bool,
+    /// Whether streams can be reset, as both sides support RECONFIG
+    pub use_reconfig: bool,
+    /// Number of outbound streams that may be used
+    pub num_outbound_streams: u16,
+    /// Number of inbound streams the peer may use
+    pub num_inbound_streams: u16,
+    /// Receiver window the peer advertised in its INIT or INIT ACK
+    pub peer_receiver_window: u32,
+    /// Extensions the peer listed in its INIT or INIT ACK
+    pub peer_supported_extensions: SupportedExtensions,
+    /// Local SCTP port
+    pub local_port: u16,
+    /// SCTP port of the peer
+    pub peer_port: u16,
+    /// Verification tag the peer puts in the packets it sends
+    pub local_verification_tag: u32,
+    /// Verification tag put in the packets sent to the peer
+    pub peer_verification_tag: u32,
 }
 
 ///Association represents an SCTP association
@@ -121,7 +286,12 @@ pub struct Association {
     state: AssociationState,
     handshake_completed: bool,
     max_message_size: u32,
-    inflight_queue_length: usize,
+    max_send_buffer_size: u32,
+    max_receive_message_size: u32,
+    max_num_partial_messages: u32,
+    /// Partial messages in the reassembly queues of all streams
+    num_partial_messages: usize,
+    partial_delivery_point: u32,
     will_send_shutdown: bool,
     bytes_received: usize,
     bytes_sent: usize,
@@ -138,6 +308,8 @@ pub struct Association {
 
     will_send_shutdown_ack: bool,
     will_send_shutdown_complete: bool,
+    will_send_zero_window_probe: bool,
+    will_send_cookie_echo: bool,
 
     // Reconfig
     my_next_rsn: u32,
@@ -158,6 +330,20 @@ pub struct Association {
     pending_queue: PendingQueue,
     control_queue: VecDeque<Packet>,
     stream_queue: VecDeque<u16>,
+    // last stream read by read_any, which takes turns between streams
+    last_read_stream: Option<StreamId>,
+    next_message_handle: u64,
+    // messages reported abandoned, until the cumulative TSN ack passes their
+    // ending fragment, i.e. once the FORWARD TSN skipping it is acknowledged
+    abandoned_messages: FxHashSet<MessageHandle>,
+    // stream of the message being written in pieces, and the chunks of other
+    // messages that would interleave with its fragments, held back until it ends
+    partial_write_stream: Option<StreamId>,
+    deferred_payload: PendingBaseQueue,
+    // when the next queued message exceeds the max queue age of its stream, to
+    // be recomputed once messages are written or the max queue age changes
+    next_queue_expiry: Option<Instant>,
+    pub(crate) queue_expiry_stale: bool,
 
     pub(crate) mtu: u32,
     // max DATA chunk payload size
@@ -165,6 +351,8 @@ pub struct Association {
     cumulative_tsn_ack_point: u32,
     advanced_peer_tsn_ack_point: u32,
     use_forward_tsn: bool,
+    supported_extensions: SupportedExtensions,
+    negotiated: Option<NegotiatedParameters>,
 
     pub(crate) rto_mgr: RtoManager,
     timers: TimerTable,
@@ -175,6 +363,27 @@ pub struct Association {
     pub(crate) cwnd: u32,
     // calculated peer's receiver windows size
     rwnd: u32,
+    // receiver window credit last advertised to the peer
+    advertised_rwnd: u32,
+    // max number of new DATA packets sent per SACK received (0: unlimited)
+    max_burst: u32,
+    // new DATA packets that may still be sent until the next SACK
+    burst_budget: u32,
+    // last time a DATA chunk was transmitted, for cwnd validation after idle
+    last_data_sent: Option<Instant>,
+
+    // Pacing
+    pacing: bool,
+    pacer: Pacer,
+    paced_packets: VecDeque<Bytes>,
+    next_pacing_slot: Option<Instant>,
+    /// Slot of the last batch of paced packets released
+    last_pacing_slot: Option<Instant>,
+
+    // Time to linger in the Closed state before draining (zero: no linger)
+    linger_timeout: Duration,
+    // Whether the association was closed and lingers before draining
+    lingering: bool,
     // slow start threshold
     pub(crate) ssthresh: u32,
     partial_bytes_acked: u32,
@@ -184,6 +393,7 @@ pub struct Association {
     // Chunks stored for retransmission
     stored_init: Option<ChunkInit>,
     stored_cookie_echo: Option<ChunkCookieEcho>,
+    stored_cookie_echo_error: Option<ChunkError>,
     pub(crate) streams: FxHashMap<StreamId, StreamState>,
 
     events: VecDeque<Event>,
@@ -208,7 +418,11 @@ impl Default for Association {
             state: AssociationState::default(),
             handshake_completed: false,
             max_message_size: 0,
-            inflight_queue_length: 0,
+            max_send_buffer_size: 0,
+            max_receive_message_size: 0,
+            max_num_partial_messages: 0,
+            num_partial_messages: 0,
+            partial_delivery_point: 0,
             will_send_shutdown: false,
             bytes_received: 0,
             bytes_sent: 0,
@@ -225,6 +439,8 @@ impl Default for Association {
 
             will_send_shutdown_ack: false,
             will_send_shutdown_complete: false,
+            will_send_zero_window_probe: false,
+            will_send_cookie_echo: false,
 
             // Reconfig
             my_next_rsn: 0,
@@ -245,6 +461,13 @@ impl Default for Association {
             pending_queue: PendingQueue::default(),
             control_queue: VecDeque::default(),
             stream_queue: VecDeque::default(),
+            last_read_stream: None,
+            next_message_handle: 0,
+            abandoned_messages: FxHashSet::default(),
+            partial_write_stream: None,
+            deferred_payload: PendingBaseQueue::new(),
+            next_queue_expiry: None,
+            queue_expiry_stale: false,
 
             mtu: 0,
             // max DATA chunk payload size
@@ -252,6 +475,8 @@ impl Default for Association {
             cumulative_tsn_ack_point: 0,
             advanced_peer_tsn_ack_point: 0,
             use_forward_tsn: false,
+            supported_extensions: DEFAULT_SUPPORTED_EXTENSIONS,
+            negotiated: None,
 
             rto_mgr: RtoManager::default(),
             timers: TimerTable::default(),
@@ -262,6 +487,23 @@ impl Default for Association {
             cwnd: 0,
             // calculated peer's receiver windows size
             rwnd: 0,
+            // receiver window credit last advertised to the peer
+            advertised_rwnd: 0,
+            // max number of new DATA packets sent per SACK received (0: unlimited)
+            max_burst: 0,
+            burst_budget: 0,
+            // last time a DATA chunk was transmitted, for cwnd validation after idle
+            last_data_sent: None,
+
+            // Pacing
+            pacing: false,
+            pacer: Pacer::default(),
+            paced_packets: VecDeque::default(),
+            next_pacing_slot: None,
+            last_pacing_slot: None,
+
+            linger_timeout: Duration::ZERO,
+            lingering: false,
             // slow start threshold
             ssthresh: 0,
             partial_bytes_acked: 0,
@@ -271,6 +513,7 @@ impl Default for Association {
             // Chunks stored for retransmission
             stored_init: None,
             stored_cookie_echo: None,
+            stored_cookie_echo_error: None,
             streams: FxHashMap::default(),
 
             events: VecDeque::default(),
@@ -291,6 +534,7 @@ impl Default for Association {
 }
 
 impl Association {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         server_config: Option<Arc<ServerConfig>>,
         config: Arc<TransportConfig>,
@@ -298,6 +542,8 @@ impl Association {
         local_aid: AssociationId,
         remote_addr: SocketAddr,
         local_ip: Option<IpAddr>,
+        local_port: u16,
+        remote_port: u16,
         now: Instant,
     ) -> Self {
         let side = if server_config.is_some() {
@@ -323,7 +569,17 @@ impl Association {
             side,
             handshake_completed: false,
             max_receive_buffer_size: config.max_receive_buffer_size(),
+            advertised_rwnd: config.max_receive_buffer_size(),
+            max_burst: config.max_burst(),
+            burst_budget: config.max_burst(),
+            pacing: config.pacing(),
+            linger_timeout: config.linger_timeout(),
+            supported_extensions: config.supported_extensions(),
             max_message_size: config.max_message_size(),
+            max_send_buffer_size: config.max_send_buffer_size(),
+            max_receive_message_size: config.max_receive_message_size(),
+            max_num_partial_messages: config.max_num_partial_messages(),
+            partial_delivery_point: config.partial_delivery_point(),
             my_max_num_outbound_streams: config.max_num_outbound_streams(),
             my_max_num_inbound_streams: config.max_num_inbound_streams(),
             max_payload_size,
@@ -335,6 +591,8 @@ impl Association {
             cwnd,
             remote_addr,
             local_ip,
+            source_port: local_port,
+            destination_port: remote_port,
 
             my_verification_tag: local_aid,
             my_next_tsn: tsn,
@@ -356,7 +614,7 @@ impl Association {
                 advertised_receiver_window_credit: this.max_receive_buffer_size,
                 ..Default::default()
             };
-            init.set_supported_extensions();
+            init.set_supported_extensions(this.supported_extensions.chunk_types());
 
             this.set_state(AssociationState::CookieWait);
             this.stored_init = Some(init);
@@ -405,7 +663,19 @@ impl Association {
     /// - a call was made to `handle_timeout`
     #[must_use]
     pub fn poll_timeout(&mut self) -> Option<Instant> {
-        self.timers.next_timeout()
+        let pacing_slot = if self.paced_packets.is_empty() {
+            None
+        } else {
+            self.last_pacing_slot
+        };
+        [
+            self.timers.next_timeout(),
+            pacing_slot,
+            self.next_queue_expiry,
+        ]
+        .into_iter()
+        .flatten()
+        .min()
     }
 
     /// Returns packets to transmit
@@ -416,7 +686,20 @@ impl Association {
     /// - a call was made to `handle_timeout`
     #[must_use]
     pub fn poll_transmit(&mut self, now: Instant) -> Option<Transmit> {
-        let (contents, _) = self.gather_outbound(now);
+        self.expire_pending_messages(now);
+        self.start_linger_timer(now);
+        let (contents, ok) = self.gather_outbound(now);
+        if !ok {
+            // SHUTDOWN-COMPLETE is the final chunk of the association
+            self.events.push_back(Event::ShutdownComplete);
+            let _ = self.close();
+        }
+        // Packets without DATA are sent right away, ahead of the paced ones
+        let (contents, earliest_send) = if self.pacing && contents.is_empty() {
+            self.pop_paced_packets(now)
+        } else {
+            (contents, None)
+        };
         if contents.is_empty() {
             None
         } else {
@@ -432,6 +715,7 @@ impl Association {
                 payload: Payload::RawEncode(contents),
                 ecn: None,
                 local_ip: self.local_ip,
+                earliest_send,
             })
         }
     }
@@ -446,6 +730,8 @@ impl Association {
     /// `Instant` that was output by `poll_timeout`; however spurious extra calls will simply
     /// no-op and therefore are safe.
     pub fn handle_timeout(&mut self, now: Instant) {
+        self.expire_pending_messages(now);
+
         for &timer in &Timer::VALUES {
             let (expired, failure, n_rtos) = self.timers.is_expired(timer, now);
             if !expired {
@@ -456,6 +742,8 @@ impl Association {
 
             if timer == Timer::Ack {
                 self.on_ack_timeout();
+            } else if timer == Timer::Linger {
+                self.on_linger_timeout();
             } else if failure {
                 self.on_retransmission_failure(timer);
             } else {
@@ -550,6 +838,12 @@ impl Association {
         self.remote_addr
     }
 
+    /// The parameters negotiated with the peer, once its INIT or INIT ACK chunk
+    /// was processed
+    pub fn negotiated(&self) -> Option<&NegotiatedParameters> {
+        self.negotiated.as_ref()
+    }
+
     /// Current best estimate of this Association's latency (round-trip-time)
     pub fn rtt(&self) -> Duration {
         Duration::from_millis(self.rto_mgr.get_rto())
@@ -573,9 +867,9 @@ impl Association {
         self.local_ip
     }
 
-    /// Shutdown initiates the shutdown sequence. The method blocks until the
-    /// shutdown sequence is completed and the association is closed, or until the
-    /// passed context is done, in which case the context's error is returned.
+    /// Shutdown initiates the graceful shutdown sequence of RFC 4960 sec 9.2.
+    /// The data already written is still delivered, then SHUTDOWN is sent and
+    /// `Event::ShutdownComplete` is emitted once the peer completes the exchange.
     pub fn shutdown(&mut self) -> Result<()> {
         debug!("[{}] closing association..", self.side);
 
@@ -587,15 +881,13 @@ impl Association {
         // Attempt a graceful shutdown.
         self.set_state(AssociationState::ShutdownPending);
 
-        if self.inflight_queue_length == 0 {
+        if !self.has_outstanding_data() {
             // No more outstanding, send shutdown.
             self.will_send_shutdown = true;
             self.awake_write_loop();
             self.set_state(AssociationState::ShutdownSent);
         }
 
-        self.endpoint_events.push_back(EndpointEventInner::Drained);
-
         Ok(())
     }
 
@@ -607,11 +899,24 @@ impl Association {
             debug!("[{}] closing association..", self.side);
 
             self.close_all_timers();
+            self.fail_unsent_messages();
+
+            // DATA held by the pacer must not follow an ABORT or SHUTDOWN COMPLETE
+            self.paced_packets.clear();
+            self.next_pacing_slot = None;
+            self.last_pacing_slot = None;
 
             for si in self.streams.keys().cloned().collect::<Vec<u16>>() {
                 self.unregister_stream(si);
             }
 
+            // Late retransmissions from the peer are answered while lingering
+            if self.linger_timeout.is_zero() {
+                self.endpoint_events.push_back(EndpointEventInner::Drained);
+            } else {
+                self.lingering = true;
+            }
+
             debug!("[{}] association closed", self.side);
             debug!(
                 "[{}] stats nDATAs (in) : {}",
@@ -653,6 +958,10 @@ impl Association {
             return Err(Error::ErrStreamAlreadyExist);
         }
 
+        if stream_identifier >= self.my_max_num_outbound_streams {
+            return Err(Error::ErrStreamIdentifierOutOfRange);
+        }
+
         if let Some(s) = self.create_stream(stream_identifier, false, default_payload_type) {
             Ok(s)
         } else {
@@ -682,6 +991,45 @@ impl Association {
         }
     }
 
+    /// read_any reads the next complete message from any readable stream,
+    /// taking turns in stream identifier order between the streams that have one.
+    pub fn read_any(&mut self) -> Option<ReceivedMessage> {
+        let next = self.last_read_stream.map_or(0, |id| id.wrapping_add(1));
+        let stream_identifier = self
+            .streams
+            .iter()
+            .filter(|(_, s)| {
+                matches!(
+                    s.state,
+                    RecvSendState::Readable | RecvSendState::ReadWritable
+                ) && s.reassembly_queue.has_complete_message()
+            })
+            .map(|(id, _)| *id)
+            .min_by_key(|id| id.wrapping_sub(next))?;
+
+        let chunks = self
+            .streams
+            .get_mut(&stream_identifier)?
+            .reassembly_queue
+            .read()?;
+        self.last_read_stream = Some(stream_identifier);
+        self.on_receive_window_opened(stream_identifier);
+
+        let (unordered, tsn) = chunks
+            .chunks
+            .first()
+            .map_or((false, 0), |c| (c.unordered, c.tsn));
+        Some(ReceivedMessage {
+            stream_identifier,
+            ppi: chunks.ppi,
+            unordered,
+            ssn: chunks.ssn,
+            tsn,
+            cumulative_tsn: self.peer_last_tsn,
+            chunks,
+        })
+    }
+
     /// bytes_sent returns the number of bytes sent
     pub(crate) fn bytes_sent(&self) -> usize {
         self.bytes_sent
@@ -697,6 +1045,13 @@ impl Association {
         self.max_message_size
     }
 
+    /// has_send_buffer_space returns whether `n` more bytes can be written without
+    /// exceeding the send buffer limit.
+    pub(crate) fn has_send_buffer_space(&self, n: usize) -> bool {
+        self.max_send_buffer_size == 0
+            || self.buffered_amount() + n <= self.max_send_buffer_size as usize
+    }
+
     /// set_max_message_size sets the maximum message size you can send.
     pub(crate) fn set_max_message_size(&mut self, max_message_size: u32) {
         self.max_message_size = max_message_size;
@@ -708,9 +1063,23 @@ impl Association {
         if let Some(mut s) = self.streams.remove(&stream_identifier) {
             debug!("[{}] unregister_stream {}", self.side, stream_identifier);
             s.state = RecvSendState::Closed;
+            self.update_num_partial_messages(s.reassembly_queue.num_partial_messages(), 0);
         }
     }
 
+    /// update_num_partial_messages accounts for the number of partial messages of
+    /// a stream changing from `before` to `after`.
+    pub(crate) fn update_num_partial_messages(&mut self, before: usize, after: usize) {
+        self.num_partial_messages = (self.num_partial_messages + after).saturating_sub(before);
+        debug_assert_eq!(
+            self.streams
+                .values()
+                .map(|s| s.reassembly_queue.num_partial_messages())
+                .sum::<usize>(),
+            self.num_partial_messages
+        );
+    }
+
     /// set_state atomically sets the state of the Association.
     fn set_state(&mut self, new_state: AssociationState) {
         if new_state != self.state {
@@ -732,9 +1101,6 @@ impl Association {
         if let Some(stored_init) = &self.stored_init {
             debug!("[{}] sending INIT", self.side);
 
-            self.source_port = 5000; // Spec??
-            self.destination_port = 5000; // Spec??
-
             let outbound = Packet {
                 common_header: CommonHeader {
                     source_port: self.source_port,
@@ -755,19 +1121,12 @@ impl Association {
 
     /// caller must hold self.lock
     fn send_cookie_echo(&mut self) -> Result<()> {
-        if let Some(stored_cookie_echo) = &self.stored_cookie_echo {
+        if self.stored_cookie_echo.is_some() {
             debug!("[{}] sending COOKIE-ECHO", self.side);
 
-            let outbound = Packet {
-                common_header: CommonHeader {
-                    source_port: self.source_port,
-                    destination_port: self.destination_port,
-                    verification_tag: self.peer_verification_tag,
-                },
-                chunks: vec![Box::new(stored_cookie_echo.clone())],
-            };
-
-            self.control_queue.push_back(outbound);
+            // The packet is built by gather_outbound_cookie_echo_packets so that
+            // DATA written up to the next poll_transmit can be bundled with it.
+            self.will_send_cookie_echo = true;
             self.awake_write_loop();
 
             Ok(())
@@ -783,9 +1142,21 @@ impl Association {
             return Ok(());
         }
 
+        if self.lingering {
+            self.start_linger_timer(now);
+            self.handle_out_of_the_blue(&p);
+            return Ok(());
+        }
+
         self.handle_chunk_start();
 
         for c in &p.chunks {
+            if let Some(c) = c.as_any().downcast_ref::<ChunkUnknown>() {
+                if self.handle_unrecognized_chunk(c) {
+                    continue;
+                }
+                break;
+            }
             self.handle_chunk(&p, c, now)?;
         }
 
@@ -794,6 +1165,110 @@ impl Association {
         Ok(())
     }
 
+    /// handle_out_of_the_blue answers a packet received while the closed association
+    /// lingers, as RFC 4960 sec 8.4 has it for an out of the blue packet.
+    fn handle_out_of_the_blue(&mut self, p: &Packet) {
+        for c in &p.chunks {
+            let chunk_any = c.as_any();
+            if let Some(e) = chunk_any.downcast_ref::<ChunkError>() {
+                // 4) If the packet contains a "Stale Cookie" ERROR or a COOKIE ACK,
+                //    the SCTP packet should be silently discarded.
+                if e.error_causes
+                    .iter()
+                    .any(|e| matches!(e, ErrorCause::StaleCookie { .. }))
+                {
+                    return;
+                }
+            } else if chunk_any.is::<ChunkShutdownAck>() {
+                // 5) If the packet contains a SHUTDOWN ACK chunk, the receiver should
+                //    respond to the sender of the OOTB packet with a SHUTDOWN
+                //    COMPLETE.
+                debug!("[{}] SHUTDOWN-ACK received while lingering", self.side);
+                self.control_queue
+                    .push_back(self.create_packet(vec![Box::new(ChunkShutdownComplete {})]));
+                self.awake_write_loop();
+                return;
+            } else if chunk_any.is::<ChunkAbort>()
+                || chunk_any.is::<ChunkShutdownComplete>()
+                || chunk_any.is::<ChunkCookieAck>()
+                || chunk_any.is::<ChunkInit>()
+            {
+                // 2) If the OOTB packet contains an ABORT chunk, the receiver MUST
+                //    silently discard the OOTB packet and take no further action.
+                // 6) If the packet contains a SHUTDOWN COMPLETE chunk, the receiver
+                //    should silently discard the packet and take no further action.
+                // An INIT is retransmitted by the peer until the association is gone.
+                return;
+            }
+        }
+
+        // 8) The receiver should respond to the sender of the OOTB packet with an
+        //    ABORT.
+        debug!("[{}] packet received while lingering, aborting", self.side);
+        self.send_abort(vec![]);
+    }
+
+    /// handle_unrecognized_chunk reports the chunk if its type asks for it, and
+    /// returns whether the rest of the packet should be processed.
+    fn handle_unrecognized_chunk(&mut self, c: &ChunkUnknown) -> bool {
+        debug!(
+            "[{}] unrecognized chunk type {} received",
+            self.side, c.typ.0
+        );
+
+        if c.report() {
+            // Nothing can be sent before the peer's verification tag is known
+            if self.peer_verification_tag != 0 {
+                let cerr = Association::create_unrecognized_chunk_error(c);
+                self.control_queue
+                    .push_back(self.create_packet(vec![Box::new(cerr)]));
+                self.awake_write_loop();
+            }
+        }
+
+        !c.stop_processing()
+    }
+
+    /// create_unrecognized_chunk_error builds an ERROR chunk reporting `c` with an
+    /// Unrecognized Chunk Type cause.
+    fn create_unrecognized_chunk_error(c: &dyn Chunk) -> ChunkError {
+        ChunkError {
+            error_causes: vec![ErrorCause::UnrecognizedChunkType {
+                chunk: c.marshal().unwrap_or_default(),
+            }],
+        }
+    }
+
+    /// partition_params returns the parameters of an INIT or INIT ACK chunk to
+    /// process, along with the unrecognized ones to report back, as the
+    /// highest-order 2 bits of their types require. See ParamUnknown.
+    fn partition_params(
+        params: &[Box<dyn Param + Send + Sync>],
+    ) -> (Vec<&(dyn Param + Send + Sync)>, Vec<Bytes>) {
+        let mut to_process = vec![];
+        let mut to_report = vec![];
+        for param in params {
+            let unknown = match param.as_any().downcast_ref::<ParamUnknown>() {
+                Some(v) if v.is_unrecognized() => v,
+                _ => {
+                    to_process.push(param.as_ref());
+                    continue;
+                }
+            };
+
+            if unknown.report() {
+                if let Ok(raw) = unknown.marshal() {
+                    to_report.push(raw);
+                }
+            }
+            if unknown.stop_processing() {
+                break;
+            }
+        }
+
+        (to_process, to_report)
+    }
+
     fn handle_chunk_start(&mut self) {
         self.delayed_ack_triggered = false;
         self.immediate_ack_triggered = false;
@@ -818,7 +1293,12 @@ impl Association {
         chunk: &Box<dyn Chunk + Send + Sync>,
         now: Instant,
     ) -> Result<()> {
-        chunk.check()?;
+        if let Err(err) = chunk.check() {
+            if let Some(i) = chunk.as_any().downcast_ref::<ChunkInit>() {
+                self.abort_invalid_init(p, i);
+            }
+            return Err(err);
+        }
         let chunk_any = chunk.as_any();
         let packets = if let Some(c) = chunk_any.downcast_ref::<ChunkInit>() {
             if c.is_ack {
@@ -829,7 +1309,7 @@ impl Association {
         } else if let Some(c) = chunk_any.downcast_ref::<ChunkAbort>() {
             let mut err_str = String::new();
             for e in &c.error_causes {
-                if matches!(e.code, USER_INITIATED_ABORT) {
+                if matches!(e, ErrorCause::UserInitiatedAbort { .. }) {
                     debug!("User initiated abort received");
                     let _ = self.close();
                     return Ok(());
@@ -838,17 +1318,13 @@ impl Association {
             }
             return Err(Error::ErrAbortChunk(err_str));
         } else if let Some(c) = chunk_any.downcast_ref::<ChunkError>() {
-            let mut err_str = String::new();
-            for e in &c.error_causes {
-                err_str += &format!("({})", e);
-            }
-            return Err(Error::ErrAbortChunk(err_str));
+            self.handle_error(c)
         } else if let Some(c) = chunk_any.downcast_ref::<ChunkHeartbeat>() {
             self.handle_heartbeat(c)?
         } else if let Some(c) = chunk_any.downcast_ref::<ChunkCookieEcho>() {
             self.handle_cookie_echo(c)?
         } else if chunk_any.downcast_ref::<ChunkCookieAck>().is_some() {
-            self.handle_cookie_ack()?
+            self.handle_cookie_ack(now)?
         } else if let Some(c) = chunk_any.downcast_ref::<ChunkPayloadData>() {
             self.handle_data(c)?
         } else if let Some(c) = chunk_any.downcast_ref::<ChunkSelectiveAck>() {
@@ -858,7 +1334,7 @@ impl Association {
         } else if let Some(c) = chunk_any.downcast_ref::<ChunkForwardTsn>() {
             self.handle_forward_tsn(c)?
         } else if let Some(c) = chunk_any.downcast_ref::<ChunkShutdown>() {
-            self.handle_shutdown(c)?
+            self.handle_shutdown(c, now)?
         } else if let Some(c) = chunk_any.downcast_ref::<ChunkShutdownAck>() {
             self.handle_shutdown_ack(c)?
         } else if let Some(c) = chunk_any.downcast_ref::<ChunkShutdownComplete>() {
@@ -876,6 +1352,117 @@ impl Association {
         Ok(())
     }
 
+    /// negotiate_num_streams limits the number of streams in each direction to what
+    /// the peer announced in its INIT or INIT ACK chunk.
+    fn negotiate_num_streams(&mut self, i: &ChunkInit) {
+        // RFC 4960 sec 5.1.1
+        //   After the initialization of the association, the number of outbound
+        //   streams the endpoint uses MUST be set to the smaller of the MIS in
+        //   the received INIT ACK and the OS it announced; likewise, the peer
+        //   can use no more inbound streams than the OS it announced.
+        self.my_max_num_inbound_streams =
+            std::cmp::min(i.num_outbound_streams, self.my_max_num_inbound_streams);
+        self.my_max_num_outbound_streams =
+            std::cmp::min(i.num_inbound_streams, self.my_max_num_outbound_streams);
+        self.close_out_of_range_streams();
+    }
+
+    /// close_out_of_range_streams closes the streams opened during the handshake
+    /// that the peer cannot receive, reporting each with `StreamEvent::Stopped`
+    /// and the messages written on them with `Event::SendFailed`.
+    fn close_out_of_range_streams(&mut self) {
+        let max = self.my_max_num_outbound_streams;
+        let mut closed: Vec<StreamId> = self
+            .streams
+            .keys()
+            .filter(|si| **si >= max)
+            .copied()
+            .collect();
+        if closed.is_empty() {
+            return;
+        }
+        closed.sort_unstable();
+
+        // No DATA is sent before the association is established, so the queues
+        // can be rebuilt without the chunks of the closed streams.
+        let mut failed = vec![];
+        for c in self.pending_queue.drain() {
+            if c.stream_identifier >= max {
+                failed.push(c);
+            } else {
+                self.pending_queue.push(c);
+            }
+        }
+        if self.partial_write_stream.is_some_and(|si| si >= max) {
+            self.partial_write_stream = None;
+        }
+        for c in std::mem::take(&mut self.deferred_payload) {
+            if c.stream_identifier >= max {
+                failed.push(c);
+            } else if self.partial_write_stream.is_some() {
+                self.deferred_payload.push_back(c);
+            } else {
+                self.pending_queue.push(c);
+            }
+        }
+
+        for si in closed {
+            warn!(
+                "[{}] closing stream {}: the peer accepts {} streams",
+                self.side, si, max
+            );
+            self.unregister_stream(si);
+            self.events.push_back(Event::Stream(StreamEvent::Stopped {
+                id: si,
+                error_code: INVALID_STREAM_IDENTIFIER,
+            }));
+        }
+        self.report_failed_messages(failed);
+    }
+
+    /// disable_partial_reliability makes reliable the streams configured with
+    /// partial reliability during the handshake, as the peer does not support
+    /// FORWARD TSN, reporting each with `StreamEvent::PartialReliabilityDisabled`.
+    fn disable_partial_reliability(&mut self) {
+        let mut ids: Vec<StreamId> = vec![];
+        for (si, s) in self.streams.iter_mut() {
+            if s.reliability_type != ReliabilityType::Reliable {
+                s.reliability_type = ReliabilityType::Reliable;
+                s.reliability_value = 0;
+                ids.push(*si);
+            }
+        }
+        ids.sort_unstable();
+        for id in ids {
+            warn!("[{}] stream {} made reliable", self.side, id);
+            self.events
+                .push_back(Event::Stream(StreamEvent::PartialReliabilityDisabled {
+                    id,
+                }));
+        }
+    }
+
+    /// record_negotiated_parameters keeps the outcome of the INIT or INIT ACK
+    /// processing for `negotiated`.
+    fn record_negotiated_parameters(
+        &mut self,
+        i: &ChunkInit,
+        peer_supported_extensions: SupportedExtensions,
+    ) {
+        self.negotiated = Some(NegotiatedParameters {
+            use_forward_tsn: self.use_forward_tsn,
+            use_reconfig: self.supported_extensions.reconfig && peer_supported_extensions.reconfig,
+            num_outbound_streams: self.my_max_num_outbound_streams,
+            num_inbound_streams: self.my_max_num_inbound_streams,
+            peer_receiver_window: i.advertised_receiver_window_credit,
+            peer_supported_extensions,
+            local_port: self.source_port,
+            peer_port: self.destination_port,
+            local_verification_tag: self.my_verification_tag,
+            peer_verification_tag: self.peer_verification_tag,
+        });
+    }
+
     fn handle_init(&mut self, p: &Packet, i: &ChunkInit) -> Result<Vec<Packet>> {
         let state = self.state();
         debug!("[{}] chunkInit received in state '{}'", self.side, state);
@@ -897,10 +1484,7 @@ impl Association {
         }
 
         // Should we be setting any of these permanently until we've ACKed further?
-        self.my_max_num_inbound_streams =
-            std::cmp::min(i.num_inbound_streams, self.my_max_num_inbound_streams);
-        self.my_max_num_outbound_streams =
-            std::cmp::min(i.num_outbound_streams, self.my_max_num_outbound_streams);
+        self.negotiate_num_streams(i);
         self.peer_verification_tag = i.initiate_tag;
         self.source_port = p.common_header.destination_port;
         self.destination_port = p.common_header.source_port;
@@ -915,19 +1499,22 @@ impl Association {
             i.initial_tsn - 1
         };
 
-        for param in &i.params {
+        let (params, unrecognized_params) = Association::partition_params(&i.params);
+        let mut peer_supported_extensions = SupportedExtensions::default();
+        for param in params {
             if let Some(v) = param.as_any().downcast_ref::<ParamSupportedExtensions>() {
-                for t in &v.chunk_types {
-                    if *t == CT_FORWARD_TSN {
-                        debug!("[{}] use ForwardTSN (on init)", self.side);
-                        self.use_forward_tsn = true;
-                    }
+                peer_supported_extensions = SupportedExtensions::from_chunk_types(&v.chunk_types);
+                if peer_supported_extensions.forward_tsn && self.supported_extensions.forward_tsn {
+                    debug!("[{}] use ForwardTSN (on init)", self.side);
+                    self.use_forward_tsn = true;
                 }
             }
         }
         if !self.use_forward_tsn {
             warn!("[{}] not using ForwardTSN (on init)", self.side);
+            self.disable_partial_reliability();
         }
+        self.record_negotiated_parameters(i, peer_supported_extensions);
 
         let mut outbound = Packet {
             common_header: CommonHeader {
@@ -956,7 +1543,18 @@ impl Association {
             init_ack.params = vec![Box::new(my_cookie.clone())];
         }
 
-        init_ack.set_supported_extensions();
+        // RFC 4960 sec 3.3.3
+        //   Unrecognized Parameter: ... This parameter is returned to the
+        //   originator of the INIT chunk when the INIT contains an unrecognized
+        //   parameter that has a value that indicates it should be reported to
+        //   the sender.
+        for raw_param in unrecognized_params {
+            init_ack
+                .params
+                .push(Box::new(ParamUnrecognized { raw_param }));
+        }
+
+        init_ack.set_supported_extensions(self.supported_extensions.chunk_types());
 
         outbound.chunks = vec![Box::new(init_ack)];
 
@@ -981,10 +1579,7 @@ impl Association {
             return Ok(vec![]);
         }
 
-        self.my_max_num_inbound_streams =
-            std::cmp::min(i.num_inbound_streams, self.my_max_num_inbound_streams);
-        self.my_max_num_outbound_streams =
-            std::cmp::min(i.num_outbound_streams, self.my_max_num_outbound_streams);
+        self.negotiate_num_streams(i);
         self.peer_verification_tag = i.initiate_tag;
         self.peer_last_tsn = if i.initial_tsn == 0 {
             u32::MAX
@@ -1017,22 +1612,52 @@ impl Association {
         self.timers.stop(Timer::T1Init);
         self.stored_init = None;
 
+        let (params, unrecognized_params) = Association::partition_params(&i.params);
         let mut cookie_param = None;
-        for param in &i.params {
+        let mut peer_supported_extensions = SupportedExtensions::default();
+        for param in params {
             if let Some(v) = param.as_any().downcast_ref::<ParamStateCookie>() {
                 cookie_param = Some(v);
             } else if let Some(v) = param.as_any().downcast_ref::<ParamSupportedExtensions>() {
-                for t in &v.chunk_types {
-                    if *t == CT_FORWARD_TSN {
-                        debug!("[{}] use ForwardTSN (on initAck)", self.side);
-                        self.use_forward_tsn = true;
-                    }
+                peer_supported_extensions = SupportedExtensions::from_chunk_types(&v.chunk_types);
+                if peer_supported_extensions.forward_tsn && self.supported_extensions.forward_tsn {
+                    debug!("[{}] use ForwardTSN (on initAck)", self.side);
+                    self.use_forward_tsn = true;
                 }
+            } else if let Some(v) = param.as_any().downcast_ref::<ParamUnrecognized>() {
+                debug!(
+                    "[{}] peer did not recognize parameter {:?}",
+                    self.side, v.raw_param
+                );
             }
         }
+
+        // RFC 9260 sec 3.2.2
+        //   When the receiver of an INIT ACK chunk detects unrecognized
+        //   parameters and has to report them according to Section 3.2.1, it
+        //   MUST put the 'Unrecognized Parameters' error cause(s) in the ERROR
+        //   chunk sent in response to the INIT ACK chunk (e.g., bundled with
+        //   the COOKIE ECHO chunk).
+        self.stored_cookie_echo_error = if unrecognized_params.is_empty() {
+            None
+        } else {
+            let mut raw = BytesMut::new();
+            for raw_param in unrecognized_params {
+                let padding = get_padding_size(raw.len());
+                raw.extend_from_slice(&[0u8; 4][..padding]);
+                raw.extend_from_slice(&raw_param);
+            }
+            Some(ChunkError {
+                error_causes: vec![ErrorCause::UnrecognizedParameters {
+                    params: raw.freeze(),
+                }],
+            })
+        };
         if !self.use_forward_tsn {
             warn!("[{}] not using ForwardTSN (on initAck)", self.side);
+            self.disable_partial_reliability();
         }
+        self.record_negotiated_parameters(i, peer_supported_extensions);
 
         if let Some(v) = cookie_param {
             self.stored_cookie_echo = Some(ChunkCookieEcho {
@@ -1048,10 +1673,62 @@ impl Association {
 
             Ok(vec![])
         } else {
+            // RFC 4960 sec 3.3.10.2
+            //   Indicates that one or more mandatory TLV parameters are missing
+            //   in a received INIT or INIT ACK.
+            self.send_abort(vec![ErrorCause::MissingMandatoryParameter {
+                missing_params: vec![ParamType::StateCookie.into()],
+            }]);
             Err(Error::ErrInitAckNoCookie)
         }
     }
 
+    /// abort_invalid_init answers an INIT or INIT ACK chunk failing its checks
+    /// with an ABORT, when the chunk tells which tag to send it with.
+    fn abort_invalid_init(&mut self, p: &Packet, i: &ChunkInit) {
+        // RFC 4960 sec 3.3.10.7
+        //   This error cause is returned to the originator of an INIT or INIT ACK
+        //   chunk when one of the mandatory parameters is set to an invalid value.
+        if i.initiate_tag == 0 {
+            return;
+        }
+        self.control_queue.push_back(Packet {
+            common_header: CommonHeader {
+                verification_tag: i.initiate_tag,
+                source_port: p.common_header.destination_port,
+                destination_port: p.common_header.source_port,
+            },
+            chunks: vec![Box::new(ChunkAbort {
+                error_causes: vec![ErrorCause::InvalidMandatoryParameter],
+            })],
+        });
+        self.awake_write_loop();
+    }
+
+    /// send_abort queues an ABORT chunk carrying `error_causes`. The caller is
+    /// expected to close the association right after.
+    fn send_abort(&mut self, error_causes: Vec<ErrorCause>) {
+        let abort = ChunkAbort { error_causes };
+        self.control_queue
+            .push_back(self.create_packet(vec![Box::new(abort)]));
+        self.awake_write_loop();
+    }
+
+    /// handle_error reports the causes of an ERROR chunk to the application.
+    fn handle_error(&mut self, c: &ChunkError) -> Vec<Packet> {
+        // RFC 4960 sec 3.3.10
+        //   An Operation Error is not considered fatal in and of itself, but may
+        //   be used with an ABORT chunk to report a fatal condition.
+        for e in &c.error_causes {
+            warn!("[{}] ERROR received: {}", self.side, e);
+        }
+        self.events.push_back(Event::RemoteError {
+            causes: c.error_causes.clone(),
+        });
+
+        vec![]
+    }
+
     fn handle_heartbeat(&self, c: &ChunkHeartbeat) -> Result<Vec<Packet>> {
         trace!("[{}] chunkHeartbeat", self.side);
         if let Some(p) = c.params.first() {
@@ -1102,11 +1779,27 @@ impl Association {
 
                     self.timers.stop(Timer::T1Cookie);
                     self.stored_cookie_echo = None;
+                    self.stored_cookie_echo_error = None;
 
                     self.events.push_back(Event::Connected);
                     self.set_state(AssociationState::Established);
                     self.handshake_completed = true;
                 }
+                AssociationState::ShutdownAckSent => {
+                    if my_cookie.cookie != c.cookie {
+                        return Ok(vec![]);
+                    }
+
+                    // RFC 4960 sec 3.3.10.10
+                    //   A COOKIE ECHO was received while the endpoint was in the
+                    //   SHUTDOWN-ACK-SENT state.  This error is usually returned in an
+                    //   ERROR chunk bundled with the retransmitted SHUTDOWN ACK.
+                    self.will_send_shutdown_ack = true;
+                    self.awake_write_loop();
+                    return Ok(vec![self.create_packet(vec![Box::new(ChunkError {
+                        error_causes: vec![ErrorCause::CookieReceivedWhileShuttingDown],
+                    })])]);
+                }
                 _ => return Ok(vec![]),
             };
         } else {
@@ -1124,7 +1817,7 @@ impl Association {
         }])
     }
 
-    fn handle_cookie_ack(&mut self) -> Result<Vec<Packet>> {
+    fn handle_cookie_ack(&mut self, now: Instant) -> Result<Vec<Packet>> {
         let state = self.state();
         debug!("[{}] COOKIE-ACK received in state '{}'", self.side, state);
         if state != AssociationState::CookieEchoed {
@@ -1137,11 +1830,18 @@ impl Association {
 
         self.timers.stop(Timer::T1Cookie);
         self.stored_cookie_echo = None;
+        self.stored_cookie_echo_error = None;
 
         self.events.push_back(Event::Connected);
         self.set_state(AssociationState::Established);
         self.handshake_completed = true;
 
+        // DATA bundled with the COOKIE ECHO was covered by T1-cookie so far.
+        if !self.inflight_queue.is_empty() {
+            self.timers
+                .restart_if_stale(Timer::T3RTX, now, self.rto_mgr.get_rto());
+        }
+
         Ok(vec![])
     }
 
@@ -1155,9 +1855,41 @@ impl Association {
         );
         self.stats.inc_datas();
 
+        // RFC 4960 sec 6.2
+        //   If an endpoint receives a DATA chunk with no user data (i.e., the
+        //   Length field is set to 16), it MUST send an ABORT with error cause
+        //   set to "No User Data".
+        if d.user_data.is_empty() {
+            self.send_abort(vec![ErrorCause::NoUserData { tsn: d.tsn }]);
+            return Err(Error::ErrNoUserData);
+        }
+
         let can_push = self.payload_queue.can_push(d, self.peer_last_tsn);
         let mut stream_handle_data = false;
-        if can_push {
+        let mut reply = vec![];
+        if !can_push {
+            // Already received: recorded for the Duplicate TSN report of the next SACK
+            self.stats.inc_duplicate_tsns();
+            self.payload_queue.push(d.clone(), self.peer_last_tsn);
+        } else if d.stream_identifier >= self.my_max_num_inbound_streams {
+            // RFC 4960 sec 6.5
+            //   Every DATA chunk MUST carry a valid stream identifier.  If an
+            //   endpoint receives a DATA chunk with an invalid stream identifier,
+            //   it shall acknowledge the reception of the DATA chunk following the
+            //   normal procedure, immediately send an ERROR chunk with cause set to
+            //   "Invalid Stream Identifier" (see Section 3.3.10), and discard the
+            //   DATA chunk.
+            debug!(
+                "[{}] DATA for invalid stream {} (inbound streams: {})",
+                self.side, d.stream_identifier, self.my_max_num_inbound_streams
+            );
+            self.payload_queue.push(d.clone(), self.peer_last_tsn);
+            reply.push(self.create_packet(vec![Box::new(ChunkError {
+                error_causes: vec![ErrorCause::InvalidStreamIdentifier {
+                    stream_identifier: d.stream_identifier,
+                }],
+            })]));
+        } else {
             if self.get_or_create_stream(d.stream_identifier).is_some() {
                 if self.get_my_receiver_window_credit() > 0 {
                     // Pass the new chunk to stream level as soon as it arrives
@@ -1186,21 +1918,78 @@ impl Association {
             }
         }
 
-        let immediate_sack = d.immediate_sack;
+        // RFC 4960 sec 6.7
+        //   When a packet arrives with duplicate DATA chunk(s) and with no new
+        //   DATA chunk(s), the endpoint MUST immediately send a SACK with no
+        //   delay.
+        let immediate_sack = d.immediate_sack || !can_push;
 
         if stream_handle_data {
             if let Some(s) = self.streams.get_mut(&d.stream_identifier) {
                 self.events.push_back(Event::DatagramReceived);
+                let n = s.reassembly_queue.num_partial_messages();
                 s.handle_data(d);
-                if s.reassembly_queue.is_readable() {
+                let (after, readable) = (
+                    s.reassembly_queue.num_partial_messages(),
+                    s.reassembly_queue.is_readable(),
+                );
+                self.update_num_partial_messages(n, after);
+                if self.start_partial_delivery(d.stream_identifier) || readable {
                     self.events.push_back(Event::Stream(StreamEvent::Readable {
                         id: d.stream_identifier,
                     }))
                 }
             }
+            self.check_reassembly_limits(d)?;
+        }
+
+        // RFC 4960 sec 9.2
+        //   While in the SHUTDOWN-SENT state, the SHUTDOWN sender MUST immediately
+        //   respond to each received packet containing one or more DATA chunks
+        //   with a SHUTDOWN chunk and restart the T2-shutdown timer.
+        if self.state() == AssociationState::ShutdownSent {
+            self.will_send_shutdown = true;
+            self.awake_write_loop();
+        }
+
+        reply.extend(self.handle_peer_last_tsn_and_acknowledgement(immediate_sack)?);
+        Ok(reply)
+    }
+
+    /// check_reassembly_limits aborts the association when `d` grew its message
+    /// beyond max_receive_message_size, or started one partial message too many.
+    fn check_reassembly_limits(&mut self, d: &ChunkPayloadData) -> Result<()> {
+        let s = match self.streams.get(&d.stream_identifier) {
+            Some(s) => s,
+            None => return Ok(()),
+        };
+
+        if self.max_receive_message_size > 0
+            && s.reassembly_queue.message_len(d) > self.max_receive_message_size as usize
+        {
+            warn!(
+                "[{}] inbound message on stream {} exceeds {} bytes",
+                self.side, d.stream_identifier, self.max_receive_message_size
+            );
+            self.send_abort(vec![ErrorCause::ProtocolViolation {
+                additional_information: Bytes::from_static(b"message too large"),
+            }]);
+            return Err(Error::ErrInboundMessageTooLarge);
+        }
+
+        // A message carried by a single chunk is never partial
+        if self.max_num_partial_messages > 0 && !(d.beginning_fragment && d.ending_fragment) {
+            let n = self.num_partial_messages;
+            if n > self.max_num_partial_messages as usize {
+                warn!("[{}] too many partial messages: {}", self.side, n);
+                self.send_abort(vec![ErrorCause::ProtocolViolation {
+                    additional_information: Bytes::from_static(b"too many partial messages"),
+                }]);
+                return Err(Error::ErrTooManyPartialMessages);
+            }
         }
 
-        self.handle_peer_last_tsn_and_acknowledgement(immediate_sack)
+        Ok(())
     }
 
     fn handle_sack(&mut self, d: &ChunkSelectiveAck, now: Instant) -> Result<Vec<Packet>> {
@@ -1221,6 +2010,9 @@ impl Association {
 
         self.stats.inc_sacks();
 
+        // Max.Burst limits the new DATA sent in response to each SACK
+        self.burst_budget = self.max_burst;
+
         if sna32gt(self.cumulative_tsn_ack_point, d.cumulative_tsn_ack) {
             // RFC 4960 sec 6.2.1.  Processing a Received SACK
             // D)
@@ -1323,6 +2115,7 @@ impl Association {
         }
 
         self.postprocess_sack(state, cum_tsn_ack_point_advanced, now);
+        self.update_zero_window_probe(now);
 
         Ok(vec![])
     }
@@ -1330,6 +2123,13 @@ impl Association {
     fn handle_reconfig(&mut self, c: &ChunkReconfig) -> Result<Vec<Packet>> {
         trace!("[{}] handle_reconfig", self.side);
 
+        if !self.use_reconfig() {
+            warn!("[{}] received RECONFIG but not enabled", self.side);
+            // Return an error chunk
+            let cerr = Association::create_unrecognized_chunk_error(c);
+            return Ok(vec![self.create_packet(vec![Box::new(cerr)])]);
+        }
+
         let mut pp = vec![];
 
         if let Some(param_a) = &c.param_a {
@@ -1344,14 +2144,12 @@ impl Association {
     }
 
     fn handle_forward_tsn(&mut self, c: &ChunkForwardTsn) -> Result<Vec<Packet>> {
-        trace!("[{}] FwdTSN: {}", self.side, c.to_string());
+        trace!("[{}] FwdTSN: {}", self.side, c);
 
         if !self.use_forward_tsn {
             warn!("[{}] received FwdTSN but not enabled", self.side);
             // Return an error chunk
-            let cerr = ChunkError {
-                error_causes: vec![ErrorCauseUnrecognizedChunkType::default()],
-            };
+            let cerr = Association::create_unrecognized_chunk_error(c);
 
             let outbound = Packet {
                 common_header: CommonHeader {
@@ -1407,7 +2205,16 @@ impl Association {
         // from the reassemblyQueue.
         for forwarded in &c.streams {
             if let Some(s) = self.streams.get_mut(&forwarded.identifier) {
-                s.handle_forward_tsn_for_ordered(forwarded.sequence);
+                let n = s.reassembly_queue.num_partial_messages();
+                let aborted = s.handle_forward_tsn_for_ordered(forwarded.sequence);
+                let after = s.reassembly_queue.num_partial_messages();
+                self.update_num_partial_messages(n, after);
+                if aborted {
+                    self.events
+                        .push_back(Event::Stream(StreamEvent::PartialDeliveryAborted {
+                            id: forwarded.identifier,
+                        }));
+                }
             }
         }
 
@@ -1416,42 +2223,115 @@ impl Association {
         // Therefore, we need to broadcast this event to all existing streams for
         // unordered chunks.
         // See https://github.com/pion/sctp/issues/106
+        let (mut before, mut after) = (0, 0);
         for s in self.streams.values_mut() {
-            s.handle_forward_tsn_for_unordered(c.new_cumulative_tsn);
+            before += s.reassembly_queue.num_partial_messages();
+            let aborted = s.handle_forward_tsn_for_unordered(c.new_cumulative_tsn);
+            after += s.reassembly_queue.num_partial_messages();
+            if aborted {
+                self.events
+                    .push_back(Event::Stream(StreamEvent::PartialDeliveryAborted {
+                        id: s.stream_identifier,
+                    }));
+            }
         }
+        self.update_num_partial_messages(before, after);
 
         self.handle_peer_last_tsn_and_acknowledgement(false)
     }
 
-    fn handle_shutdown(&mut self, _: &ChunkShutdown) -> Result<Vec<Packet>> {
+    fn handle_shutdown(&mut self, c: &ChunkShutdown, now: Instant) -> Result<Vec<Packet>> {
         let state = self.state();
 
-        if state == AssociationState::Established {
-            if !self.inflight_queue.is_empty() {
-                self.set_state(AssociationState::ShutdownReceived);
-            } else {
-                // No more outstanding, send shutdown ack.
+        match state {
+            AssociationState::Established
+            | AssociationState::ShutdownPending
+            | AssociationState::ShutdownReceived => {
+                // RFC 4960 sec 9.2
+                //   Upon reception of the SHUTDOWN, the peer endpoint shall enter the
+                //   SHUTDOWN-RECEIVED state, stop accepting new data from its SCTP
+                //   user, and verify, by checking the Cumulative TSN Ack field of the
+                //   chunk, that all its outstanding DATA chunks have been received by
+                //   the SHUTDOWN sender.
+                self.handle_shutdown_cumulative_tsn_ack(c.cumulative_tsn_ack, now)?;
+
+                if self.has_outstanding_data() {
+                    self.set_state(AssociationState::ShutdownReceived);
+                } else {
+                    // No more outstanding, send shutdown ack.
+                    self.timers.stop(Timer::T2Shutdown);
+                    self.will_send_shutdown_ack = true;
+                    self.set_state(AssociationState::ShutdownAckSent);
+
+                    self.awake_write_loop();
+                }
+            }
+            AssociationState::ShutdownSent => {
+                self.handle_shutdown_cumulative_tsn_ack(c.cumulative_tsn_ack, now)?;
+
                 self.will_send_shutdown_ack = true;
                 self.set_state(AssociationState::ShutdownAckSent);
 
                 self.awake_write_loop();
             }
-        } else if state == AssociationState::ShutdownSent {
-            // self.cumulative_tsn_ack_point = c.cumulative_tsn_ack
+            _ => {}
+        }
 
-            self.will_send_shutdown_ack = true;
-            self.set_state(AssociationState::ShutdownAckSent);
+        Ok(vec![])
+    }
 
-            self.awake_write_loop();
+    /// handle_shutdown_cumulative_tsn_ack processes the Cumulative TSN Ack of a
+    /// SHUTDOWN chunk like that of a SACK without Gap Ack Blocks.
+    fn handle_shutdown_cumulative_tsn_ack(
+        &mut self,
+        cumulative_tsn_ack: u32,
+        now: Instant,
+    ) -> Result<()> {
+        if !sna32gt(cumulative_tsn_ack, self.cumulative_tsn_ack_point) {
+            return Ok(());
         }
 
-        Ok(vec![])
+        let d = ChunkSelectiveAck {
+            cumulative_tsn_ack,
+            advertised_receiver_window_credit: self.rwnd,
+            gap_ack_blocks: vec![],
+            duplicate_tsn: vec![],
+        };
+        let (bytes_acked_per_stream, _) = self.process_selective_ack(&d, now)?;
+
+        let total_bytes_acked = bytes_acked_per_stream.values().sum();
+        trace!(
+            "[{}] SHUTDOWN: cumTSN advanced: {} -> {}",
+            self.side,
+            self.cumulative_tsn_ack_point,
+            cumulative_tsn_ack
+        );
+        self.cumulative_tsn_ack_point = cumulative_tsn_ack;
+        if sna32lt(
+            self.advanced_peer_tsn_ack_point,
+            self.cumulative_tsn_ack_point,
+        ) {
+            self.advanced_peer_tsn_ack_point = self.cumulative_tsn_ack_point;
+        }
+        self.on_cumulative_tsn_ack_point_advanced(total_bytes_acked, now);
+
+        for (si, n_bytes_acked) in &bytes_acked_per_stream {
+            if let Some(s) = self.streams.get_mut(si) {
+                if s.on_buffer_released(*n_bytes_acked) {
+                    self.events
+                        .push_back(Event::Stream(StreamEvent::BufferedAmountLow { id: *si }))
+                }
+            }
+        }
+
+        Ok(())
     }
 
     fn handle_shutdown_ack(&mut self, _: &ChunkShutdownAck) -> Result<Vec<Packet>> {
         let state = self.state();
         if state == AssociationState::ShutdownSent || state == AssociationState::ShutdownAckSent {
             self.timers.stop(Timer::T2Shutdown);
+            self.timers.stop(Timer::T5ShutdownGuard);
             self.will_send_shutdown_complete = true;
 
             self.awake_write_loop();
@@ -1464,6 +2344,7 @@ impl Association {
         let state = self.state();
         if state == AssociationState::ShutdownAckSent {
             self.timers.stop(Timer::T2Shutdown);
+            self.events.push_back(Event::ShutdownComplete);
             self.close()?;
         }
 
@@ -1560,6 +2441,17 @@ impl Association {
         //log::debug!("[{}] i={} d={}", self.name, i, d.cumulative_tsn_ack);
         while sna32lte(i, d.cumulative_tsn_ack) {
             if let Some(c) = self.inflight_queue.pop(i) {
+                // Fragments have consecutive TSNs, so the whole message is acked
+                // with its ending fragment
+                if let (true, Some(handle)) = (c.ending_fragment, c.message_handle) {
+                    if !self.abandoned_messages.remove(&handle) {
+                        self.events.push_back(Event::MessageAcked {
+                            stream: c.stream_identifier,
+                            handle,
+                        });
+                    }
+                }
+
                 if !c.acked {
                     // RFC 4096 sec 6.3.2.  Retransmission Timer Rules
                     //   R3)  Whenever a SACK is received that acknowledges the DATA chunk
@@ -1833,6 +2725,8 @@ impl Association {
             trace!("[{}] T3-rtx timer start (pt3)", self.side);
             self.timers
                 .restart_if_stale(Timer::T3RTX, now, self.rto_mgr.get_rto());
+        } else if self.has_outstanding_data() {
+            // The pending chunks are sent before the SHUTDOWN exchange starts
         } else if state == AssociationState::ShutdownPending {
             // No more outstanding, send shutdown.
             should_awake_write_loop = true;
@@ -1923,15 +2817,19 @@ impl Association {
     /// The caller should hold the read lock.
     pub(crate) fn create_packet(&self, chunks: Vec<Box<dyn Chunk + Send + Sync>>) -> Packet {
         Packet {
-            common_header: CommonHeader {
-                verification_tag: self.peer_verification_tag,
-                source_port: self.source_port,
-                destination_port: self.destination_port,
-            },
+            common_header: self.common_header(),
             chunks,
         }
     }
 
+    fn common_header(&self) -> CommonHeader {
+        CommonHeader {
+            verification_tag: self.peer_verification_tag,
+            source_port: self.source_port,
+            destination_port: self.destination_port,
+        }
+    }
+
     /// create_stream creates a stream. The caller should hold the lock and check no stream exists for this id.
     fn create_stream(
         &mut self,
@@ -1981,93 +2879,284 @@ impl Association {
             bytes_queued += s.get_num_bytes_in_reassembly_queue() as u32;
         }
 
-        if bytes_queued >= self.max_receive_buffer_size {
-            0
-        } else {
-            self.max_receive_buffer_size - bytes_queued
+        self.max_receive_buffer_size.saturating_sub(bytes_queued)
+    }
+
+    /// get_advertised_receiver_window_credit returns the a_rwnd to place in an
+    /// outgoing SACK, applying receiver-side SWS avoidance.
+    fn get_advertised_receiver_window_credit(&mut self) -> u32 {
+        // RFC 4960 sec 6.2.  Acknowledgement on Reception of DATA Chunks
+        //   For transmission efficiency, SCTP defines mechanisms for avoiding
+        //   silly window syndrome (SWS) as described in [RFC0813].
+        //
+        // A shrinking window is always reported as is. A growing window is only
+        // announced once it has opened by a meaningful amount.
+        let credit = self.get_my_receiver_window_credit();
+        if credit > self.advertised_rwnd && !self.is_window_update_due(credit) {
+            return self.advertised_rwnd;
+        }
+
+        self.advertised_rwnd = credit;
+        credit
+    }
+
+    /// sws_threshold returns the minimum window increase worth announcing to the peer:
+    /// min(MTU, half of the receive buffer).
+    fn sws_threshold(&self) -> u32 {
+        std::cmp::min(self.mtu, self.max_receive_buffer_size / 2)
+    }
+
+    /// is_window_update_due tells whether the window has grown by at least
+    /// sws_threshold since it was last advertised.
+    fn is_window_update_due(&self, credit: u32) -> bool {
+        credit > self.advertised_rwnd && credit - self.advertised_rwnd >= self.sws_threshold()
+    }
+
+    /// start_partial_delivery puts the head message of the stream in partial
+    /// delivery once enough of it has been received, reporting it to the
+    /// application. Returns whether partial delivery started.
+    fn start_partial_delivery(&mut self, stream_identifier: StreamId) -> bool {
+        if self.partial_delivery_point == 0 {
+            return false;
+        }
+        let started = self.streams.get_mut(&stream_identifier).is_some_and(|s| {
+            s.reassembly_queue
+                .start_partial_delivery(self.partial_delivery_point as usize)
+        });
+        if started {
+            self.events
+                .push_back(Event::Stream(StreamEvent::PartialDeliveryStarted {
+                    id: stream_identifier,
+                }));
+        }
+        started
+    }
+
+    /// on_receive_window_opened is called after the application has consumed data
+    /// from the reassembly queue of a stream. The read may have made the next
+    /// message of the stream eligible for partial delivery. It schedules a window
+    /// update SACK once the window has grown by the amount a SACK would announce.
+    pub(crate) fn on_receive_window_opened(&mut self, stream_identifier: StreamId) {
+        if self.start_partial_delivery(stream_identifier) {
+            self.events.push_back(Event::Stream(StreamEvent::Readable {
+                id: stream_identifier,
+            }));
+        }
+
+        let state = self.state();
+        if state != AssociationState::Established
+            && state != AssociationState::ShutdownPending
+            && state != AssociationState::ShutdownSent
+        {
+            return;
+        }
+
+        let credit = self.get_my_receiver_window_credit();
+        if self.is_window_update_due(credit) {
+            trace!(
+                "[{}] sending window update: a_rwnd {} -> {}",
+                self.side,
+                self.advertised_rwnd,
+                credit
+            );
+            self.ack_state = AckState::Immediate;
+            self.awake_write_loop();
         }
     }
 
     /// gather_outbound gathers outgoing packets. The returned bool value set to
     /// false means the association should be closed down after the final send.
     fn gather_outbound(&mut self, now: Instant) -> (Vec<Bytes>, bool) {
-        let mut raw_packets = vec![];
+        let mut builder = PacketBuilder::new(self.common_header(), self.mtu);
 
-        if !self.control_queue.is_empty() {
-            for p in self.control_queue.drain(..) {
-                if let Ok(raw) = p.marshal() {
-                    raw_packets.push(raw);
-                } else {
-                    warn!("[{}] failed to serialize a control packet", self.side);
-                    continue;
-                }
-            }
+        for p in self.control_queue.drain(..) {
+            builder.push_packet(p);
         }
 
         let state = self.state();
-        match state {
+        let ok = match state {
             AssociationState::Established => {
-                raw_packets = self.gather_data_packets_to_retransmit(raw_packets, now);
-                raw_packets = self.gather_outbound_data_and_reconfig_packets(raw_packets, now);
-                raw_packets = self.gather_outbound_fast_retransmission_packets(raw_packets, now);
-                raw_packets = self.gather_outbound_sack_packets(raw_packets);
-                raw_packets = self.gather_outbound_forward_tsn_packets(raw_packets);
-                (raw_packets, true)
+                // Retransmissions go first: they carry lower TSNs than new DATA,
+                // and the builder keeps DATA in push order (RFC 4960 sec 6.10).
+                self.gather_data_packets_to_retransmit(&mut builder, now);
+                self.gather_zero_window_probe_packets(&mut builder, now);
+                self.gather_outbound_fast_retransmission_packets(&mut builder, now);
+                self.gather_outbound_data_and_reconfig_packets(&mut builder, now);
+                self.gather_outbound_sack_packets(&mut builder);
+                self.gather_outbound_forward_tsn_packets(&mut builder);
+                true
             }
             AssociationState::ShutdownPending
             | AssociationState::ShutdownSent
             | AssociationState::ShutdownReceived => {
-                raw_packets = self.gather_data_packets_to_retransmit(raw_packets, now);
-                raw_packets = self.gather_outbound_fast_retransmission_packets(raw_packets, now);
-                raw_packets = self.gather_outbound_sack_packets(raw_packets);
-                self.gather_outbound_shutdown_packets(raw_packets, now)
+                // RFC 4960 sec 9.2
+                //   The outstanding data is still sent, and retransmitted, until the
+                //   peer has acknowledged all of it.
+                self.gather_data_packets_to_retransmit(&mut builder, now);
+                self.gather_zero_window_probe_packets(&mut builder, now);
+                self.gather_outbound_fast_retransmission_packets(&mut builder, now);
+                self.gather_outbound_data_and_reconfig_packets(&mut builder, now);
+                self.gather_outbound_sack_packets(&mut builder);
+                self.gather_outbound_forward_tsn_packets(&mut builder);
+                self.gather_outbound_shutdown_packets(&mut builder, now)
+            }
+            AssociationState::CookieEchoed => {
+                self.gather_outbound_cookie_echo_packets(&mut builder, now);
+                true
             }
             AssociationState::ShutdownAckSent => {
-                self.gather_outbound_shutdown_packets(raw_packets, now)
+                self.gather_outbound_shutdown_packets(&mut builder, now)
             }
-            _ => (raw_packets, true),
-        }
-    }
+            _ => true,
+        };
 
-    fn gather_data_packets_to_retransmit(
-        &mut self,
-        mut raw_packets: Vec<Bytes>,
-        now: Instant,
-    ) -> Vec<Bytes> {
-        for p in &self.get_data_packets_to_retransmit(now) {
+        let mut raw_packets = vec![];
+        for p in builder.build() {
             if let Ok(raw) = p.marshal() {
-                raw_packets.push(raw);
+                if self.pacing && p.chunks.iter().any(|c| c.header().typ == CT_PAYLOAD_DATA) {
+                    self.paced_packets.push_back(raw);
+                } else {
+                    raw_packets.push(raw);
+                }
             } else {
-                warn!(
-                    "[{}] failed to serialize a DATA packet to be retransmitted",
-                    self.side
-                );
+                warn!("[{}] failed to serialize a packet", self.side);
             }
         }
 
-        raw_packets
+        (raw_packets, ok)
+    }
+
+    fn gather_outbound_cookie_echo_packets(&mut self, builder: &mut PacketBuilder, now: Instant) {
+        if !self.will_send_cookie_echo {
+            return;
+        }
+        self.will_send_cookie_echo = false;
+
+        let cookie_echo = match &self.stored_cookie_echo {
+            Some(cookie_echo) => cookie_echo.clone(),
+            None => return,
+        };
+
+        // RFC 9260 sec 5.1
+        //   D) ... The COOKIE ECHO chunk MAY be bundled with any pending outbound
+        //      DATA chunks, but it MUST be the first chunk in the packet and until
+        //      the COOKIE ACK is returned the sender MUST NOT send any other packets
+        //      to the peer.
+        let mut chunks: Vec<Box<dyn Chunk + Send + Sync>> = vec![Box::new(cookie_echo)];
+        if let Some(cerr) = &self.stored_cookie_echo_error {
+            chunks.push(Box::new(cerr.clone()));
+        }
+
+        let mut control_size = 0;
+        for c in &chunks {
+            let chunk_size = CHUNK_HEADER_SIZE + c.value_length();
+            control_size += chunk_size + get_padding_size(chunk_size);
+        }
+        let room = (self.mtu as usize).saturating_sub(COMMON_HEADER_SIZE as usize + control_size);
+
+        for c in self.get_early_data_chunks(now, room) {
+            self.last_data_sent = Some(now);
+            chunks.push(Box::new(c));
+        }
+
+        builder.push_packet(Packet {
+            common_header: self.common_header(),
+            chunks,
+        });
+    }
+
+    /// get_early_data_chunks returns the DATA chunks to bundle with the COOKIE ECHO,
+    /// fitting in `room` bytes. A retransmitted COOKIE ECHO carries the same DATA
+    /// chunks as the first one.
+    fn get_early_data_chunks(&mut self, now: Instant, room: usize) -> Vec<ChunkPayloadData> {
+        let mut chunks = vec![];
+
+        if !self.inflight_queue.is_empty() {
+            let mut tsn = self.cumulative_tsn_ack_point.wrapping_add(1);
+            while let Some(c) = self.inflight_queue.get_mut(tsn) {
+                c.nsent += 1;
+                chunks.push(c.clone());
+                tsn = tsn.wrapping_add(1);
+            }
+            return chunks;
+        }
+
+        let mut bytes = 0;
+        while let Some(c) = self.pending_queue.peek() {
+            let (beginning_fragment, unordered, data_len) =
+                (c.beginning_fragment, c.unordered, c.user_data.len());
+
+            if data_len == 0 {
+                break; // stream reset requests wait for the association to be established
+            }
+
+            let chunk_size = DATA_CHUNK_HEADER_SIZE as usize + data_len;
+            let chunk_size = chunk_size + get_padding_size(chunk_size);
+            if bytes + chunk_size > room
+                || self.inflight_queue.get_num_bytes() + data_len > self.cwnd as usize
+                || data_len > self.rwnd as usize
+            {
+                break;
+            }
+
+            bytes += chunk_size;
+            self.rwnd -= data_len as u32;
+
+            if let Some(chunk) =
+                self.move_pending_data_chunk_to_inflight_queue(beginning_fragment, unordered, now)
+            {
+                chunks.push(chunk);
+            }
+        }
+
+        chunks
+    }
+
+    fn gather_data_packets_to_retransmit(&mut self, builder: &mut PacketBuilder, now: Instant) {
+        let chunks = self.get_data_chunks_to_retransmit(now);
+        if !chunks.is_empty() {
+            self.last_data_sent = Some(now);
+        }
+        for c in chunks {
+            builder.push_data(c);
+        }
+    }
+
+    fn gather_zero_window_probe_packets(&mut self, builder: &mut PacketBuilder, now: Instant) {
+        if !self.will_send_zero_window_probe {
+            return;
+        }
+        self.will_send_zero_window_probe = false;
+
+        if let Some(c) = self.get_zero_window_probe_chunk(now) {
+            self.last_data_sent = Some(now);
+            builder.push_data(c);
+        }
     }
 
     fn gather_outbound_data_and_reconfig_packets(
         &mut self,
-        mut raw_packets: Vec<Bytes>,
+        builder: &mut PacketBuilder,
         now: Instant,
-    ) -> Vec<Bytes> {
+    ) {
+        self.validate_cwnd_after_idle(now);
+
         // Pop unsent data chunks from the pending queue to send as much as
         // cwnd and rwnd allow.
         let (chunks, sis_to_reset) = self.pop_pending_data_chunks_to_send(now);
         if !chunks.is_empty() {
             // Start timer. (noop if already started)
-            trace!("[{}] T3-rtx timer start (pt1)", self.side);
-            self.timers
-                .restart_if_stale(Timer::T3RTX, now, self.rto_mgr.get_rto());
+            // While probing a closed window, the zero window probe timer drives
+            // retransmissions instead.
+            if !self.is_zero_window_probing() {
+                trace!("[{}] T3-rtx timer start (pt1)", self.side);
+                self.timers
+                    .restart_if_stale(Timer::T3RTX, now, self.rto_mgr.get_rto());
+            }
 
-            for p in &self.bundle_data_chunks_into_packets(chunks) {
-                if let Ok(raw) = p.marshal() {
-                    raw_packets.push(raw);
-                } else {
-                    warn!("[{}] failed to serialize a DATA packet", self.side);
-                }
+            self.last_data_sent = Some(now);
+            for c in chunks {
+                builder.push_data(c);
             }
         }
 
@@ -2080,15 +3169,7 @@ impl Association {
                     self.reconfigs.len()
                 );
                 for c in self.reconfigs.values() {
-                    let p = self.create_packet(vec![Box::new(c.clone())]);
-                    if let Ok(raw) = p.marshal() {
-                        raw_packets.push(raw);
-                    } else {
-                        warn!(
-                            "[{}] failed to serialize a RECONFIG packet to be retransmitted",
-                            self.side,
-                        );
-                    }
+                    builder.push_control(Box::new(c.clone()));
                 }
             }
 
@@ -2114,15 +3195,7 @@ impl Association {
                 };
                 self.reconfigs.insert(rsn, c.clone()); // store in the map for retransmission
 
-                let p = self.create_packet(vec![Box::new(c)]);
-                if let Ok(raw) = p.marshal() {
-                    raw_packets.push(raw);
-                } else {
-                    warn!(
-                        "[{}] failed to serialize a RECONFIG packet to be transmitted",
-                        self.side
-                    );
-                }
+                builder.push_control(Box::new(c));
             }
 
             if !self.reconfigs.is_empty() {
@@ -2130,19 +3203,17 @@ impl Association {
                     .start(Timer::Reconfig, now, self.rto_mgr.get_rto());
             }
         }
-
-        raw_packets
     }
 
     fn gather_outbound_fast_retransmission_packets(
         &mut self,
-        mut raw_packets: Vec<Bytes>,
+        builder: &mut PacketBuilder,
         now: Instant,
-    ) -> Vec<Bytes> {
+    ) {
         if self.will_retransmit_fast {
             self.will_retransmit_fast = false;
 
-            let mut to_fast_retrans: Vec<Box<dyn Chunk + Send + Sync>> = vec![];
+            let mut to_fast_retrans = vec![];
             let mut fast_retrans_size = COMMON_HEADER_SIZE;
 
             let mut i = 0;
@@ -2183,8 +3254,10 @@ impl Association {
                         self.use_forward_tsn,
                         self.side,
                         &self.streams,
+                        &mut self.abandoned_messages,
+                        &mut self.events,
                     );
-                    to_fast_retrans.push(Box::new(c.clone()));
+                    to_fast_retrans.push(c.clone());
                     trace!(
                         "[{}] fast-retransmit: tsn={} sent={} htna={}",
                         self.side,
@@ -2196,37 +3269,22 @@ impl Association {
                 i += 1;
             }
 
-            if !to_fast_retrans.is_empty() {
-                if let Ok(raw) = self.create_packet(to_fast_retrans).marshal() {
-                    raw_packets.push(raw);
-                } else {
-                    warn!(
-                        "[{}] failed to serialize a DATA packet to be fast-retransmitted",
-                        self.side
-                    );
-                }
+            for c in to_fast_retrans {
+                builder.push_data(c);
             }
         }
-
-        raw_packets
     }
 
-    fn gather_outbound_sack_packets(&mut self, mut raw_packets: Vec<Bytes>) -> Vec<Bytes> {
+    fn gather_outbound_sack_packets(&mut self, builder: &mut PacketBuilder) {
         if self.ack_state == AckState::Immediate {
             self.ack_state = AckState::Idle;
             let sack = self.create_selective_ack_chunk();
             trace!("[{}] sending SACK: {}", self.side, sack);
-            if let Ok(raw) = self.create_packet(vec![Box::new(sack)]).marshal() {
-                raw_packets.push(raw);
-            } else {
-                warn!("[{}] failed to serialize a SACK packet", self.side);
-            }
+            builder.push_control(Box::new(sack));
         }
-
-        raw_packets
     }
 
-    fn gather_outbound_forward_tsn_packets(&mut self, mut raw_packets: Vec<Bytes>) -> Vec<Bytes> {
+    fn gather_outbound_forward_tsn_packets(&mut self, builder: &mut PacketBuilder) {
         /*log::debug!(
             "[{}] gatherOutboundForwardTSNPackets {}",
             self.name,
@@ -2239,75 +3297,61 @@ impl Association {
                 self.cumulative_tsn_ack_point,
             ) {
                 let fwd_tsn = self.create_forward_tsn();
-                if let Ok(raw) = self.create_packet(vec![Box::new(fwd_tsn)]).marshal() {
-                    raw_packets.push(raw);
-                } else {
-                    warn!("[{}] failed to serialize a Forward TSN packet", self.side);
-                }
+                builder.push_control(Box::new(fwd_tsn));
             }
         }
-
-        raw_packets
     }
 
+    /// gather_outbound_shutdown_packets adds the pending SHUTDOWN, SHUTDOWN-ACK or
+    /// SHUTDOWN-COMPLETE chunk. Returns false once SHUTDOWN-COMPLETE is sent.
     fn gather_outbound_shutdown_packets(
         &mut self,
-        mut raw_packets: Vec<Bytes>,
+        builder: &mut PacketBuilder,
         now: Instant,
-    ) -> (Vec<Bytes>, bool) {
+    ) -> bool {
         let mut ok = true;
 
         if self.will_send_shutdown {
             self.will_send_shutdown = false;
 
             let shutdown = ChunkShutdown {
-                cumulative_tsn_ack: self.cumulative_tsn_ack_point,
+                cumulative_tsn_ack: self.peer_last_tsn,
             };
 
-            if let Ok(raw) = self.create_packet(vec![Box::new(shutdown)]).marshal() {
+            self.timers
+                .start(Timer::T2Shutdown, now, self.rto_mgr.get_rto());
+            // RFC 4960 sec 9.2
+            //   The sender of the SHUTDOWN MAY also start an overall guard timer
+            //   'T5-shutdown-guard' to bound the overall time for the shutdown
+            //   sequence.
+            if self.timers.get(Timer::T5ShutdownGuard).is_none() {
                 self.timers
-                    .start(Timer::T2Shutdown, now, self.rto_mgr.get_rto());
-                raw_packets.push(raw);
-            } else {
-                warn!("[{}] failed to serialize a Shutdown packet", self.side);
+                    .start(Timer::T5ShutdownGuard, now, T5_SHUTDOWN_GUARD);
             }
+            builder.push_control(Box::new(shutdown));
         } else if self.will_send_shutdown_ack {
             self.will_send_shutdown_ack = false;
 
             let shutdown_ack = ChunkShutdownAck {};
 
-            if let Ok(raw) = self.create_packet(vec![Box::new(shutdown_ack)]).marshal() {
-                self.timers
-                    .start(Timer::T2Shutdown, now, self.rto_mgr.get_rto());
-                raw_packets.push(raw);
-            } else {
-                warn!("[{}] failed to serialize a ShutdownAck packet", self.side);
-            }
+            self.timers
+                .start(Timer::T2Shutdown, now, self.rto_mgr.get_rto());
+            builder.push_control(Box::new(shutdown_ack));
         } else if self.will_send_shutdown_complete {
             self.will_send_shutdown_complete = false;
 
             let shutdown_complete = ChunkShutdownComplete {};
 
-            if let Ok(raw) = self
-                .create_packet(vec![Box::new(shutdown_complete)])
-                .marshal()
-            {
-                raw_packets.push(raw);
-                ok = false;
-            } else {
-                warn!(
-                    "[{}] failed to serialize a ShutdownComplete packet",
-                    self.side
-                );
-            }
+            builder.push_control(Box::new(shutdown_complete));
+            ok = false;
         }
 
-        (raw_packets, ok)
+        ok
     }
 
-    /// get_data_packets_to_retransmit is called when T3-rtx is timed out and retransmit outstanding data chunks
+    /// get_data_chunks_to_retransmit is called when T3-rtx is timed out and retransmit outstanding data chunks
     /// that are not acked or abandoned yet.
-    fn get_data_packets_to_retransmit(&mut self, now: Instant) -> Vec<Packet> {
+    fn get_data_chunks_to_retransmit(&mut self, now: Instant) -> Vec<ChunkPayloadData> {
         let awnd = std::cmp::min(self.cwnd, self.rwnd);
         let mut chunks = vec![];
         let mut bytes_to_send = 0;
@@ -2345,6 +3389,8 @@ impl Association {
                     self.use_forward_tsn,
                     self.side,
                     &self.streams,
+                    &mut self.abandoned_messages,
+                    &mut self.events,
                 );
 
                 trace!(
@@ -2360,7 +3406,7 @@ impl Association {
             i += 1;
         }
 
-        self.bundle_data_chunks_into_packets(chunks)
+        chunks
     }
 
     /// pop_pending_data_chunks_to_send pops chunks from the pending queues as many as
@@ -2371,6 +3417,14 @@ impl Association {
     ) -> (Vec<ChunkPayloadData>, Vec<u16>) {
         let mut chunks = vec![];
         let mut sis_to_reset = vec![]; // stream identifiers to reset
+
+        // packets needed so far to bundle the popped chunks, for Max.Burst
+        let mut n_packets = 0;
+        let mut bytes_in_packet = COMMON_HEADER_SIZE;
+        if self.inflight_queue.is_empty() {
+            // No SACK is coming to renew the Max.Burst budget
+            self.burst_budget = self.max_burst;
+        }
         if !self.pending_queue.is_empty() {
             // RFC 4960 sec 6.1.  Transmission of DATA Chunks
             //   A) At any given time, the data sender MUST NOT transmit new data to
@@ -2408,6 +3462,27 @@ impl Association {
                     break; // no more rwnd
                 }
 
+                // RFC 9260 sec 6.1
+                //   D) ... The data sender SHOULD NOT send more than Max.Burst packets
+                //      of new DATA chunks at once.
+                // The budget is renewed by each SACK, so that an ack releases at most
+                // Max.Burst packets. Estimate the packet count from the new DATA
+                // chunks alone. PacketBuilder also places control chunks and
+                // retransmissions ahead of them, so this is a lower bound on the
+                // packets sent.
+                if self.max_burst > 0 {
+                    if n_packets == 0 || bytes_in_packet + data_len as u32 > self.mtu {
+                        if self.burst_budget == 0 {
+                            trace!("[{}] Max.Burst reached ({} packets)", self.side, n_packets);
+                            break;
+                        }
+                        self.burst_budget -= 1;
+                        n_packets += 1;
+                        bytes_in_packet = COMMON_HEADER_SIZE;
+                    }
+                    bytes_in_packet += DATA_CHUNK_HEADER_SIZE + data_len as u32;
+                }
+
                 self.rwnd -= data_len as u32;
 
                 if let Some(chunk) = self.move_pending_data_chunk_to_inflight_queue(
@@ -2422,16 +3497,8 @@ impl Association {
             // the data sender can always have one DATA chunk in flight to the receiver
             if chunks.is_empty() && self.inflight_queue.is_empty() {
                 // Send zero window probe
-                if let Some(c) = self.pending_queue.peek() {
-                    let (beginning_fragment, unordered) = (c.beginning_fragment, c.unordered);
-
-                    if let Some(chunk) = self.move_pending_data_chunk_to_inflight_queue(
-                        beginning_fragment,
-                        unordered,
-                        now,
-                    ) {
-                        chunks.push(chunk);
-                    }
+                if let Some(chunk) = self.pop_zero_window_probe_chunk(now) {
+                    chunks.push(chunk);
                 }
             }
         }
@@ -2439,35 +3506,198 @@ impl Association {
         (chunks, sis_to_reset)
     }
 
-    /// bundle_data_chunks_into_packets packs DATA chunks into packets. It tries to bundle
-    /// DATA chunks into a packet so long as the resulting packet size does not exceed
-    /// the path MTU.
-    fn bundle_data_chunks_into_packets(&self, chunks: Vec<ChunkPayloadData>) -> Vec<Packet> {
+    /// pop_paced_packets releases the next batch of queued packets along with the
+    /// pacing slot they should be sent at. A batch is released up to one slot
+    /// ahead, so that the driver can schedule it; the batch after it is released
+    /// once that slot has come, which is reported through `poll_timeout`.
+    fn pop_paced_packets(&mut self, now: Instant) -> (Vec<Bytes>, Option<Instant>) {
+        if self.paced_packets.is_empty() || self.last_pacing_slot.is_some_and(|s| s > now) {
+            return (vec![], None);
+        }
+
+        let mut slot = self.next_pacing_slot.take().unwrap_or(now).max(now);
         let mut packets = vec![];
-        let mut chunks_to_send = vec![];
-        let mut bytes_in_packet = COMMON_HEADER_SIZE;
+        while let Some(p) = self.paced_packets.front() {
+            let bytes = p.len() as u64;
+            if let Some(next_slot) = self.pacer.delay(
+                slot,
+                bytes,
+                self.cwnd as u64,
+                self.rto_mgr.srtt,
+                self.mtu as u64,
+            ) {
+                trace!(
+                    "[{}] pacing: {} packets held until {:?}",
+                    self.side,
+                    self.paced_packets.len(),
+                    next_slot.saturating_duration_since(now)
+                );
+                if packets.is_empty() {
+                    // Nothing fits the slot, so release the batch at the next one
+                    slot = next_slot;
+                    continue;
+                }
+                self.next_pacing_slot = Some(next_slot);
+                break;
+            }
 
-        for c in chunks {
-            // RFC 4960 sec 6.1.  Transmission of DATA Chunks
-            //   Multiple DATA chunks committed for transmission MAY be bundled in a
-            //   single packet.  Furthermore, DATA chunks being retransmitted MAY be
-            //   bundled with new DATA chunks, as long as the resulting packet size
-            //   does not exceed the path MTU.
-            if bytes_in_packet + c.user_data.len() as u32 > self.mtu {
-                packets.push(self.create_packet(chunks_to_send));
-                chunks_to_send = vec![];
-                bytes_in_packet = COMMON_HEADER_SIZE;
+            self.pacer.on_transmit(bytes);
+            if let Some(p) = self.paced_packets.pop_front() {
+                packets.push(p);
             }
+        }
+
+        self.last_pacing_slot = Some(slot);
+        (packets, Some(slot))
+    }
+
+    /// validate_cwnd_after_idle decays cwnd for every RTO that passed without any
+    /// DATA chunk being sent, so that an idle association does not release a
+    /// full-cwnd burst at line rate when the application resumes.
+    fn validate_cwnd_after_idle(&mut self, now: Instant) {
+        // RFC 9260 sec 7.2.1.  Slow-Start
+        //   When the endpoint does not transmit data on a given transport
+        //   address, the cwnd of the transport address SHOULD be adjusted to
+        //   max(cwnd/2, 4*MTU) per RTO.
+        if !self.inflight_queue.is_empty() {
+            return;
+        }
+        let last_data_sent = match self.last_data_sent {
+            Some(last_data_sent) => last_data_sent,
+            None => return,
+        };
 
-            bytes_in_packet += DATA_CHUNK_HEADER_SIZE + c.user_data.len() as u32;
-            chunks_to_send.push(Box::new(c));
+        let rto = Duration::from_millis(self.rto_mgr.get_rto());
+        let idle = now.saturating_duration_since(last_data_sent);
+        if rto.is_zero() || idle < rto {
+            return;
         }
 
-        if !chunks_to_send.is_empty() {
-            packets.push(self.create_packet(chunks_to_send));
+        // cwnd is down to 4*MTU after at most 32 halvings, however long the idle
+        let n_rtos = idle.as_nanos() / rto.as_nanos();
+        for _ in 0..n_rtos.min(u32::BITS as u128) {
+            self.cwnd = std::cmp::min(self.cwnd, std::cmp::max(self.cwnd / 2, 4 * self.mtu));
         }
+        trace!(
+            "[{}] updated cwnd={} after idle of {:?}",
+            self.side,
+            self.cwnd,
+            idle
+        );
+
+        // The remainder of the idle counts towards the next RTO
+        let remainder = Duration::from_nanos((idle.as_nanos() % rto.as_nanos()) as u64);
+        self.last_data_sent = Some(now - remainder);
+    }
+
+    /// pop_zero_window_probe_chunk moves the next pending chunk to the inflight queue
+    /// to be sent as a zero window probe, and arms the zero window probe timer.
+    fn pop_zero_window_probe_chunk(&mut self, now: Instant) -> Option<ChunkPayloadData> {
+        let (beginning_fragment, unordered) = {
+            let c = self.pending_queue.peek()?;
+            (c.beginning_fragment, c.unordered)
+        };
+
+        let chunk =
+            self.move_pending_data_chunk_to_inflight_queue(beginning_fragment, unordered, now)?;
+
+        debug!(
+            "[{}] sending zero window probe: tsn={} rwnd={}",
+            self.side, chunk.tsn, self.rwnd
+        );
+        self.stats.inc_zero_window_probes();
+        self.timers.stop(Timer::T3RTX);
+        self.timers
+            .restart_if_stale(Timer::ZeroWindowProbe, now, self.rto_mgr.get_rto());
+
+        Some(chunk)
+    }
+
+    /// get_zero_window_probe_chunk is called when the zero window probe timer
+    /// fires. It retransmits the oldest outstanding chunk, or sends a new one if
+    /// nothing is in flight, so that the peer answers with its current window.
+    fn get_zero_window_probe_chunk(&mut self, now: Instant) -> Option<ChunkPayloadData> {
+        let mut i = self.cumulative_tsn_ack_point + 1;
+        let mut probe = None;
+        while let Some(c) = self.inflight_queue.get_mut(i) {
+            if !c.acked && !c.abandoned() {
+                // A chunk out of retransmissions or lifetime is left to FORWARD TSN
+                Association::check_partial_reliability_status(
+                    c,
+                    now,
+                    self.use_forward_tsn,
+                    self.side,
+                    &self.streams,
+                    &mut self.abandoned_messages,
+                    &mut self.events,
+                );
+                if !c.abandoned() {
+                    c.retransmit = false;
+                    c.nsent += 1;
+                    probe = Some(c.clone());
+                    break;
+                }
+            }
+            i += 1;
+        }
+
+        let chunk = match probe {
+            Some(chunk) => {
+                debug!(
+                    "[{}] retransmitting zero window probe: tsn={} nsent={}",
+                    self.side, chunk.tsn, chunk.nsent
+                );
+                self.stats.inc_zero_window_probes();
+                chunk
+            }
+            None => self.pop_zero_window_probe_chunk(now)?,
+        };
+
+        Some(chunk)
+    }
 
-        packets
+    /// is_zero_window_probing returns true while the peer's window is closed and
+    /// the zero window probe timer is running.
+    fn is_zero_window_probing(&self) -> bool {
+        self.timers.get(Timer::ZeroWindowProbe).is_some()
+    }
+
+    /// update_zero_window_probe enters or leaves zero window probing after the
+    /// peer's rwnd has been updated by a SACK.
+    fn update_zero_window_probe(&mut self, now: Instant) {
+        let has_data_to_send =
+            self.inflight_queue.get_num_bytes() > 0 || !self.pending_queue.is_empty();
+
+        if self.rwnd == 0 && has_data_to_send {
+            if !self.is_zero_window_probing() {
+                debug!(
+                    "[{}] peer window closed, start zero window probing (inflight={} pending={})",
+                    self.side,
+                    self.inflight_queue.get_num_bytes(),
+                    self.pending_queue.get_num_bytes()
+                );
+                self.timers
+                    .start(Timer::ZeroWindowProbe, now, self.rto_mgr.get_rto());
+            }
+            // RFC 4960 sec 6.1 A) rwnd is 0, T3-rtx would only retransmit data
+            // the peer cannot accept. Probes are paced by their own timer.
+            self.timers.stop(Timer::T3RTX);
+        } else if self.is_zero_window_probing() {
+            debug!(
+                "[{}] peer window opened, stop zero window probing (rwnd={})",
+                self.side, self.rwnd
+            );
+            self.timers.stop(Timer::ZeroWindowProbe);
+            self.will_send_zero_window_probe = false;
+
+            // The peer dropped anything that arrived while its window was
+            // closed. Retransmit it now instead of waiting for T3-rtx.
+            if !self.inflight_queue.is_empty() {
+                self.inflight_queue.mark_all_to_retrasmit();
+                self.timers
+                    .restart_if_stale(Timer::T3RTX, now, self.rto_mgr.get_rto());
+            }
+        }
     }
 
     /// generate_next_tsn returns the my_next_tsn and increases it. The caller should hold the lock.
@@ -2477,6 +3707,13 @@ impl Association {
         tsn
     }
 
+    /// generate_message_handle returns a handle to track a new message.
+    pub(crate) fn generate_message_handle(&mut self) -> MessageHandle {
+        let handle = MessageHandle(self.next_message_handle);
+        self.next_message_handle += 1;
+        handle
+    }
+
     /// generate_next_rsn returns the my_next_rsn and increases it. The caller should hold the lock.
     fn generate_next_rsn(&mut self) -> u32 {
         let rsn = self.my_next_rsn;
@@ -2484,12 +3721,32 @@ impl Association {
         rsn
     }
 
+    /// abandon_chunk marks the chunk as abandoned, reporting its message the
+    /// first time one of its fragments is.
+    fn abandon_chunk(
+        c: &mut ChunkPayloadData,
+        abandoned_messages: &mut FxHashSet<MessageHandle>,
+        events: &mut VecDeque<Event>,
+    ) {
+        c.set_abandoned(true);
+        if let Some(handle) = c.message_handle {
+            if abandoned_messages.insert(handle) {
+                events.push_back(Event::MessageAbandoned {
+                    stream: c.stream_identifier,
+                    handle,
+                });
+            }
+        }
+    }
+
     fn check_partial_reliability_status(
         c: &mut ChunkPayloadData,
         now: Instant,
         use_forward_tsn: bool,
         side: Side,
         streams: &FxHashMap<u16, StreamState>,
+        abandoned_messages: &mut FxHashSet<MessageHandle>,
+        events: &mut VecDeque<Event>,
     ) {
         if !use_forward_tsn {
             return;
@@ -2511,7 +3768,7 @@ impl Association {
 
             if reliability_type == ReliabilityType::Rexmit {
                 if c.nsent >= reliability_value {
-                    c.set_abandoned(true);
+                    Association::abandon_chunk(c, abandoned_messages, events);
                     trace!(
                         "[{}] marked as abandoned: tsn={} ppi={} (remix: {})",
                         side,
@@ -2524,7 +3781,7 @@ impl Association {
                 if let Some(since) = &c.since {
                     let elapsed = now.duration_since(*since);
                     if elapsed.as_millis() as u32 >= reliability_value {
-                        c.set_abandoned(true);
+                        Association::abandon_chunk(c, abandoned_messages, events);
                         trace!(
                             "[{}] marked as abandoned: tsn={} ppi={} (timed: {:?})",
                             side,
@@ -2543,11 +3800,32 @@ impl Association {
     }
 
     fn create_selective_ack_chunk(&mut self) -> ChunkSelectiveAck {
+        let mut gap_ack_blocks = self.payload_queue.get_gap_ack_blocks(self.peer_last_tsn);
+        let mut duplicate_tsn = self.payload_queue.pop_duplicates();
+
+        // Keep the SACK within a single packet. Gap ack blocks and duplicate TSNs
+        // take 4 bytes each. The blocks closest to the cumulative TSN tell the
+        // sender what to fast retransmit first, so the highest ones are dropped
+        // first. Duplicate TSNs are informational and only get the remaining room.
+        let max_entries = (self.mtu as usize).saturating_sub(
+            COMMON_HEADER_SIZE as usize + CHUNK_HEADER_SIZE + SELECTIVE_ACK_HEADER_SIZE,
+        ) / 4;
+        if gap_ack_blocks.len() > max_entries {
+            debug!(
+                "[{}] SACK: reporting {} of {} gap ack blocks",
+                self.side,
+                max_entries,
+                gap_ack_blocks.len()
+            );
+            gap_ack_blocks.truncate(max_entries);
+        }
+        duplicate_tsn.truncate(max_entries - gap_ack_blocks.len());
+
         ChunkSelectiveAck {
             cumulative_tsn_ack: self.peer_last_tsn,
-            advertised_receiver_window_credit: self.get_my_receiver_window_credit(),
-            gap_ack_blocks: self.payload_queue.get_gap_ack_blocks(self.peer_last_tsn),
-            duplicate_tsn: self.payload_queue.pop_duplicates(),
+            advertised_receiver_window_credit: self.get_advertised_receiver_window_credit(),
+            gap_ack_blocks,
+            duplicate_tsn,
         }
     }
 
@@ -2623,6 +3901,8 @@ impl Association {
                 self.use_forward_tsn,
                 self.side,
                 &self.streams,
+                &mut self.abandoned_messages,
+                &mut self.events,
             );
 
             trace!(
@@ -2646,11 +3926,28 @@ impl Association {
         }
     }
 
+    /// use_forward_tsn_allowed reports whether partial reliability may be used,
+    /// as far as is known before the INIT or INIT ACK from the peer is processed.
+    pub(crate) fn use_forward_tsn_allowed(&self) -> bool {
+        self.supported_extensions.forward_tsn
+            && self.negotiated.as_ref().map_or(true, |n| n.use_forward_tsn)
+    }
+
+    /// use_reconfig reports whether streams may be reset, as far as is known
+    /// before the INIT or INIT ACK from the peer is processed.
+    pub(crate) fn use_reconfig(&self) -> bool {
+        self.supported_extensions.reconfig
+            && self.negotiated.as_ref().map_or(true, |n| n.use_reconfig)
+    }
+
     pub(crate) fn send_reset_request(&mut self, stream_identifier: StreamId) -> Result<()> {
         let state = self.state();
         if state != AssociationState::Established {
             return Err(Error::ErrResetPacketInStateNotExist);
         }
+        if !self.use_reconfig() {
+            return Err(Error::ErrReconfigNotSupported);
+        }
 
         // Create DATA chunk which only contains valid stream identifier with
         // nil userData and use it as a EOS from the stream.
@@ -2668,26 +3965,275 @@ impl Association {
         Ok(())
     }
 
-    /// send_payload_data sends the data chunks.
-    pub(crate) fn send_payload_data(&mut self, chunks: Vec<ChunkPayloadData>) -> Result<()> {
+    fn check_payload_data_state(&self) -> Result<()> {
+        // Writes are queued from the start of the handshake, the first of them being
+        // bundled with the COOKIE ECHO.
         let state = self.state();
-        if state != AssociationState::Established {
+        if !matches!(
+            state,
+            AssociationState::CookieWait
+                | AssociationState::CookieEchoed
+                | AssociationState::Established
+        ) {
             return Err(Error::ErrPayloadDataStateNotExist);
         }
+        Ok(())
+    }
+
+    /// has_outstanding_data reports whether DATA chunks are still waiting to be
+    /// sent or acknowledged, which holds back the SHUTDOWN exchange.
+    fn has_outstanding_data(&self) -> bool {
+        !self.inflight_queue.is_empty() || !self.pending_queue.is_empty()
+    }
+
+    /// send_payload_data sends the data chunks.
+    pub(crate) fn send_payload_data(&mut self, chunks: Vec<ChunkPayloadData>) -> Result<()> {
+        self.check_payload_data_state()?;
+        self.queue_expiry_stale = true;
+
+        // Fragments of different messages must not interleave. The pending queue
+        // sends the fragments of a message back to back from its ordered or
+        // unordered queue, so while a message is being written in pieces, only the
+        // chunks queued behind its missing fragments are held back. The others
+        // are sent before it starts, or after it ends.
+        let partial_unordered = self.partial_write_stream.map(|si| {
+            self.streams
+                .get(&si)
+                .and_then(|s| s.partial_message.as_ref())
+                .map(|m| m.unordered)
+        });
 
         // Push the chunks into the pending queue first.
+        for c in chunks {
+            match partial_unordered {
+                Some(unordered) if unordered.map_or(true, |u| u == c.unordered) => {
+                    self.deferred_payload.push_back(c)
+                }
+                _ => self.pending_queue.push(c),
+            }
+        }
+
+        self.awake_write_loop();
+        Ok(())
+    }
+
+    /// send_partial_payload_data sends the data chunks of a message being written
+    /// in pieces on the stream, releasing the chunks held back once it ends.
+    pub(crate) fn send_partial_payload_data(
+        &mut self,
+        stream_identifier: StreamId,
+        chunks: Vec<ChunkPayloadData>,
+        end_of_record: bool,
+    ) -> Result<()> {
+        self.check_payload_data_state()?;
+        self.queue_expiry_stale = true;
+
         for c in chunks {
             self.pending_queue.push(c);
         }
 
+        if end_of_record {
+            self.partial_write_stream = None;
+            for c in std::mem::take(&mut self.deferred_payload) {
+                self.pending_queue.push(c);
+            }
+        } else {
+            self.partial_write_stream = Some(stream_identifier);
+        }
+
         self.awake_write_loop();
         Ok(())
     }
 
+    /// discard_pending_messages removes the messages none of whose fragments was
+    /// sent yet that match the predicate, applied to their first fragment, and
+    /// returns their chunks. The stream sequence numbers of the ordered messages
+    /// queued behind them are renumbered to leave no gap.
+    pub(crate) fn discard_pending_messages<F>(
+        &mut self,
+        mut should_discard: F,
+    ) -> Vec<ChunkPayloadData>
+    where
+        F: FnMut(&ChunkPayloadData) -> bool,
+    {
+        // Messages held back are queued behind the pending ones
+        let mut n_removed = FxHashMap::default();
+        let mut discarded = self
+            .pending_queue
+            .remove_messages(&mut should_discard, &mut n_removed);
+        discarded.extend(remove_messages(
+            &mut self.deferred_payload,
+            &mut should_discard,
+            &mut n_removed,
+        ));
+
+        for (si, n) in n_removed {
+            if let Some(s) = self.streams.get_mut(&si) {
+                s.sequence_number = s.sequence_number.wrapping_sub(n);
+            }
+        }
+
+        let mut bytes_discarded_per_stream: FxHashMap<StreamId, i64> = FxHashMap::default();
+        for c in &discarded {
+            *bytes_discarded_per_stream
+                .entry(c.stream_identifier)
+                .or_default() += c.user_data.len() as i64;
+        }
+        for (si, n_bytes) in bytes_discarded_per_stream {
+            if let Some(s) = self.streams.get_mut(&si) {
+                if s.on_buffer_released(n_bytes) {
+                    self.events
+                        .push_back(Event::Stream(StreamEvent::BufferedAmountLow { id: si }));
+                }
+            }
+        }
+
+        if !discarded.is_empty() {
+            debug!(
+                "[{}] discarded {} pending chunks",
+                self.side,
+                discarded.len()
+            );
+        }
+
+        discarded
+    }
+
+    /// expire_pending_messages discards the messages that have been queued for
+    /// longer than the max queue age of their stream. Messages are timestamped
+    /// the first time the association is polled for transmission after they
+    /// were written. The queues are only walked when messages were written or
+    /// the next message is due to expire, which `poll_timeout` reports.
+    fn expire_pending_messages(&mut self, now: Instant) {
+        if !self.queue_expiry_stale && self.next_queue_expiry.map_or(true, |t| now < t) {
+            return;
+        }
+        self.queue_expiry_stale = false;
+        self.next_queue_expiry = None;
+        if !self.streams.values().any(|s| s.max_queue_age.is_some()) {
+            return;
+        }
+
+        for c in self
+            .pending_queue
+            .iter_mut()
+            .chain(self.deferred_payload.iter_mut())
+        {
+            c.queued_at.get_or_insert(now);
+        }
+
+        let max_ages: FxHashMap<StreamId, Duration> = self
+            .streams
+            .iter()
+            .filter_map(|(si, s)| s.max_queue_age.map(|max_age| (*si, max_age)))
+            .collect();
+        let expiry = |c: &ChunkPayloadData| match (max_ages.get(&c.stream_identifier), c.queued_at)
+        {
+            (Some(max_age), Some(queued_at)) if c.beginning_fragment => Some(queued_at + *max_age),
+            _ => None,
+        };
+        let expired = self.discard_pending_messages(|c| expiry(c).is_some_and(|t| t <= now));
+        self.next_queue_expiry = self
+            .pending_queue
+            .iter_mut()
+            .chain(self.deferred_payload.iter_mut())
+            .filter_map(|c| expiry(c))
+            .min();
+
+        for c in expired {
+            if let (true, Some(handle)) = (c.beginning_fragment, c.message_handle) {
+                self.events.push_back(Event::MessageAbandoned {
+                    stream: c.stream_identifier,
+                    handle,
+                });
+            }
+        }
+    }
+
     /// buffered_amount returns total amount (in bytes) of currently buffered user data.
-    /// This is used only by testing.
     pub(crate) fn buffered_amount(&self) -> usize {
-        self.pending_queue.get_num_bytes() + self.inflight_queue.get_num_bytes()
+        self.pending_queue.get_num_bytes()
+            + self.inflight_queue.get_num_bytes()
+            + self
+                .deferred_payload
+                .iter()
+                .map(|c| c.user_data.len())
+                .sum::<usize>()
+    }
+
+    /// fail_unsent_messages empties the inflight and pending queues, reporting the
+    /// messages not fully acknowledged with `Event::SendFailed` (RFC 6458 sec
+    /// 6.1.11).
+    fn fail_unsent_messages(&mut self) {
+        let mut chunks = vec![];
+        let mut tsn = self.cumulative_tsn_ack_point.wrapping_add(1);
+        while let Some(c) = self.inflight_queue.pop(tsn) {
+            chunks.push(c);
+            tsn = tsn.wrapping_add(1);
+        }
+        chunks.extend(self.pending_queue.drain());
+        chunks.extend(self.deferred_payload.drain(..));
+        self.partial_write_stream = None;
+        self.abandoned_messages.clear();
+        self.report_failed_messages(chunks);
+    }
+
+    /// report_failed_messages reassembles the messages of the chunks, which were
+    /// not delivered, and reports them with `Event::SendFailed`. Messages
+    /// abandoned under partial reliability are not reported.
+    fn report_failed_messages(&mut self, chunks: Vec<ChunkPayloadData>) {
+        // Fragments of a message are contiguous in each queue, but ordered and
+        // unordered messages of a stream may interleave.
+        let mut messages = vec![];
+        let mut partial: HashMap<(StreamId, bool), (FailedMessage, BytesMut, bool)> =
+            HashMap::new();
+        for c in chunks {
+            let key = (c.stream_identifier, c.unordered);
+            if !c.beginning_fragment && !partial.contains_key(&key) {
+                // The leading fragments were cumulatively acked, so the peer has
+                // part of the message and it cannot be reported for a retry
+                debug!(
+                    "[{}] not reporting the rest of a partly acked message: tsn={}",
+                    self.side, c.tsn
+                );
+                continue;
+            }
+            let (m, payload, failed) = partial.entry(key).or_insert_with(|| {
+                (
+                    FailedMessage {
+                        stream_identifier: c.stream_identifier,
+                        ppi: c.payload_type,
+                        payload: Bytes::new(),
+                        sent: false,
+                        handle: c.message_handle,
+                    },
+                    BytesMut::new(),
+                    true,
+                )
+            });
+            m.sent |= c.nsent > 0;
+            *failed &= !c.abandoned;
+            payload.extend_from_slice(&c.user_data);
+            if c.ending_fragment {
+                if let Some((mut m, payload, failed)) = partial.remove(&key) {
+                    // Gap acked fragments may not have been delivered yet, only
+                    // fully acked messages have been
+                    if failed && !payload.is_empty() {
+                        m.payload = payload.freeze();
+                        messages.push(m);
+                    }
+                }
+            }
+        }
+
+        if !messages.is_empty() {
+            debug!(
+                "[{}] {} messages could not be sent",
+                self.side,
+                messages.len()
+            );
+            self.events.push_back(Event::SendFailed { messages });
+        }
     }
 
     fn awake_write_loop(&self) {
@@ -2701,6 +4247,20 @@ impl Association {
         }
     }
 
+    /// start_linger_timer starts lingering once the association is closed.
+    fn start_linger_timer(&mut self, now: Instant) {
+        if self.lingering && self.timers.get(Timer::Linger).is_none() {
+            self.timers
+                .start(Timer::Linger, now, self.linger_timeout.as_millis() as u64);
+        }
+    }
+
+    fn on_linger_timeout(&mut self) {
+        debug!("[{}] linger timed out, draining", self.side);
+        self.lingering = false;
+        self.endpoint_events.push_back(EndpointEventInner::Drained);
+    }
+
     fn on_ack_timeout(&mut self) {
         trace!(
             "[{}] ack timed out (ack_state: {})",
@@ -2819,6 +4379,15 @@ impl Association {
                 self.awake_write_loop();
             }
 
+            Timer::ZeroWindowProbe => {
+                debug!(
+                    "[{}] zero window probe timed out: n_rtos={} rwnd={}",
+                    self.side, n_rtos, self.rwnd
+                );
+                self.will_send_zero_window_probe = true;
+                self.awake_write_loop();
+            }
+
             _ => {}
         }
     }
@@ -2827,6 +4396,7 @@ impl Association {
         match id {
             Timer::T1Init => {
                 error!("[{}] retransmission failure: T1-init", self.side);
+                self.fail_unsent_messages();
                 self.error = Some(AssociationError::HandshakeFailed(
                     Error::ErrHandshakeInitAck,
                 ));
@@ -2834,6 +4404,7 @@ impl Association {
 
             Timer::T1Cookie => {
                 error!("[{}] retransmission failure: T1-cookie", self.side);
+                self.fail_unsent_messages();
                 self.error = Some(AssociationError::HandshakeFailed(
                     Error::ErrHandshakeCookieEcho,
                 ));
@@ -2841,6 +4412,12 @@ impl Association {
 
             Timer::T2Shutdown => {
                 error!("[{}] retransmission failure: T2-shutdown", self.side);
+                self.abort_shutdown();
+            }
+
+            Timer::T5ShutdownGuard => {
+                error!("[{}] shutdown timed out: T5-shutdown-guard", self.side);
+                self.abort_shutdown();
             }
 
             Timer::T3RTX => {
@@ -2855,6 +4432,16 @@ impl Association {
         }
     }
 
+    /// abort_shutdown gives up on a SHUTDOWN exchange the peer stopped answering.
+    fn abort_shutdown(&mut self) {
+        // RFC 4960 sec 9.2
+        //   If the timer expires, the endpoint MUST send an ABORT and enter the
+        //   CLOSED state.
+        self.send_abort(vec![]);
+        self.error = Some(AssociationError::TimedOut);
+        let _ = self.close();
+    }
+
     /// Whether no timers are running
     #[cfg(test)]
     pub(crate) fn is_idle(&self) -> bool {
@@ -0,0 +1,73 @@
+use std::time::{Duration, Instant};
+
+/// Interval over which the pacer allows a burst, in msec
+const BURST_INTERVAL: u64 = 2;
+/// Minimum number of packets allowed in a burst
+const MIN_BURST_PACKETS: u64 = 2;
+/// Maximum number of packets allowed in a burst
+const MAX_BURST_PACKETS: u64 = 256;
+
+/// Pacer is a token bucket spreading the transmission of a congestion window
+/// over one smoothed RTT, i.e. at a rate of cwnd/SRTT.
+#[derive(Default, Debug)]
+pub(crate) struct Pacer {
+    tokens: u64,
+    capacity: u64,
+    last_refill: Option<Instant>,
+}
+
+impl Pacer {
+    /// delay returns the time at which a packet of `bytes` may be sent, or None if
+    /// it can be sent right away. `srtt` is in msec; zero disables pacing as no
+    /// RTT has been measured yet.
+    pub(crate) fn delay(
+        &mut self,
+        now: Instant,
+        bytes: u64,
+        cwnd: u64,
+        srtt: u64,
+        mtu: u64,
+    ) -> Option<Instant> {
+        if srtt == 0 || cwnd == 0 {
+            return None;
+        }
+
+        let capacity = (cwnd * BURST_INTERVAL / srtt)
+            .clamp(MIN_BURST_PACKETS * mtu, MAX_BURST_PACKETS * mtu)
+            .max(bytes);
+        if capacity != self.capacity {
+            self.capacity = capacity;
+            self.tokens = self.tokens.min(capacity);
+        }
+
+        match self.last_refill {
+            None => {
+                self.tokens = capacity;
+                self.last_refill = Some(now);
+            }
+            Some(last_refill) => {
+                let elapsed = now.saturating_duration_since(last_refill).as_micros() as u64;
+                let refill = elapsed.saturating_mul(cwnd) / (srtt * 1000);
+                // Keep accumulating elapsed time until at least one token is earned
+                if refill > 0 {
+                    self.tokens = self.tokens.saturating_add(refill).min(capacity);
+                    self.last_refill = Some(now);
+                }
+            }
+        }
+
+        if self.tokens >= bytes {
+            return None;
+        }
+
+        // Time to accumulate the missing tokens at cwnd/srtt bytes per msec
+        let missing = bytes - self.tokens;
+        let micros = (missing * srtt * 1000).div_ceil(cwnd);
+        Some(now + Duration::from_micros(micros))
+    }
+
+    /// on_transmit consumes the tokens for `bytes` that have been sent.
+    pub(crate) fn on_transmit(&mut self, bytes: u64) {
+        self.tokens = self.tokens.saturating_sub(bytes);
+    }
+}
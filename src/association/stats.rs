@@ -6,6 +6,8 @@ pub struct AssociationStats {
     n_t3timeouts: u64,
     n_ack_timeouts: u64,
     n_fast_retrans: u64,
+    n_zero_window_probes: u64,
+    n_duplicate_tsns: u64,
 }
 
 impl AssociationStats {
@@ -49,11 +51,29 @@ impl AssociationStats {
         self.n_fast_retrans
     }
 
+    pub fn inc_zero_window_probes(&mut self) {
+        self.n_zero_window_probes += 1;
+    }
+
+    pub fn get_num_zero_window_probes(&mut self) -> u64 {
+        self.n_zero_window_probes
+    }
+
+    pub fn inc_duplicate_tsns(&mut self) {
+        self.n_duplicate_tsns += 1;
+    }
+
+    pub fn get_num_duplicate_tsns(&mut self) -> u64 {
+        self.n_duplicate_tsns
+    }
+
     pub fn reset(&mut self) {
         self.n_datas = 0;
         self.n_sacks = 0;
         self.n_t3timeouts = 0;
         self.n_ack_timeouts = 0;
         self.n_fast_retrans = 0;
+        self.n_zero_window_probes = 0;
+        self.n_duplicate_tsns = 0;
     }
 }
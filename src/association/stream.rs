@@ -2,17 +2,23 @@ use crate::association::state::AssociationState;
 use crate::association::Association;
 use crate::chunk::chunk_payload_data::{ChunkPayloadData, PayloadProtocolIdentifier};
 use crate::error::{Error, Result};
-use crate::queue::reassembly_queue::{Chunks, ReassemblyQueue};
+use crate::queue::reassembly_queue::{Chunks, MessagePayload, PartialChunks, ReassemblyQueue};
 use crate::{ErrorCauseCode, Side};
 
-use crate::util::{ByteSlice, BytesArray, BytesSource};
-use bytes::Bytes;
+use crate::util::{BytesArray, BytesSource};
+use bytes::{Bytes, BytesMut};
 use log::{debug, error, trace};
 use std::fmt;
+use std::time::Duration;
 
 /// Identifier for a stream within a particular association
 pub type StreamId = u16;
 
+/// Identifier for a message written on an association, reported by
+/// `Event::MessageAcked` and `Event::MessageAbandoned`
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct MessageHandle(pub u64);
+
 /// Application events about streams
 #[derive(Debug, PartialEq, Eq)]
 pub enum StreamEvent {
@@ -49,6 +55,24 @@ pub enum StreamEvent {
         /// Which stream is now readable
         id: StreamId,
     },
+    /// The head message of a stream reached the partial delivery point and can
+    /// be read in pieces with `Stream::read_partial`
+    PartialDeliveryStarted {
+        /// Which stream is delivering a message in pieces
+        id: StreamId,
+    },
+    /// The peer abandoned the message being delivered in pieces, which will not
+    /// be completed
+    PartialDeliveryAborted {
+        /// Which stream was delivering the message
+        id: StreamId,
+    },
+    /// The stream was configured with partial reliability during the handshake,
+    /// but the peer does not support FORWARD TSN, so the stream was made reliable
+    PartialReliabilityDisabled {
+        /// Which stream was made reliable
+        id: StreamId,
+    },
 }
 
 /// Reliability type for stream
@@ -101,11 +125,47 @@ impl<'a> Stream<'a> {
     /// read_sctp reads a packet of len(p) bytes and returns the associated Payload
     /// Protocol Identifier.
     /// Returns EOF when the stream is reset or an error if the stream is closed
-    /// otherwise.
+    /// otherwise. Once a message has been partly read with `read_partial`, the
+    /// rest of it must be read with `read_partial` too.
     pub fn read_sctp(&mut self) -> Result<Option<Chunks>> {
         if let Some(s) = self.association.streams.get_mut(&self.stream_identifier) {
             if s.state == RecvSendState::ReadWritable || s.state == RecvSendState::Readable {
-                return Ok(s.reassembly_queue.read());
+                if s.reassembly_queue.is_partially_read() {
+                    return Err(Error::ErrPartialDeliveryInProgress);
+                }
+                let chunks = s.reassembly_queue.read();
+                if chunks.is_some() {
+                    self.association
+                        .on_receive_window_opened(self.stream_identifier);
+                }
+                return Ok(chunks);
+            }
+        }
+
+        Err(Error::ErrStreamClosed)
+    }
+
+    /// read_bytes reads the next message along with its Payload Protocol
+    /// Identifier, without copying its payload out of the received packets.
+    pub fn read_bytes(&mut self) -> Result<Option<(PayloadProtocolIdentifier, MessagePayload)>> {
+        Ok(self.read_sctp()?.map(|c| (c.ppi, c.into_payload())))
+    }
+
+    /// read_partial reads the next piece of the message in partial delivery,
+    /// along with whether it ends the message. When no message is in partial
+    /// delivery, the next complete message is read in one piece.
+    pub fn read_partial(&mut self) -> Result<Option<PartialChunks>> {
+        if let Some(s) = self.association.streams.get_mut(&self.stream_identifier) {
+            if s.state == RecvSendState::ReadWritable || s.state == RecvSendState::Readable {
+                let n = s.reassembly_queue.num_partial_messages();
+                let chunks = s.reassembly_queue.read_partial();
+                let after = s.reassembly_queue.num_partial_messages();
+                self.association.update_num_partial_messages(n, after);
+                if chunks.is_some() {
+                    self.association
+                        .on_receive_window_opened(self.stream_identifier);
+                }
+                return Ok(chunks);
             }
         }
 
@@ -114,31 +174,70 @@ impl<'a> Stream<'a> {
 
     /// write_sctp writes len(p) bytes from p to the DTLS connection
     pub fn write_sctp(&mut self, p: &Bytes, ppi: PayloadProtocolIdentifier) -> Result<usize> {
-        self.write_source(&mut ByteSlice::from_slice(p), ppi)
+        self.write_bytes_with_ppi(p.clone(), ppi)?;
+        Ok(p.len())
+    }
+
+    /// Send a message on the given stream without copying it.
+    ///
+    /// Uses the default payload protocol (PPI).
+    ///
+    /// Returns a handle to the message, reported once the message is acked or
+    /// abandoned.
+    pub fn write_bytes(&mut self, data: Bytes) -> Result<MessageHandle> {
+        self.write_bytes_with_ppi(data, self.get_default_payload_type()?)
+    }
+
+    /// Send a message on the given stream without copying it, with a specific
+    /// payload protocol.
+    ///
+    /// Returns a handle to the message, reported once the message is acked or
+    /// abandoned.
+    pub fn write_bytes_with_ppi(
+        &mut self,
+        data: Bytes,
+        ppi: PayloadProtocolIdentifier,
+    ) -> Result<MessageHandle> {
+        let handles = self.send_messages(std::slice::from_ref(&data), ppi)?;
+        Ok(handles[0])
+    }
+
+    /// Send each buffer as a separate message on the given stream without
+    /// copying them. Either all of the messages are written or none is.
+    ///
+    /// Uses the default payload protocol (PPI).
+    ///
+    /// Returns the handles to the messages, in the order they were given.
+    pub fn write_messages(&mut self, messages: Vec<Bytes>) -> Result<Vec<MessageHandle>> {
+        self.send_messages(&messages, self.get_default_payload_type()?)
     }
 
     /// Send data on the given stream.
     ///
     /// Uses the deafult payload protocol (PPI).
     ///
-    /// Returns the number of bytes successfully written.
-    pub fn write(&mut self, data: &[u8]) -> Result<usize> {
+    /// Returns a handle to the message, reported once the message is acked or
+    /// abandoned.
+    pub fn write(&mut self, data: &[u8]) -> Result<MessageHandle> {
         self.write_with_ppi(data, self.get_default_payload_type()?)
     }
 
     /// Send data on the given stream, with a specific payload protocol.
     ///
-    /// Returns the number of bytes successfully written.
-    pub fn write_with_ppi(&mut self, data: &[u8], ppi: PayloadProtocolIdentifier) -> Result<usize> {
-        self.write_source(&mut ByteSlice::from_slice(data), ppi)
+    /// Returns a handle to the message, reported once the message is acked or
+    /// abandoned.
+    pub fn write_with_ppi(
+        &mut self,
+        data: &[u8],
+        ppi: PayloadProtocolIdentifier,
+    ) -> Result<MessageHandle> {
+        self.write_bytes_with_ppi(Bytes::copy_from_slice(data), ppi)
     }
 
     /// write writes len(p) bytes from p with the default Payload Protocol Identifier
     pub fn write_chunk(&mut self, p: &Bytes) -> Result<usize> {
-        self.write_source(
-            &mut ByteSlice::from_slice(p),
-            self.get_default_payload_type()?,
-        )
+        self.write_bytes(p.clone())?;
+        Ok(p.len())
     }
 
     /// Send data on the given stream
@@ -154,18 +253,67 @@ impl<'a> Stream<'a> {
         )
     }
 
-    /// write_source writes BytesSource to the DTLS connection
-    fn write_source<B: BytesSource>(
+    /// write_partial writes a piece of a message, the piece written with
+    /// `end_of_record` set ending it. Pieces are fragmented as they are written,
+    /// so the message is not limited by the maximum message size. The payload
+    /// protocol identifier of the first piece applies to the whole message.
+    ///
+    /// Fragments of different messages cannot interleave, so until the message
+    /// ends, messages written on other streams with the same ordering are held
+    /// back, and writing other messages on this stream or writing pieces on
+    /// another stream fails with `ErrMessageInProgress`. Messages with the other
+    /// ordering may be sent before the message starts, but no DATA of other
+    /// messages is sent while it is being sent. Held back messages count against
+    /// the send buffer.
+    ///
+    /// Returns a handle to the message, the same for all of its pieces, reported
+    /// once the message is acked or abandoned. An empty message is not reported.
+    pub fn write_partial(
         &mut self,
-        source: &mut B,
+        data: &[u8],
         ppi: PayloadProtocolIdentifier,
-    ) -> Result<usize> {
-        if !self.is_writable() {
-            return Err(Error::ErrStreamClosed);
+        end_of_record: bool,
+    ) -> Result<MessageHandle> {
+        self.check_writable()?;
+
+        if self
+            .association
+            .partial_write_stream
+            .is_some_and(|id| id != self.stream_identifier)
+        {
+            return Err(Error::ErrMessageInProgress);
         }
 
-        if source.remaining() > self.association.max_message_size() as usize {
-            return Err(Error::ErrOutboundPacketTooLarge);
+        if !self.association.has_send_buffer_space(data.len()) {
+            return Err(Error::ErrSendBufferFull);
+        }
+
+        let handle = match self
+            .association
+            .streams
+            .get(&self.stream_identifier)
+            .and_then(|s| s.partial_message.as_ref())
+        {
+            Some(m) => m.handle,
+            None => self.association.generate_message_handle(),
+        };
+        if let Some(s) = self.association.streams.get_mut(&self.stream_identifier) {
+            let chunks = s.packetize_partial(data, ppi, end_of_record, handle);
+            self.association.send_partial_payload_data(
+                self.stream_identifier,
+                chunks,
+                end_of_record,
+            )?;
+
+            Ok(handle)
+        } else {
+            Err(Error::ErrStreamClosed)
+        }
+    }
+
+    fn check_writable(&self) -> Result<()> {
+        if !self.is_writable() {
+            return Err(Error::ErrStreamClosed);
         }
 
         let state: AssociationState = self.association.state();
@@ -173,17 +321,71 @@ impl<'a> Stream<'a> {
             AssociationState::ShutdownSent
             | AssociationState::ShutdownAckSent
             | AssociationState::ShutdownPending
-            | AssociationState::ShutdownReceived => return Err(Error::ErrStreamClosed),
-            _ => {}
-        };
+            | AssociationState::ShutdownReceived => Err(Error::ErrStreamClosed),
+            _ => Ok(()),
+        }
+    }
+
+    /// write_source writes BytesSource to the DTLS connection
+    fn write_source<B: BytesSource>(
+        &mut self,
+        source: &mut B,
+        ppi: PayloadProtocolIdentifier,
+    ) -> Result<usize> {
+        self.check_writable()?;
+
+        if source.remaining() > self.association.max_message_size() as usize {
+            return Err(Error::ErrOutboundPacketTooLarge);
+        }
 
         let (p, _) = source.pop_chunk(self.association.max_message_size() as usize);
 
+        self.send_messages(std::slice::from_ref(&p), ppi)?;
+        Ok(p.len())
+    }
+
+    /// send_messages fragments the messages, which share their buffers with the
+    /// DATA chunks, and queues them for sending. Fragments carry the handle of
+    /// their message, to report it. Returns the handles of the messages.
+    fn send_messages(
+        &mut self,
+        messages: &[Bytes],
+        ppi: PayloadProtocolIdentifier,
+    ) -> Result<Vec<MessageHandle>> {
+        self.check_writable()?;
+
+        let max_message_size = self.association.max_message_size() as usize;
+        if messages.iter().any(|m| m.len() > max_message_size) {
+            return Err(Error::ErrOutboundPacketTooLarge);
+        }
+
+        if let Some(s) = self.association.streams.get(&self.stream_identifier) {
+            if s.partial_message.is_some() {
+                return Err(Error::ErrMessageInProgress);
+            }
+        }
+
+        let n: usize = messages.iter().map(|m| m.len()).sum();
+        if !self.association.has_send_buffer_space(n) {
+            return Err(Error::ErrSendBufferFull);
+        }
+
+        let handles: Vec<MessageHandle> = messages
+            .iter()
+            .map(|_| self.association.generate_message_handle())
+            .collect();
         if let Some(s) = self.association.streams.get_mut(&self.stream_identifier) {
-            let chunks = s.packetize(&p, ppi);
+            let mut chunks = vec![];
+            for (m, handle) in messages.iter().zip(&handles) {
+                let mut fragments = s.packetize(m, ppi);
+                for c in &mut fragments {
+                    c.message_handle = Some(*handle);
+                }
+                chunks.extend(fragments);
+            }
             self.association.send_payload_data(chunks)?;
 
-            Ok(p.len())
+            Ok(handles)
         } else {
             Err(Error::ErrStreamClosed)
         }
@@ -208,21 +410,17 @@ impl<'a> Stream<'a> {
     /// stop closes the read-direction of the stream.
     /// Future calls to read are not permitted after calling stop.
     pub fn stop(&mut self) -> Result<()> {
-        let mut reset = false;
-        if let Some(s) = self.association.streams.get_mut(&self.stream_identifier) {
-            if s.state == RecvSendState::Readable || s.state == RecvSendState::ReadWritable {
-                reset = true;
-            }
-            s.state = ((s.state as u8) & 0x2).into();
-        }
-
-        if reset {
+        if self.is_readable() {
             // Reset the outgoing stream
             // https://tools.ietf.org/html/rfc6525
             self.association
                 .send_reset_request(self.stream_identifier)?;
         }
 
+        if let Some(s) = self.association.streams.get_mut(&self.stream_identifier) {
+            s.state = ((s.state as u8) & 0x2).into();
+        }
+
         Ok(())
     }
 
@@ -269,6 +467,11 @@ impl<'a> Stream<'a> {
         rel_type: ReliabilityType,
         rel_val: u32,
     ) -> Result<()> {
+        // Partial reliability needs FORWARD TSN, which the peer may not support
+        if rel_type != ReliabilityType::Reliable && !self.association.use_forward_tsn_allowed() {
+            return Err(Error::ErrForwardTsnNotSupported);
+        }
+
         if let Some(s) = self.association.streams.get_mut(&self.stream_identifier) {
             debug!(
                 "[{}] reliability params: ordered={} type={} value={}",
@@ -292,6 +495,69 @@ impl<'a> Stream<'a> {
         }
     }
 
+    /// cancel removes the message from the send queue, unless one of its fragments
+    /// was already sent. Returns whether the message was removed.
+    pub fn cancel(&mut self, handle: MessageHandle) -> Result<bool> {
+        if !self
+            .association
+            .streams
+            .contains_key(&self.stream_identifier)
+        {
+            return Err(Error::ErrStreamClosed);
+        }
+
+        let si = self.stream_identifier;
+        let discarded = self.association.discard_pending_messages(|c| {
+            c.stream_identifier == si && c.message_handle == Some(handle)
+        });
+        Ok(!discarded.is_empty())
+    }
+
+    /// discard_pending removes from the send queue the messages of the stream
+    /// none of whose fragments was sent yet. The message being written with
+    /// `write_partial` is kept. Returns the number of messages removed.
+    pub fn discard_pending(&mut self) -> Result<usize> {
+        if !self
+            .association
+            .streams
+            .contains_key(&self.stream_identifier)
+        {
+            return Err(Error::ErrStreamClosed);
+        }
+
+        let si = self.stream_identifier;
+        let discarded = self
+            .association
+            .discard_pending_messages(|c| c.stream_identifier == si);
+        Ok(discarded.iter().filter(|c| c.beginning_fragment).count())
+    }
+
+    /// max_queue_age returns how long messages may wait in the send queue before
+    /// being discarded, if they may not wait indefinitely.
+    pub fn max_queue_age(&self) -> Result<Option<Duration>> {
+        if let Some(s) = self.association.streams.get(&self.stream_identifier) {
+            Ok(s.max_queue_age)
+        } else {
+            Err(Error::ErrStreamClosed)
+        }
+    }
+
+    /// set_max_queue_age sets how long messages may wait in the send queue.
+    /// Messages none of whose fragments was sent within that time are discarded,
+    /// those written with `write` being reported by `Event::MessageAbandoned`.
+    ///
+    /// As the association only learns the time when it is polled, the age of a
+    /// message counts from the first `poll_transmit` after it was written.
+    pub fn set_max_queue_age(&mut self, max_age: Option<Duration>) -> Result<()> {
+        if let Some(s) = self.association.streams.get_mut(&self.stream_identifier) {
+            s.max_queue_age = max_age;
+            self.association.queue_expiry_stale = true;
+            Ok(())
+        } else {
+            Err(Error::ErrStreamClosed)
+        }
+    }
+
     /// buffered_amount_low_threshold returns the number of bytes of buffered outgoing data that is
     /// considered "low." Defaults to 0.
     pub fn buffered_amount_low_threshold(&self) -> Result<usize> {
@@ -349,7 +615,21 @@ pub struct StreamState {
     pub(crate) reliability_value: u32,
     pub(crate) buffered_amount: usize,
     pub(crate) buffered_amount_low: usize,
+    pub(crate) partial_message: Option<PartialMessage>,
+    pub(crate) max_queue_age: Option<Duration>,
 }
+
+/// PartialMessage is an outgoing message being written in pieces
+#[derive(Debug)]
+pub(crate) struct PartialMessage {
+    pub(crate) handle: MessageHandle,
+    pub(crate) ppi: PayloadProtocolIdentifier,
+    pub(crate) unordered: bool,
+    pub(crate) started: bool,
+    /// Bytes not fragmented yet, at most one fragment unless ending the message
+    pub(crate) tail: BytesMut,
+}
+
 impl StreamState {
     pub(crate) fn new(
         side: Side,
@@ -370,6 +650,8 @@ impl StreamState {
             reliability_value: 0,
             buffered_amount: 0,
             buffered_amount_low: 0,
+            partial_message: None,
+            max_queue_age: None,
         }
     }
 
@@ -377,25 +659,27 @@ impl StreamState {
         self.reassembly_queue.push(pd.clone());
     }
 
-    pub(crate) fn handle_forward_tsn_for_ordered(&mut self, ssn: u16) {
+    /// Returns whether a partial delivery was aborted.
+    pub(crate) fn handle_forward_tsn_for_ordered(&mut self, ssn: u16) -> bool {
         if self.unordered {
-            return; // unordered chunks are handled by handleForwardUnordered method
+            return false; // unordered chunks are handled by handleForwardUnordered method
         }
 
         // Remove all chunks older than or equal to the new TSN from
         // the reassembly_queue.
-        self.reassembly_queue.forward_tsn_for_ordered(ssn);
+        self.reassembly_queue.forward_tsn_for_ordered(ssn)
     }
 
-    pub(crate) fn handle_forward_tsn_for_unordered(&mut self, new_cumulative_tsn: u32) {
+    /// Returns whether a partial delivery was aborted.
+    pub(crate) fn handle_forward_tsn_for_unordered(&mut self, new_cumulative_tsn: u32) -> bool {
         if !self.unordered {
-            return; // ordered chunks are handled by handleForwardTSNOrdered method
+            return false; // ordered chunks are handled by handleForwardTSNOrdered method
         }
 
         // Remove all chunks older than or equal to the new TSN from
         // the reassembly_queue.
         self.reassembly_queue
-            .forward_tsn_for_unordered(new_cumulative_tsn);
+            .forward_tsn_for_unordered(new_cumulative_tsn)
     }
 
     fn packetize(&mut self, raw: &Bytes, ppi: PayloadProtocolIdentifier) -> Vec<ChunkPayloadData> {
@@ -414,8 +698,7 @@ impl StreamState {
         while remaining != 0 {
             let fragment_size = std::cmp::min(self.max_payload_size as usize, remaining); //self.association.max_payload_size
 
-            // Copy the userdata since we'll have to store it until acked
-            // and the caller may re-use the buffer in the mean time
+            // Share the caller's buffer, which is kept until acked
             let user_data = raw.slice(i..i + fragment_size);
 
             let chunk = ChunkPayloadData {
@@ -453,6 +736,60 @@ impl StreamState {
         chunks
     }
 
+    /// packetize_partial fragments a piece of the message being written in pieces.
+    /// Up to one fragment of it is held back until more data arrives, so the
+    /// ending fragment is never empty.
+    fn packetize_partial(
+        &mut self,
+        data: &[u8],
+        ppi: PayloadProtocolIdentifier,
+        end_of_record: bool,
+        handle: MessageHandle,
+    ) -> Vec<ChunkPayloadData> {
+        let unordered = ppi != PayloadProtocolIdentifier::Dcep && self.unordered;
+        let m = self.partial_message.get_or_insert_with(|| PartialMessage {
+            handle,
+            ppi,
+            unordered,
+            started: false,
+            tail: BytesMut::new(),
+        });
+        m.tail.extend_from_slice(data);
+
+        let mut chunks = vec![];
+        while m.tail.len() > self.max_payload_size as usize || (end_of_record && !m.tail.is_empty())
+        {
+            let fragment_size = std::cmp::min(self.max_payload_size as usize, m.tail.len());
+            let user_data = m.tail.split_to(fragment_size).freeze();
+
+            chunks.push(ChunkPayloadData {
+                stream_identifier: self.stream_identifier,
+                user_data,
+                unordered: m.unordered,
+                beginning_fragment: !m.started,
+                ending_fragment: end_of_record && m.tail.is_empty(),
+                payload_type: m.ppi,
+                stream_sequence_number: self.sequence_number,
+                message_handle: Some(m.handle),
+                ..Default::default()
+            });
+            m.started = true;
+        }
+
+        if end_of_record {
+            // An empty message sends nothing and takes no sequence number
+            if let Some(m) = self.partial_message.take() {
+                if m.started && !m.unordered {
+                    self.sequence_number = self.sequence_number.wrapping_add(1);
+                }
+            }
+        }
+
+        self.buffered_amount += data.len();
+
+        chunks
+    }
+
     /// This method is called by association's read_loop (go-)routine to notify this stream
     /// of the specified amount of outgoing data has been delivered to the peer.
     pub(crate) fn on_buffer_released(&mut self, n_bytes_released: i64) -> bool {
@@ -3,8 +3,9 @@ use std::time::{Duration, Instant};
 pub(crate) const ACK_INTERVAL: u64 = 200;
 const MAX_INIT_RETRANS: usize = 8;
 const PATH_MAX_RETRANS: usize = 5;
+const ASSOCIATION_MAX_RETRANS: usize = 10;
 const NO_MAX_RETRANS: usize = usize::MAX;
-const TIMER_COUNT: usize = 6;
+const TIMER_COUNT: usize = 9;
 
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq)]
 pub(crate) enum Timer {
@@ -14,6 +15,9 @@ pub(crate) enum Timer {
     T3RTX = 3,
     Reconfig = 4,
     Ack = 5,
+    ZeroWindowProbe = 6,
+    T5ShutdownGuard = 7,
+    Linger = 8,
 }
 
 impl Timer {
@@ -24,6 +28,9 @@ impl Timer {
         Timer::T3RTX,
         Timer::Reconfig,
         Timer::Ack,
+        Timer::ZeroWindowProbe,
+        Timer::T5ShutdownGuard,
+        Timer::Linger,
     ];
 }
 
@@ -39,12 +46,15 @@ impl TimerTable {
     pub fn new() -> Self {
         TimerTable {
             max_retrans: [
-                MAX_INIT_RETRANS, //T1Init
-                MAX_INIT_RETRANS, //T1Cookie
-                NO_MAX_RETRANS,   //T2Shutdown
-                NO_MAX_RETRANS,   //T3RTX
-                NO_MAX_RETRANS,   //Reconfig
-                NO_MAX_RETRANS,   //Ack
+                MAX_INIT_RETRANS,        //T1Init
+                MAX_INIT_RETRANS,        //T1Cookie
+                ASSOCIATION_MAX_RETRANS, //T2Shutdown
+                NO_MAX_RETRANS,          //T3RTX
+                NO_MAX_RETRANS,          //Reconfig
+                NO_MAX_RETRANS,          //Ack
+                NO_MAX_RETRANS,          //ZeroWindowProbe
+                0,                       //T5ShutdownGuard
+                NO_MAX_RETRANS,          //Linger
             ],
             ..Default::default()
         }
@@ -63,7 +73,7 @@ impl TimerTable {
     }
 
     pub fn start(&mut self, timer: Timer, now: Instant, interval: u64) {
-        let interval = if timer == Timer::Ack {
+        let interval = if matches!(timer, Timer::Ack | Timer::T5ShutdownGuard | Timer::Linger) {
             interval
         } else {
             calculate_next_timeout(interval, self.retrans[timer as usize])
@@ -90,7 +100,7 @@ impl TimerTable {
     }
 
     pub fn is_expired(&mut self, timer: Timer, after: Instant) -> (bool, bool, usize) {
-        let expired = self.data[timer as usize].map_or(false, |x| x <= after);
+        let expired = self.data[timer as usize].is_some_and(|x| x <= after);
         let mut failure = false;
         if expired {
             self.retrans[timer as usize] += 1;
@@ -106,6 +116,8 @@ impl TimerTable {
 const RTO_INITIAL: u64 = 3000; // msec
 const RTO_MIN: u64 = 1000; // msec
 const RTO_MAX: u64 = 60000; // msec
+/// RFC 4960 sec 9.2 recommends 5 times RTO.Max for the T5-shutdown-guard timer
+pub(crate) const T5_SHUTDOWN_GUARD: u64 = 5 * RTO_MAX; // msec
 const RTO_ALPHA: u64 = 1;
 const RTO_BETA: u64 = 2;
 const RTO_BASE: u64 = 8;
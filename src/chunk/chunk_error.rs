@@ -55,24 +55,16 @@ impl Chunk for ChunkError {
             return Err(Error::ErrChunkTypeNotCt);
         }
 
-        let mut error_causes = vec![];
-        let mut offset = CHUNK_HEADER_SIZE;
-        while offset + 4 <= raw.len() {
-            let e = ErrorCause::unmarshal(
-                &raw.slice(offset..CHUNK_HEADER_SIZE + header.value_length()),
-            )?;
-            offset += e.length();
-            error_causes.push(e);
-        }
+        let error_causes = unmarshal_error_causes(
+            &raw.slice(CHUNK_HEADER_SIZE..CHUNK_HEADER_SIZE + header.value_length()),
+        )?;
 
         Ok(ChunkError { error_causes })
     }
 
     fn marshal_to(&self, buf: &mut BytesMut) -> Result<usize> {
         self.header().marshal_to(buf)?;
-        for ec in &self.error_causes {
-            buf.extend(ec.marshal());
-        }
+        marshal_error_causes(&self.error_causes, buf);
         Ok(buf.len())
     }
 
@@ -81,9 +73,7 @@ impl Chunk for ChunkError {
     }
 
     fn value_length(&self) -> usize {
-        self.error_causes
-            .iter()
-            .fold(0, |length, ec| length + ec.length())
+        error_causes_length(&self.error_causes)
     }
 
     fn as_any(&self) -> &(dyn Any + Send + Sync) {
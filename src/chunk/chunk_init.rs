@@ -272,13 +272,15 @@ impl Chunk for ChunkInit {
 }
 
 impl ChunkInit {
-    pub(crate) fn set_supported_extensions(&mut self) {
+    pub(crate) fn set_supported_extensions(&mut self, chunk_types: Vec<ChunkType>) {
         // RFC5061 https://tools.ietf.org/html/rfc6525#section-5.2
         // An implementation supporting this (Supported Extensions Parameter)
         // extension MUST list the ASCONF, the ASCONF-ACK, and the AUTH chunks
         // in its INIT and INIT-ACK parameters.
-        self.params.push(Box::new(ParamSupportedExtensions {
-            chunk_types: vec![CT_RECONFIG, CT_FORWARD_TSN],
-        }));
+        if chunk_types.is_empty() {
+            return;
+        }
+        self.params
+            .push(Box::new(ParamSupportedExtensions { chunk_types }));
     }
 }
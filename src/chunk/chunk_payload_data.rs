@@ -1,5 +1,7 @@
 use super::{chunk_header::*, chunk_type::*, *};
 
+use crate::association::stream::MessageHandle;
+
 use std::time::Instant;
 
 pub(crate) const PAYLOAD_DATA_ENDING_FRAGMENT_BITMASK: u8 = 1;
@@ -115,6 +117,11 @@ pub struct ChunkPayloadData {
     /// Retransmission flag set when T1-RTX timeout occurred and this
     /// chunk is still in the inflight queue
     pub(crate) retransmit: bool,
+
+    /// Handle of the message this chunk is a fragment of, if it is tracked
+    pub(crate) message_handle: Option<MessageHandle>,
+    /// When the chunk was first seen in the pending queue, used to expire it
+    pub(crate) queued_at: Option<Instant>,
 }
 
 impl Default for ChunkPayloadData {
@@ -136,6 +143,8 @@ impl Default for ChunkPayloadData {
             abandoned: false,
             all_inflight: false,
             retransmit: false,
+            message_handle: None,
+            queued_at: None,
         }
     }
 }
@@ -214,6 +223,8 @@ impl Chunk for ChunkPayloadData {
             abandoned: false,
             all_inflight: false,
             retransmit: false,
+            message_handle: None,
+            queued_at: None,
         })
     }
 
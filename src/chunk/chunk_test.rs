@@ -48,9 +48,8 @@ use super::chunk_abort::*;
 #[test]
 fn test_abort_chunk_one_error_cause() -> Result<()> {
     let abort1 = ChunkAbort {
-        error_causes: vec![ErrorCause {
-            code: PROTOCOL_VIOLATION,
-            ..Default::default()
+        error_causes: vec![ErrorCause::ProtocolViolation {
+            additional_information: Bytes::new(),
         }],
     };
 
@@ -59,8 +58,8 @@ fn test_abort_chunk_one_error_cause() -> Result<()> {
 
     assert_eq!(1, abort2.error_causes.len(), "should have only one cause");
     assert_eq!(
-        abort1.error_causes[0].error_cause_code(),
-        abort2.error_causes[0].error_cause_code(),
+        abort1.error_causes[0].code(),
+        abort2.error_causes[0].code(),
         "errorCause code should match"
     );
 
@@ -71,17 +70,12 @@ fn test_abort_chunk_one_error_cause() -> Result<()> {
 fn test_abort_chunk_many_error_causes() -> Result<()> {
     let abort1 = ChunkAbort {
         error_causes: vec![
-            ErrorCause {
-                code: INVALID_MANDATORY_PARAMETER,
-                ..Default::default()
+            ErrorCause::InvalidMandatoryParameter,
+            ErrorCause::UnrecognizedChunkType {
+                chunk: Bytes::new(),
             },
-            ErrorCause {
-                code: UNRECOGNIZED_CHUNK_TYPE,
-                ..Default::default()
-            },
-            ErrorCause {
-                code: PROTOCOL_VIOLATION,
-                ..Default::default()
+            ErrorCause::ProtocolViolation {
+                additional_information: Bytes::new(),
             },
         ],
     };
@@ -91,8 +85,8 @@ fn test_abort_chunk_many_error_causes() -> Result<()> {
     assert_eq!(3, abort2.error_causes.len(), "should have only one cause");
     for (i, error_cause) in abort1.error_causes.iter().enumerate() {
         assert_eq!(
-            error_cause.error_cause_code(),
-            abort2.error_causes[i].error_cause_code(),
+            error_cause.code(),
+            abort2.error_causes[i].code(),
             "errorCause code should match"
         );
     }
@@ -100,6 +94,156 @@ fn test_abort_chunk_many_error_causes() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_error_cause_round_trip() -> Result<()> {
+    let request = Bytes::from_static(&[0xc0, 0x01, 0x00, 0x08, 0x00, 0x00, 0x00, 0x01]);
+    let tests = vec![
+        (
+            ErrorCause::InvalidStreamIdentifier {
+                stream_identifier: 0x1234,
+            },
+            vec![0x00, 0x01, 0x00, 0x08, 0x12, 0x34, 0x00, 0x00],
+        ),
+        (
+            ErrorCause::MissingMandatoryParameter {
+                missing_params: vec![7, 9],
+            },
+            vec![
+                0x00, 0x02, 0x00, 0x0c, 0x00, 0x00, 0x00, 0x02, 0x00, 0x07, 0x00, 0x09,
+            ],
+        ),
+        (
+            ErrorCause::StaleCookie {
+                measure_of_staleness: 1000,
+            },
+            vec![0x00, 0x03, 0x00, 0x08, 0x00, 0x00, 0x03, 0xe8],
+        ),
+        (ErrorCause::OutOfResource, vec![0x00, 0x04, 0x00, 0x04]),
+        (
+            ErrorCause::UnresolvableAddress {
+                address: Bytes::from_static(&[0x00, 0x0b, 0x00, 0x05, 0x61]),
+            },
+            vec![0x00, 0x05, 0x00, 0x09, 0x00, 0x0b, 0x00, 0x05, 0x61],
+        ),
+        (
+            ErrorCause::UnrecognizedChunkType {
+                chunk: ORG_UNRECOGNIZED_CHUNK.clone(),
+            },
+            vec![
+                0x00, 0x06, 0x00, 0x0c, 0xc0, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x03,
+            ],
+        ),
+        (
+            ErrorCause::InvalidMandatoryParameter,
+            vec![0x00, 0x07, 0x00, 0x04],
+        ),
+        (
+            ErrorCause::UnrecognizedParameters {
+                params: request.clone(),
+            },
+            vec![
+                0x00, 0x08, 0x00, 0x0c, 0xc0, 0x01, 0x00, 0x08, 0x00, 0x00, 0x00, 0x01,
+            ],
+        ),
+        (
+            ErrorCause::NoUserData { tsn: 0xaabbccdd },
+            vec![0x00, 0x09, 0x00, 0x08, 0xaa, 0xbb, 0xcc, 0xdd],
+        ),
+        (
+            ErrorCause::CookieReceivedWhileShuttingDown,
+            vec![0x00, 0x0a, 0x00, 0x04],
+        ),
+        (
+            ErrorCause::RestartWithNewAddresses {
+                new_addresses: Bytes::from_static(&[0x00, 0x05, 0x00, 0x08, 10, 0, 0, 1]),
+            },
+            vec![0x00, 0x0b, 0x00, 0x0c, 0x00, 0x05, 0x00, 0x08, 10, 0, 0, 1],
+        ),
+        (
+            ErrorCause::UserInitiatedAbort {
+                reason: Bytes::from_static(b"bye"),
+            },
+            vec![0x00, 0x0c, 0x00, 0x07, b'b', b'y', b'e'],
+        ),
+        (
+            ErrorCause::ProtocolViolation {
+                additional_information: Bytes::from_static(b"oops"),
+            },
+            vec![0x00, 0x0d, 0x00, 0x08, b'o', b'o', b'p', b's'],
+        ),
+        (
+            ErrorCause::DeleteLastRemainingAddress {
+                request: request.clone(),
+            },
+            vec![
+                0x00, 0xa0, 0x00, 0x0c, 0xc0, 0x01, 0x00, 0x08, 0x00, 0x00, 0x00, 0x01,
+            ],
+        ),
+        (
+            ErrorCause::OperationRefusedResourceShortage {
+                request: request.clone(),
+            },
+            vec![
+                0x00, 0xa1, 0x00, 0x0c, 0xc0, 0x01, 0x00, 0x08, 0x00, 0x00, 0x00, 0x01,
+            ],
+        ),
+        (
+            ErrorCause::DeleteSourceAddress {
+                request: request.clone(),
+            },
+            vec![
+                0x00, 0xa2, 0x00, 0x0c, 0xc0, 0x01, 0x00, 0x08, 0x00, 0x00, 0x00, 0x01,
+            ],
+        ),
+        (ErrorCause::IllegalAsconfAck, vec![0x00, 0xa3, 0x00, 0x04]),
+        (
+            ErrorCause::RequestRefusedNoAuthorization { request },
+            vec![
+                0x00, 0xa4, 0x00, 0x0c, 0xc0, 0x01, 0x00, 0x08, 0x00, 0x00, 0x00, 0x01,
+            ],
+        ),
+        (
+            ErrorCause::Unknown {
+                code: ErrorCauseCode(0x1234),
+                value: Bytes::from_static(&[0x01]),
+            },
+            vec![0x12, 0x34, 0x00, 0x05, 0x01],
+        ),
+    ];
+
+    for (cause, expected) in tests {
+        let raw = cause.marshal();
+        assert_eq!(&expected[..], &raw[..], "{}: unexpected marshal", cause);
+        assert_eq!(expected.len(), cause.length());
+        let decoded = ErrorCause::unmarshal(&raw)?;
+        assert_eq!(cause, decoded, "{}: unexpected unmarshal", cause);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_error_cause_unmarshal_too_small() -> Result<()> {
+    let tests: Vec<&[u8]> = vec![
+        &[0x00, 0x01, 0x00],
+        // Cause Length shorter than the header
+        &[0x00, 0x0d, 0x00, 0x03],
+        // Cause Length longer than the buffer
+        &[0x00, 0x0d, 0x00, 0x08, 0x00],
+        // Stream Identifier missing
+        &[0x00, 0x01, 0x00, 0x04],
+        // Fewer parameter types than announced
+        &[0x00, 0x02, 0x00, 0x0a, 0x00, 0x00, 0x00, 0x02, 0x00, 0x07],
+    ];
+
+    for raw in tests {
+        let result = ErrorCause::unmarshal(&Bytes::copy_from_slice(raw));
+        assert_eq!(Err(Error::ErrErrorCauseTooSmall), result, "{:x?}", raw);
+    }
+
+    Ok(())
+}
+
 ///////////////////////////////////////////////////////////////////
 //chunk_error_test
 ///////////////////////////////////////////////////////////////////
@@ -130,11 +274,14 @@ fn test_chunk_error_unrecognized_chunk_type_unmarshal() -> Result<()> {
     let ec = &c.error_causes[0];
     assert_eq!(
         UNRECOGNIZED_CHUNK_TYPE,
-        ec.error_cause_code(),
+        ec.code(),
         "cause code should be unrecognizedChunkType"
     );
     assert_eq!(
-        ec.raw, ORG_UNRECOGNIZED_CHUNK,
+        *ec,
+        ErrorCause::UnrecognizedChunkType {
+            chunk: ORG_UNRECOGNIZED_CHUNK.clone()
+        },
         "should have valid unrecognizedChunk"
     );
 
@@ -143,9 +290,8 @@ fn test_chunk_error_unrecognized_chunk_type_unmarshal() -> Result<()> {
 
 #[test]
 fn test_chunk_error_unrecognized_chunk_type_marshal() -> Result<()> {
-    let ec_unrecognized_chunk_type = ErrorCause {
-        code: UNRECOGNIZED_CHUNK_TYPE,
-        raw: ORG_UNRECOGNIZED_CHUNK.clone(),
+    let ec_unrecognized_chunk_type = ErrorCause::UnrecognizedChunkType {
+        chunk: ORG_UNRECOGNIZED_CHUNK.clone(),
     };
 
     let ec = ChunkError {
@@ -158,13 +304,62 @@ fn test_chunk_error_unrecognized_chunk_type_marshal() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_chunk_error_padded_error_causes() -> Result<()> {
+    let ec = ChunkError {
+        error_causes: vec![
+            ErrorCause::UserInitiatedAbort {
+                reason: Bytes::from_static(b"bye"),
+            },
+            ErrorCause::NoUserData { tsn: 1 },
+            ErrorCause::ProtocolViolation {
+                additional_information: Bytes::from_static(b"x"),
+            },
+        ],
+    };
+
+    let raw = ec.marshal()?;
+    let expected = Bytes::from_static(&[
+        CT_ERROR.0,
+        CHUNK_FLAGS,
+        0x00,
+        0x19, // header
+        0x00,
+        0x0c,
+        0x00,
+        0x07,
+        b'b',
+        b'y',
+        b'e',
+        0x00, // padded
+        0x00,
+        0x09,
+        0x00,
+        0x08,
+        0x00,
+        0x00,
+        0x00,
+        0x01, // no padding needed
+        0x00,
+        0x0d,
+        0x00,
+        0x05,
+        b'x', // last cause is padded with the chunk
+    ]);
+    assert_eq!(raw, expected, "unexpected serialization result");
+
+    let decoded = ChunkError::unmarshal(&raw)?;
+    assert_eq!(ec.error_causes, decoded.error_causes);
+
+    Ok(())
+}
+
 #[test]
 fn test_chunk_error_unrecognized_chunk_type_marshal_with_cause_value_being_nil() -> Result<()> {
     let expected =
         Bytes::from_static(&[CT_ERROR.0, CHUNK_FLAGS, 0x00, 0x08, 0x00, 0x06, 0x00, 0x04]);
-    let ec_unrecognized_chunk_type = ErrorCause {
-        code: UNRECOGNIZED_CHUNK_TYPE,
-        ..Default::default()
+    let ec_unrecognized_chunk_type = ErrorCause::UnrecognizedChunkType {
+        chunk: Bytes::new(),
     };
 
     let ec = ChunkError {
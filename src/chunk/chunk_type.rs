@@ -20,8 +20,12 @@ pub(crate) const CT_COOKIE_ECHO: ChunkType = ChunkType(10);
 pub(crate) const CT_COOKIE_ACK: ChunkType = ChunkType(11);
 pub(crate) const CT_CWR: ChunkType = ChunkType(13);
 pub(crate) const CT_SHUTDOWN_COMPLETE: ChunkType = ChunkType(14);
+pub(crate) const CT_AUTH: ChunkType = ChunkType(15);
+pub(crate) const CT_I_DATA: ChunkType = ChunkType(64);
+pub(crate) const CT_ASCONF_ACK: ChunkType = ChunkType(128);
 pub(crate) const CT_RECONFIG: ChunkType = ChunkType(130);
 pub(crate) const CT_FORWARD_TSN: ChunkType = ChunkType(192);
+pub(crate) const CT_ASCONF: ChunkType = ChunkType(193);
 
 impl fmt::Display for ChunkType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -41,8 +45,12 @@ impl fmt::Display for ChunkType {
             CT_COOKIE_ACK => "COOKIE-ACK",
             CT_CWR => "ECNE", // Explicit Congestion Notification Echo
             CT_SHUTDOWN_COMPLETE => "SHUTDOWN-COMPLETE",
+            CT_AUTH => "AUTH",
+            CT_I_DATA => "I-DATA",
+            CT_ASCONF_ACK => "ASCONF-ACK",
             CT_RECONFIG => "RECONFIG", // Re-configuration
             CT_FORWARD_TSN => "FORWARD-TSN",
+            CT_ASCONF => "ASCONF",
             _ => others.as_str(),
         };
         write!(f, "{}", s)
@@ -70,8 +78,12 @@ mod test {
             (CT_COOKIE_ACK, "COOKIE-ACK"),
             (CT_CWR, "ECNE"),
             (CT_SHUTDOWN_COMPLETE, "SHUTDOWN-COMPLETE"),
+            (CT_AUTH, "AUTH"),
+            (CT_I_DATA, "I-DATA"),
+            (CT_ASCONF_ACK, "ASCONF-ACK"),
             (CT_RECONFIG, "RECONFIG"),
             (CT_FORWARD_TSN, "FORWARD-TSN"),
+            (CT_ASCONF, "ASCONF"),
             (ChunkType(255), "Unknown ChunkType: 255"),
         ];
 
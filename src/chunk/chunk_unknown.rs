@@ -0,0 +1,81 @@
+use super::{chunk_header::*, chunk_type::*, *};
+
+/// ChunkUnknown represents a chunk whose type is not recognized. It is kept
+/// as is so that it can be reported back to the sender.
+///
+/// RFC 4960 sec 3.2
+///   Chunk Types are encoded such that the highest-order 2 bits specify
+///   the action that must be taken if the processing endpoint does not
+///   recognize the Chunk Type.
+///
+///   00 - Stop processing this SCTP packet and discard it, do not
+///        process any further chunks within it.
+///   01 - Stop processing this SCTP packet and discard it, do not
+///        process any further chunks within it, and report the
+///        unrecognized chunk in an 'Unrecognized Chunk Type'.
+///   10 - Skip this chunk and continue processing.
+///   11 - Skip this chunk and continue processing, but report in an
+///        ERROR chunk using the 'Unrecognized Chunk Type' cause of error.
+#[derive(Default, Debug, Clone)]
+pub(crate) struct ChunkUnknown {
+    pub(crate) typ: ChunkType,
+    pub(crate) flags: u8,
+    pub(crate) value: Bytes,
+}
+
+/// makes ChunkUnknown printable
+impl fmt::Display for ChunkUnknown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.header())
+    }
+}
+
+impl Chunk for ChunkUnknown {
+    fn header(&self) -> ChunkHeader {
+        ChunkHeader {
+            typ: self.typ,
+            flags: self.flags,
+            value_length: self.value_length() as u16,
+        }
+    }
+
+    fn unmarshal(raw: &Bytes) -> Result<Self> {
+        let header = ChunkHeader::unmarshal(raw)?;
+        let value = raw.slice(CHUNK_HEADER_SIZE..CHUNK_HEADER_SIZE + header.value_length());
+        Ok(ChunkUnknown {
+            typ: header.typ,
+            flags: header.flags,
+            value,
+        })
+    }
+
+    fn marshal_to(&self, buf: &mut BytesMut) -> Result<usize> {
+        self.header().marshal_to(buf)?;
+        buf.extend(self.value.clone());
+        Ok(buf.len())
+    }
+
+    fn check(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn value_length(&self) -> usize {
+        self.value.len()
+    }
+
+    fn as_any(&self) -> &(dyn Any + Send + Sync) {
+        self
+    }
+}
+
+impl ChunkUnknown {
+    /// stop_processing returns whether the rest of the packet must be discarded.
+    pub(crate) fn stop_processing(&self) -> bool {
+        self.typ.0 & 0x80 == 0
+    }
+
+    /// report returns whether the chunk must be reported to the sender.
+    pub(crate) fn report(&self) -> bool {
+        self.typ.0 & 0x40 != 0
+    }
+}
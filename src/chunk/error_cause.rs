@@ -0,0 +1,419 @@
+use crate::error::{Error, Result};
+use crate::util::get_padding_size;
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::fmt;
+
+/// ErrorCauseCode is a cause code that appears in either a ERROR or ABORT chunk
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct ErrorCauseCode(pub(crate) u16);
+
+pub(crate) const INVALID_STREAM_IDENTIFIER: ErrorCauseCode = ErrorCauseCode(1);
+pub(crate) const MISSING_MANDATORY_PARAMETER: ErrorCauseCode = ErrorCauseCode(2);
+pub(crate) const STALE_COOKIE_ERROR: ErrorCauseCode = ErrorCauseCode(3);
+pub(crate) const OUT_OF_RESOURCE: ErrorCauseCode = ErrorCauseCode(4);
+pub(crate) const UNRESOLVABLE_ADDRESS: ErrorCauseCode = ErrorCauseCode(5);
+pub(crate) const UNRECOGNIZED_CHUNK_TYPE: ErrorCauseCode = ErrorCauseCode(6);
+pub(crate) const INVALID_MANDATORY_PARAMETER: ErrorCauseCode = ErrorCauseCode(7);
+pub(crate) const UNRECOGNIZED_PARAMETERS: ErrorCauseCode = ErrorCauseCode(8);
+pub(crate) const NO_USER_DATA: ErrorCauseCode = ErrorCauseCode(9);
+pub(crate) const COOKIE_RECEIVED_WHILE_SHUTTING_DOWN: ErrorCauseCode = ErrorCauseCode(10);
+pub(crate) const RESTART_OF_AN_ASSOCIATION_WITH_NEW_ADDRESSES: ErrorCauseCode = ErrorCauseCode(11);
+pub(crate) const USER_INITIATED_ABORT: ErrorCauseCode = ErrorCauseCode(12);
+pub(crate) const PROTOCOL_VIOLATION: ErrorCauseCode = ErrorCauseCode(13);
+// RFC 5061 sec 4.3
+pub(crate) const DELETE_LAST_REMAINING_IP_ADDRESS: ErrorCauseCode = ErrorCauseCode(0x00A0);
+pub(crate) const OPERATION_REFUSED_DUE_TO_RESOURCE_SHORTAGE: ErrorCauseCode =
+    ErrorCauseCode(0x00A1);
+pub(crate) const DELETE_SOURCE_IP_ADDRESS: ErrorCauseCode = ErrorCauseCode(0x00A2);
+pub(crate) const ILLEGAL_ASCONF_ACK: ErrorCauseCode = ErrorCauseCode(0x00A3);
+pub(crate) const REQUEST_REFUSED_NO_AUTHORIZATION: ErrorCauseCode = ErrorCauseCode(0x00A4);
+
+impl fmt::Display for ErrorCauseCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let others = format!("Unknown CauseCode: {}", self.0);
+        let s = match *self {
+            INVALID_STREAM_IDENTIFIER => "Invalid Stream Identifier",
+            MISSING_MANDATORY_PARAMETER => "Missing Mandatory Parameter",
+            STALE_COOKIE_ERROR => "Stale Cookie Error",
+            OUT_OF_RESOURCE => "Out Of Resource",
+            UNRESOLVABLE_ADDRESS => "Unresolvable IP",
+            UNRECOGNIZED_CHUNK_TYPE => "Unrecognized Chunk Type",
+            INVALID_MANDATORY_PARAMETER => "Invalid Mandatory Parameter",
+            UNRECOGNIZED_PARAMETERS => "Unrecognized Parameters",
+            NO_USER_DATA => "No User Data",
+            COOKIE_RECEIVED_WHILE_SHUTTING_DOWN => "Cookie Received While Shutting Down",
+            RESTART_OF_AN_ASSOCIATION_WITH_NEW_ADDRESSES => {
+                "Restart Of An Association With New Addresses"
+            }
+            USER_INITIATED_ABORT => "User Initiated Abort",
+            PROTOCOL_VIOLATION => "Protocol Violation",
+            DELETE_LAST_REMAINING_IP_ADDRESS => "Request To Delete Last Remaining IP Address",
+            OPERATION_REFUSED_DUE_TO_RESOURCE_SHORTAGE => {
+                "Operation Refused Due To Resource Shortage"
+            }
+            DELETE_SOURCE_IP_ADDRESS => "Request To Delete Source IP Address",
+            ILLEGAL_ASCONF_ACK => "Association Aborted Due To Illegal ASCONF-ACK",
+            REQUEST_REFUSED_NO_AUTHORIZATION => "Request Refused - No Authorization",
+            _ => others.as_str(),
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl From<u16> for ErrorCauseCode {
+    fn from(v: u16) -> Self {
+        ErrorCauseCode(v)
+    }
+}
+
+pub(crate) const ERROR_CAUSE_HEADER_LENGTH: usize = 4;
+
+/// ErrorCause is an error cause carried by an ERROR or ABORT chunk, as defined
+/// by RFC 4960 sec 3.3.10 and RFC 5061 sec 4.3. RFC 6525 reports failures in
+/// its Re-configuration Response Parameter and defines no error causes.
+///
+/// 0                   1                   2                   3
+/// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+///+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///|           Cause Code          |       Cause Length            |
+///+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+///|                    Cause-Specific Information                 |
+///+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorCause {
+    /// DATA was received for a nonexistent stream (1)
+    InvalidStreamIdentifier {
+        /// The stream identifier of the DATA chunk
+        stream_identifier: u16,
+    },
+    /// Mandatory parameters are missing from an INIT or INIT ACK chunk (2)
+    MissingMandatoryParameter {
+        /// Types of the missing parameters
+        missing_params: Vec<u16>,
+    },
+    /// A valid State Cookie that has expired was received (3)
+    StaleCookie {
+        /// Difference, in usec, between the current time and the time the
+        /// State Cookie expired
+        measure_of_staleness: u32,
+    },
+    /// The sender is out of resource (4)
+    OutOfResource,
+    /// The sender is not able to resolve the address parameter (5)
+    UnresolvableAddress {
+        /// The complete unresolvable address parameter, type, length and value
+        address: Bytes,
+    },
+    /// The receiver does not understand a chunk whose type asks to report it (6)
+    UnrecognizedChunkType {
+        /// The unrecognized chunk, header included
+        chunk: Bytes,
+    },
+    /// A mandatory parameter of an INIT or INIT ACK chunk has an invalid value (7)
+    InvalidMandatoryParameter,
+    /// The receiver does not recognize parameters of an INIT ACK chunk (8)
+    UnrecognizedParameters {
+        /// The unrecognized parameters, copied from the INIT ACK chunk
+        params: Bytes,
+    },
+    /// A DATA chunk without user data was received (9)
+    NoUserData {
+        /// The TSN of the DATA chunk
+        tsn: u32,
+    },
+    /// A COOKIE ECHO was received in the SHUTDOWN-ACK-SENT state (10)
+    CookieReceivedWhileShuttingDown,
+    /// A restarting INIT added new addresses to the association (11)
+    RestartWithNewAddresses {
+        /// The address parameters that were added
+        new_addresses: Bytes,
+    },
+    /// The upper layer aborted the association (12)
+    UserInitiatedAbort {
+        /// The reason given by the upper layer
+        reason: Bytes,
+    },
+    /// The peer violated the protocol in a way no other cause describes (13)
+    ProtocolViolation {
+        /// What kind of protocol violation has been detected
+        additional_information: Bytes,
+    },
+    /// An ASCONF asked to delete the last remaining address (0x00A0)
+    DeleteLastRemainingAddress {
+        /// The offending ASCONF parameter
+        request: Bytes,
+    },
+    /// An ASCONF was refused for lack of resources (0x00A1)
+    OperationRefusedResourceShortage {
+        /// The offending ASCONF parameter
+        request: Bytes,
+    },
+    /// An ASCONF asked to delete the source address of its packet (0x00A2)
+    DeleteSourceAddress {
+        /// The offending ASCONF parameter
+        request: Bytes,
+    },
+    /// An ASCONF-ACK was received for an ASCONF that was never sent (0x00A3)
+    IllegalAsconfAck,
+    /// An ASCONF was refused by local policy (0x00A4)
+    RequestRefusedNoAuthorization {
+        /// The offending ASCONF parameter
+        request: Bytes,
+    },
+    /// A cause with a code that is not known to this implementation
+    Unknown {
+        /// The cause code
+        code: ErrorCauseCode,
+        /// The cause-specific information
+        value: Bytes,
+    },
+}
+
+/// makes ErrorCause printable
+impl fmt::Display for ErrorCause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let code = self.code();
+        match self {
+            ErrorCause::InvalidStreamIdentifier { stream_identifier } => {
+                write!(f, "{}: {}", code, stream_identifier)
+            }
+            ErrorCause::MissingMandatoryParameter { missing_params } => {
+                write!(f, "{}: {:?}", code, missing_params)
+            }
+            ErrorCause::StaleCookie {
+                measure_of_staleness,
+            } => write!(f, "{}: {}us", code, measure_of_staleness),
+            ErrorCause::NoUserData { tsn } => write!(f, "{}: tsn={}", code, tsn),
+            _ => write!(f, "{}", code),
+        }
+    }
+}
+
+impl ErrorCause {
+    /// code returns the cause code of the error cause.
+    pub fn code(&self) -> ErrorCauseCode {
+        match self {
+            ErrorCause::InvalidStreamIdentifier { .. } => INVALID_STREAM_IDENTIFIER,
+            ErrorCause::MissingMandatoryParameter { .. } => MISSING_MANDATORY_PARAMETER,
+            ErrorCause::StaleCookie { .. } => STALE_COOKIE_ERROR,
+            ErrorCause::OutOfResource => OUT_OF_RESOURCE,
+            ErrorCause::UnresolvableAddress { .. } => UNRESOLVABLE_ADDRESS,
+            ErrorCause::UnrecognizedChunkType { .. } => UNRECOGNIZED_CHUNK_TYPE,
+            ErrorCause::InvalidMandatoryParameter => INVALID_MANDATORY_PARAMETER,
+            ErrorCause::UnrecognizedParameters { .. } => UNRECOGNIZED_PARAMETERS,
+            ErrorCause::NoUserData { .. } => NO_USER_DATA,
+            ErrorCause::CookieReceivedWhileShuttingDown => COOKIE_RECEIVED_WHILE_SHUTTING_DOWN,
+            ErrorCause::RestartWithNewAddresses { .. } => {
+                RESTART_OF_AN_ASSOCIATION_WITH_NEW_ADDRESSES
+            }
+            ErrorCause::UserInitiatedAbort { .. } => USER_INITIATED_ABORT,
+            ErrorCause::ProtocolViolation { .. } => PROTOCOL_VIOLATION,
+            ErrorCause::DeleteLastRemainingAddress { .. } => DELETE_LAST_REMAINING_IP_ADDRESS,
+            ErrorCause::OperationRefusedResourceShortage { .. } => {
+                OPERATION_REFUSED_DUE_TO_RESOURCE_SHORTAGE
+            }
+            ErrorCause::DeleteSourceAddress { .. } => DELETE_SOURCE_IP_ADDRESS,
+            ErrorCause::IllegalAsconfAck => ILLEGAL_ASCONF_ACK,
+            ErrorCause::RequestRefusedNoAuthorization { .. } => REQUEST_REFUSED_NO_AUTHORIZATION,
+            ErrorCause::Unknown { code, .. } => *code,
+        }
+    }
+
+    pub(crate) fn unmarshal(buf: &Bytes) -> Result<Self> {
+        if buf.len() < ERROR_CAUSE_HEADER_LENGTH {
+            return Err(Error::ErrErrorCauseTooSmall);
+        }
+
+        let reader = &mut buf.clone();
+
+        let code = ErrorCauseCode(reader.get_u16());
+        let len = reader.get_u16() as usize;
+
+        if len < ERROR_CAUSE_HEADER_LENGTH || len > buf.len() {
+            return Err(Error::ErrErrorCauseTooSmall);
+        }
+
+        let value = buf.slice(ERROR_CAUSE_HEADER_LENGTH..len);
+        let reader = &mut value.clone();
+        let expect = |n: usize| {
+            if value.len() < n {
+                Err(Error::ErrErrorCauseTooSmall)
+            } else {
+                Ok(())
+            }
+        };
+
+        let cause = match code {
+            INVALID_STREAM_IDENTIFIER => {
+                expect(4)?;
+                ErrorCause::InvalidStreamIdentifier {
+                    stream_identifier: reader.get_u16(),
+                }
+            }
+            MISSING_MANDATORY_PARAMETER => {
+                expect(4)?;
+                let n = reader.get_u32() as usize;
+                if reader.remaining() / 2 < n {
+                    return Err(Error::ErrErrorCauseTooSmall);
+                }
+                ErrorCause::MissingMandatoryParameter {
+                    missing_params: (0..n).map(|_| reader.get_u16()).collect(),
+                }
+            }
+            STALE_COOKIE_ERROR => {
+                expect(4)?;
+                ErrorCause::StaleCookie {
+                    measure_of_staleness: reader.get_u32(),
+                }
+            }
+            OUT_OF_RESOURCE => ErrorCause::OutOfResource,
+            UNRESOLVABLE_ADDRESS => ErrorCause::UnresolvableAddress { address: value },
+            UNRECOGNIZED_CHUNK_TYPE => ErrorCause::UnrecognizedChunkType { chunk: value },
+            INVALID_MANDATORY_PARAMETER => ErrorCause::InvalidMandatoryParameter,
+            UNRECOGNIZED_PARAMETERS => ErrorCause::UnrecognizedParameters { params: value },
+            NO_USER_DATA => {
+                expect(4)?;
+                ErrorCause::NoUserData {
+                    tsn: reader.get_u32(),
+                }
+            }
+            COOKIE_RECEIVED_WHILE_SHUTTING_DOWN => ErrorCause::CookieReceivedWhileShuttingDown,
+            RESTART_OF_AN_ASSOCIATION_WITH_NEW_ADDRESSES => ErrorCause::RestartWithNewAddresses {
+                new_addresses: value,
+            },
+            USER_INITIATED_ABORT => ErrorCause::UserInitiatedAbort { reason: value },
+            PROTOCOL_VIOLATION => ErrorCause::ProtocolViolation {
+                additional_information: value,
+            },
+            DELETE_LAST_REMAINING_IP_ADDRESS => {
+                ErrorCause::DeleteLastRemainingAddress { request: value }
+            }
+            OPERATION_REFUSED_DUE_TO_RESOURCE_SHORTAGE => {
+                ErrorCause::OperationRefusedResourceShortage { request: value }
+            }
+            DELETE_SOURCE_IP_ADDRESS => ErrorCause::DeleteSourceAddress { request: value },
+            ILLEGAL_ASCONF_ACK => ErrorCause::IllegalAsconfAck,
+            REQUEST_REFUSED_NO_AUTHORIZATION => {
+                ErrorCause::RequestRefusedNoAuthorization { request: value }
+            }
+            _ => ErrorCause::Unknown { code, value },
+        };
+
+        Ok(cause)
+    }
+
+    pub(crate) fn marshal(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(self.length());
+        let _ = self.marshal_to(&mut buf);
+        buf.freeze()
+    }
+
+    pub(crate) fn marshal_to(&self, writer: &mut BytesMut) -> usize {
+        writer.put_u16(self.code().0);
+        writer.put_u16(self.length() as u16);
+        match self {
+            ErrorCause::InvalidStreamIdentifier { stream_identifier } => {
+                writer.put_u16(*stream_identifier);
+                writer.put_u16(0); // Reserved
+            }
+            ErrorCause::MissingMandatoryParameter { missing_params } => {
+                writer.put_u32(missing_params.len() as u32);
+                for typ in missing_params {
+                    writer.put_u16(*typ);
+                }
+            }
+            ErrorCause::StaleCookie {
+                measure_of_staleness,
+            } => writer.put_u32(*measure_of_staleness),
+            ErrorCause::NoUserData { tsn } => writer.put_u32(*tsn),
+            ErrorCause::OutOfResource
+            | ErrorCause::InvalidMandatoryParameter
+            | ErrorCause::CookieReceivedWhileShuttingDown
+            | ErrorCause::IllegalAsconfAck => {}
+            ErrorCause::UnresolvableAddress { address: raw }
+            | ErrorCause::UnrecognizedChunkType { chunk: raw }
+            | ErrorCause::UnrecognizedParameters { params: raw }
+            | ErrorCause::RestartWithNewAddresses { new_addresses: raw }
+            | ErrorCause::UserInitiatedAbort { reason: raw }
+            | ErrorCause::ProtocolViolation {
+                additional_information: raw,
+            }
+            | ErrorCause::DeleteLastRemainingAddress { request: raw }
+            | ErrorCause::OperationRefusedResourceShortage { request: raw }
+            | ErrorCause::DeleteSourceAddress { request: raw }
+            | ErrorCause::RequestRefusedNoAuthorization { request: raw }
+            | ErrorCause::Unknown { value: raw, .. } => writer.extend_from_slice(raw),
+        }
+        writer.len()
+    }
+
+    /// length returns the length of the error cause, padding excluded.
+    pub(crate) fn length(&self) -> usize {
+        let value_length = match self {
+            ErrorCause::InvalidStreamIdentifier { .. }
+            | ErrorCause::StaleCookie { .. }
+            | ErrorCause::NoUserData { .. } => 4,
+            ErrorCause::MissingMandatoryParameter { missing_params } => {
+                4 + 2 * missing_params.len()
+            }
+            ErrorCause::OutOfResource
+            | ErrorCause::InvalidMandatoryParameter
+            | ErrorCause::CookieReceivedWhileShuttingDown
+            | ErrorCause::IllegalAsconfAck => 0,
+            ErrorCause::UnresolvableAddress { address: raw }
+            | ErrorCause::UnrecognizedChunkType { chunk: raw }
+            | ErrorCause::UnrecognizedParameters { params: raw }
+            | ErrorCause::RestartWithNewAddresses { new_addresses: raw }
+            | ErrorCause::UserInitiatedAbort { reason: raw }
+            | ErrorCause::ProtocolViolation {
+                additional_information: raw,
+            }
+            | ErrorCause::DeleteLastRemainingAddress { request: raw }
+            | ErrorCause::OperationRefusedResourceShortage { request: raw }
+            | ErrorCause::DeleteSourceAddress { request: raw }
+            | ErrorCause::RequestRefusedNoAuthorization { request: raw }
+            | ErrorCause::Unknown { value: raw, .. } => raw.len(),
+        };
+        ERROR_CAUSE_HEADER_LENGTH + value_length
+    }
+}
+
+/// unmarshal_error_causes parses the error causes making up the value of an
+/// ERROR or ABORT chunk. Every cause but the last is padded to 4 bytes.
+pub(crate) fn unmarshal_error_causes(value: &Bytes) -> Result<Vec<ErrorCause>> {
+    let mut error_causes = vec![];
+    let mut offset = 0;
+    while offset + ERROR_CAUSE_HEADER_LENGTH <= value.len() {
+        let buf = value.slice(offset..);
+        let len = u16::from_be_bytes([buf[2], buf[3]]) as usize;
+        error_causes.push(ErrorCause::unmarshal(&buf)?);
+        offset += len + get_padding_size(len);
+    }
+
+    Ok(error_causes)
+}
+
+pub(crate) fn marshal_error_causes(error_causes: &[ErrorCause], writer: &mut BytesMut) {
+    for (i, ec) in error_causes.iter().enumerate() {
+        ec.marshal_to(writer);
+        if i + 1 < error_causes.len() {
+            let padding = get_padding_size(ec.length());
+            writer.extend_from_slice(&[0u8; 4][..padding]);
+        }
+    }
+}
+
+/// error_causes_length returns the length of the error causes as laid out by
+/// marshal_error_causes.
+pub(crate) fn error_causes_length(error_causes: &[ErrorCause]) -> usize {
+    let n = error_causes.len();
+    error_causes.iter().enumerate().fold(0, |length, (i, ec)| {
+        let padding = if i + 1 < n {
+            get_padding_size(ec.length())
+        } else {
+            0
+        };
+        length + ec.length() + padding
+    })
+}
@@ -1,7 +1,9 @@
+use crate::association::SupportedExtensions;
 use crate::util::{AssociationIdGenerator, RandomAssociationIdGenerator};
 
 use std::fmt;
 use std::sync::Arc;
+use std::time::Duration;
 
 /// MTU for inbound packet (from DTLS)
 pub(crate) const RECEIVE_MTU: usize = 8192;
@@ -11,24 +13,52 @@ pub(crate) const INITIAL_RECV_BUF_SIZE: u32 = 1024 * 1024;
 pub(crate) const COMMON_HEADER_SIZE: u32 = 12;
 pub(crate) const DATA_CHUNK_HEADER_SIZE: u32 = 16;
 pub(crate) const DEFAULT_MAX_MESSAGE_SIZE: u32 = 65536;
+pub(crate) const DEFAULT_MAX_NUM_PARTIAL_MESSAGES: u32 = 256;
+/// RFC 8841 sec 4: the SCTP port used when none is negotiated out of band
+pub(crate) const DEFAULT_SCTP_PORT: u16 = 5000;
+/// RFC 9260 sec 16: Max.Burst - 4
+pub(crate) const DEFAULT_MAX_BURST: u32 = 4;
+pub(crate) const DEFAULT_SUPPORTED_EXTENSIONS: SupportedExtensions = SupportedExtensions {
+    forward_tsn: true,
+    reconfig: true,
+    i_data: false,
+    auth: false,
+    asconf: false,
+};
 
 /// Config collects the arguments to create_association construction into
 /// a single structure
 #[derive(Debug)]
 pub struct TransportConfig {
     max_receive_buffer_size: u32,
+    max_send_buffer_size: u32,
     max_message_size: u32,
+    max_receive_message_size: u32,
+    max_num_partial_messages: u32,
+    partial_delivery_point: Option<u32>,
     max_num_outbound_streams: u16,
     max_num_inbound_streams: u16,
+    max_burst: u32,
+    pacing: bool,
+    linger_timeout: Duration,
+    supported_extensions: SupportedExtensions,
 }
 
 impl Default for TransportConfig {
     fn default() -> Self {
         TransportConfig {
             max_receive_buffer_size: INITIAL_RECV_BUF_SIZE,
+            max_send_buffer_size: 0,
             max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            max_receive_message_size: 0,
+            max_num_partial_messages: DEFAULT_MAX_NUM_PARTIAL_MESSAGES,
+            partial_delivery_point: None,
             max_num_outbound_streams: u16::MAX,
             max_num_inbound_streams: u16::MAX,
+            max_burst: DEFAULT_MAX_BURST,
+            pacing: false,
+            linger_timeout: Duration::ZERO,
+            supported_extensions: DEFAULT_SUPPORTED_EXTENSIONS,
         }
     }
 }
@@ -39,11 +69,44 @@ impl TransportConfig {
         self
     }
 
+    /// Maximum number of bytes queued or in flight across all streams. Writes that
+    /// would exceed it fail with `Error::ErrSendBufferFull`. Zero disables the limit.
+    pub fn with_max_send_buffer_size(mut self, value: u32) -> Self {
+        self.max_send_buffer_size = value;
+        self
+    }
+
     pub fn with_max_message_size(mut self, value: u32) -> Self {
         self.max_message_size = value;
         self
     }
 
+    /// Maximum size of a message accepted from the peer. A peer sending a larger
+    /// message is aborted with a Protocol Violation. Zero disables the limit,
+    /// which is the default.
+    pub fn with_max_receive_message_size(mut self, value: u32) -> Self {
+        self.max_receive_message_size = value;
+        self
+    }
+
+    /// Maximum number of messages, across all streams, the peer may have started
+    /// to send without completing them. A peer exceeding it is aborted with a
+    /// Protocol Violation. Zero disables the limit.
+    pub fn with_max_num_partial_messages(mut self, value: u32) -> Self {
+        self.max_num_partial_messages = value;
+        self
+    }
+
+    /// Number of bytes of a message that must have been received, in order, for
+    /// it to be delivered in pieces through `Stream::read_partial` before it is
+    /// complete (RFC 6458 sec 8.1.21). Use it to receive messages larger than the
+    /// receive buffer. Defaults to half the receive buffer, zero disables partial
+    /// delivery.
+    pub fn with_partial_delivery_point(mut self, value: u32) -> Self {
+        self.partial_delivery_point = Some(value);
+        self
+    }
+
     pub fn with_max_num_outbound_streams(mut self, value: u16) -> Self {
         self.max_num_outbound_streams = value;
         self
@@ -54,14 +117,72 @@ impl TransportConfig {
         self
     }
 
+    /// Maximum number of packets carrying new DATA that may be sent in response to
+    /// a single SACK (RFC 9260 sec 6.1 D). Zero disables the limit. The budget is
+    /// renewed by each SACK rather than by each `poll_transmit` call, so polling
+    /// more often does not send more; it is also renewed when nothing is in
+    /// flight, since no SACK is then expected. Defaults to 4.
+    pub fn with_max_burst(mut self, value: u32) -> Self {
+        self.max_burst = value;
+        self
+    }
+
+    /// Spread transmissions over the round trip at a rate of cwnd/SRTT. When enabled,
+    /// `Transmit::earliest_send` is set and `poll_timeout` includes the next pacing slot.
+    pub fn with_pacing(mut self, value: bool) -> Self {
+        self.pacing = value;
+        self
+    }
+
+    /// Time a closed association lingers before it is drained from the endpoint,
+    /// answering late retransmissions from the peer with SHUTDOWN COMPLETE or
+    /// ABORT (RFC 4960 sec 8.4). The linger starts with the first `poll_transmit`
+    /// after the association closed. Zero disables the linger.
+    pub fn with_linger_timeout(mut self, value: Duration) -> Self {
+        self.linger_timeout = value;
+        self
+    }
+
+    /// Whether FORWARD TSN (RFC 3758) is advertised in the INIT or INIT ACK chunk.
+    /// Partial reliability is used only when the peer advertises it too. Defaults
+    /// to true.
+    pub fn with_forward_tsn(mut self, value: bool) -> Self {
+        self.supported_extensions.forward_tsn = value;
+        self
+    }
+
+    /// Whether RECONFIG (RFC 6525) is advertised in the INIT or INIT ACK chunk.
+    /// Streams can be reset only when the peer advertises it too. Defaults to true.
+    pub fn with_reconfig(mut self, value: bool) -> Self {
+        self.supported_extensions.reconfig = value;
+        self
+    }
+
     pub(crate) fn max_receive_buffer_size(&self) -> u32 {
         self.max_receive_buffer_size
     }
 
+    pub(crate) fn max_send_buffer_size(&self) -> u32 {
+        self.max_send_buffer_size
+    }
+
     pub(crate) fn max_message_size(&self) -> u32 {
         self.max_message_size
     }
 
+    pub(crate) fn max_receive_message_size(&self) -> u32 {
+        self.max_receive_message_size
+    }
+
+    pub(crate) fn max_num_partial_messages(&self) -> u32 {
+        self.max_num_partial_messages
+    }
+
+    pub(crate) fn partial_delivery_point(&self) -> u32 {
+        self.partial_delivery_point
+            .unwrap_or(self.max_receive_buffer_size / 2)
+    }
+
     pub(crate) fn max_num_outbound_streams(&self) -> u16 {
         self.max_num_outbound_streams
     }
@@ -69,6 +190,40 @@ impl TransportConfig {
     pub(crate) fn max_num_inbound_streams(&self) -> u16 {
         self.max_num_inbound_streams
     }
+
+    pub(crate) fn max_burst(&self) -> u32 {
+        self.max_burst
+    }
+
+    pub(crate) fn pacing(&self) -> bool {
+        self.pacing
+    }
+
+    pub(crate) fn linger_timeout(&self) -> Duration {
+        self.linger_timeout
+    }
+
+    pub(crate) fn supported_extensions(&self) -> SupportedExtensions {
+        self.supported_extensions
+    }
+}
+
+/// How strictly the endpoint checks that an incoming packet belongs to the association its
+/// verification tag selects
+///
+/// Packets failing the check are discarded before the association processes them.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum PacketValidation {
+    /// Only the verification tag and the destination port are checked
+    #[default]
+    Tag,
+    /// The source port must also be the peer's SCTP port
+    Ports,
+    /// The source address must also be the association's remote address
+    ///
+    /// Packets from a peer whose address changed, e.g. after a NAT rebinding or an ICE
+    /// restart, are dropped, so this only suits transports with a fixed remote address.
+    Strict,
 }
 
 /// Global configuration for the endpoint, affecting all associations
@@ -83,6 +238,8 @@ pub struct EndpointConfig {
     /// Create a aid generator for local aid in Endpoint struct
     pub(crate) aid_generator_factory:
         Arc<dyn Fn() -> Box<dyn AssociationIdGenerator> + Send + Sync>,
+
+    pub(crate) packet_validation: PacketValidation,
 }
 
 impl Default for EndpointConfig {
@@ -99,6 +256,7 @@ impl EndpointConfig {
         Self {
             max_payload_size: INITIAL_MTU - (COMMON_HEADER_SIZE + DATA_CHUNK_HEADER_SIZE),
             aid_generator_factory: Arc::new(aid_factory),
+            packet_validation: PacketValidation::default(),
         }
     }
 
@@ -141,6 +299,15 @@ impl EndpointConfig {
     pub fn get_max_payload_size(&self) -> u32 {
         self.max_payload_size
     }
+
+    /// How strictly incoming packets are matched against their association
+    ///
+    /// Defaults to `PacketValidation::Tag`, so that an association keeps working when the
+    /// transport below delivers the peer's packets from a new address.
+    pub fn packet_validation(&mut self, value: PacketValidation) -> &mut Self {
+        self.packet_validation = value;
+        self
+    }
 }
 
 impl fmt::Debug for EndpointConfig {
@@ -148,6 +315,7 @@ impl fmt::Debug for EndpointConfig {
         fmt.debug_struct("EndpointConfig")
             .field("max_payload_size", &self.max_payload_size)
             .field("aid_generator_factory", &"[ elided ]")
+            .field("packet_validation", &self.packet_validation)
             .finish()
     }
 }
@@ -162,6 +330,9 @@ pub struct ServerConfig {
 
     /// Maximum number of concurrent associations
     pub(crate) concurrent_associations: u32,
+
+    /// SCTP ports accepting incoming associations, any port if empty
+    pub(crate) listening_ports: Vec<u16>,
 }
 
 impl Default for ServerConfig {
@@ -169,6 +340,7 @@ impl Default for ServerConfig {
         ServerConfig {
             transport: Arc::new(TransportConfig::default()),
             concurrent_associations: 100_000,
+            listening_ports: vec![],
         }
     }
 }
@@ -178,6 +350,14 @@ impl ServerConfig {
     pub fn new() -> Self {
         ServerConfig::default()
     }
+
+    /// SCTP ports accepting incoming associations. An INIT sent to any other
+    /// port is answered with an ABORT (RFC 9260 sec 8.4). Defaults to accepting
+    /// every port.
+    pub fn with_listening_ports(mut self, ports: Vec<u16>) -> Self {
+        self.listening_ports = ports;
+        self
+    }
 }
 
 /// Configuration for outgoing associations
@@ -187,12 +367,20 @@ impl ServerConfig {
 pub struct ClientConfig {
     /// Transport configuration to use
     pub transport: Arc<TransportConfig>,
+
+    /// Local SCTP port, must not be zero
+    pub local_port: u16,
+
+    /// Remote SCTP port, must not be zero
+    pub remote_port: u16,
 }
 
 impl Default for ClientConfig {
     fn default() -> Self {
         ClientConfig {
             transport: Arc::new(TransportConfig::default()),
+            local_port: DEFAULT_SCTP_PORT,
+            remote_port: DEFAULT_SCTP_PORT,
         }
     }
 }
@@ -202,4 +390,12 @@ impl ClientConfig {
     pub fn new() -> Self {
         ClientConfig::default()
     }
+
+    /// Local and remote SCTP ports of the association. Both default to 5000
+    /// (RFC 8841 sec 4). Neither may be zero.
+    pub fn with_ports(mut self, local_port: u16, remote_port: u16) -> Self {
+        self.local_port = local_port;
+        self.remote_port = remote_port;
+        self
+    }
 }
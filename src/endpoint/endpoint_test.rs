@@ -16,7 +16,7 @@ use crate::chunk::chunk_selective_ack::{ChunkSelectiveAck, GapAckBlock};
 use crate::chunk::chunk_shutdown::ChunkShutdown;
 use crate::chunk::chunk_shutdown_ack::ChunkShutdownAck;
 use crate::chunk::chunk_shutdown_complete::ChunkShutdownComplete;
-use crate::chunk::{ErrorCauseProtocolViolation, PROTOCOL_VIOLATION};
+use crate::chunk::ErrorCause;
 use crate::packet::{CommonHeader, Packet};
 use crate::param::param_outgoing_reset_request::ParamOutgoingResetRequest;
 use crate::param::param_reconfig_response::ParamReconfigResponse;
@@ -56,6 +56,7 @@ fn split_transmit(transmit: Transmit) -> Vec<Transmit> {
                 payload: Payload::RawEncode(vec![content]),
                 ecn: transmit.ecn,
                 local_ip: transmit.local_ip,
+                earliest_send: transmit.earliest_send,
             });
         }
     }
@@ -115,7 +116,7 @@ impl TestEndpoint {
             }
         }
 
-        while self.inbound.front().map_or(false, |x| x.0 <= now) {
+        while self.inbound.front().is_some_and(|x| x.0 <= now) {
             let (recv_time, ecn, packet) = self.inbound.pop_front().unwrap();
             if let Some((ch, event)) = self.endpoint.handle(recv_time, remote, None, ecn, packet) {
                 match event {
@@ -135,27 +136,29 @@ impl TestEndpoint {
         }
 
         let mut endpoint_events: Vec<(AssociationHandle, EndpointEvent)> = vec![];
+        let mut timeout = None;
         for (ch, conn) in self.associations.iter_mut() {
-            if self.timeout.map_or(false, |x| x <= now) {
-                self.timeout = None;
+            if conn.poll_timeout().is_some_and(|x| x <= now) {
                 conn.handle_timeout(now);
             }
 
-            for (_, mut events) in self.conn_events.drain() {
-                for event in events.drain(..) {
+            if let Some(events) = self.conn_events.remove(ch) {
+                for event in events {
                     conn.handle_event(event);
                 }
             }
 
-            while let Some(event) = conn.poll_endpoint_event() {
-                endpoint_events.push((*ch, event));
-            }
-
             while let Some(x) = conn.poll_transmit(now) {
                 self.outbound.extend(split_transmit(x));
             }
-            self.timeout = conn.poll_timeout();
+
+            // Sending the final chunk may drain the association
+            while let Some(event) = conn.poll_endpoint_event() {
+                endpoint_events.push((*ch, event));
+            }
+            timeout = min_opt(timeout, conn.poll_timeout());
         }
+        self.timeout = timeout;
 
         for (ch, event) in endpoint_events {
             if let Some(event) = self.handle_event(ch, event) {
@@ -328,12 +331,12 @@ impl Pair {
     fn finish_connect(&mut self, client_ch: AssociationHandle, server_ch: AssociationHandle) {
         assert_matches!(
             self.client_conn_mut(client_ch).poll(),
-            Some(Event::Connected { .. })
+            Some(Event::Connected)
         );
 
         assert_matches!(
             self.server_conn_mut(server_ch).poll(),
-            Some(Event::Connected { .. })
+            Some(Event::Connected)
         );
     }
 
@@ -381,6 +384,7 @@ fn create_association_pair(
         } else {
             TransportConfig::default()
         }),
+        ..Default::default()
     });
     pair.client_conn_mut(client_ch).ack_mode = ack_mode;
     pair.server_conn_mut(server_ch).ack_mode = ack_mode;
@@ -509,6 +513,133 @@ fn test_assoc_reliable_simple() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_assoc_early_data_bundled_with_cookie_echo() -> Result<()> {
+    //let _guard = subscribe();
+
+    let si: u16 = 1;
+    let msg: Bytes = Bytes::from_static(b"ABC");
+
+    let mut pair = Pair::default();
+    let client_ch = pair.begin_connect(client_config());
+
+    // Written while in CookieWait
+    let _ = pair
+        .client_conn_mut(client_ch)
+        .open_stream(si, PayloadProtocolIdentifier::Binary)?;
+    let n = pair
+        .client_stream(client_ch, si)?
+        .write_sctp(&msg, PayloadProtocolIdentifier::Dcep)?;
+    assert_eq!(msg.len(), n, "unexpected length of written data");
+
+    pair.drive_client(); // INIT
+    pair.drive_server(); // INIT-ACK
+
+    pair.client.drive(pair.time, pair.server.addr);
+    assert_eq!(
+        pair.client_conn_mut(client_ch).state(),
+        AssociationState::CookieEchoed
+    );
+    let mut cookie_echo_packets = vec![];
+    for x in &pair.client.outbound {
+        if let Payload::RawEncode(contents) = &x.payload {
+            for content in contents {
+                let p = Packet::unmarshal(content)?;
+                if p.chunks[0]
+                    .as_any()
+                    .downcast_ref::<ChunkCookieEcho>()
+                    .is_some()
+                {
+                    cookie_echo_packets.push(p);
+                }
+            }
+        }
+    }
+    assert_eq!(cookie_echo_packets.len(), 1, "should send one COOKIE-ECHO");
+    let p = &cookie_echo_packets[0];
+    assert_eq!(p.chunks.len(), 2, "DATA should be bundled with COOKIE-ECHO");
+    let d = p.chunks[1]
+        .as_any()
+        .downcast_ref::<ChunkPayloadData>()
+        .expect("second chunk should be DATA");
+    assert_eq!(d.user_data, msg);
+
+    pair.drive();
+    let server_ch = pair.server.assert_accept();
+    pair.finish_connect(client_ch, server_ch);
+
+    let _ = pair.server_conn_mut(server_ch).accept_stream().unwrap();
+    let chunks = pair.server_stream(server_ch, si)?.read_sctp()?.unwrap();
+    let mut buf = vec![0u8; 1024];
+    let n = chunks.read(&mut buf)?;
+    assert_eq!(&buf[..n], &msg[..], "received data mismatch");
+    assert_eq!(
+        chunks.ppi,
+        PayloadProtocolIdentifier::Dcep,
+        "unexpected ppi"
+    );
+
+    pair.drive();
+    assert_eq!(
+        0,
+        pair.client_conn_mut(client_ch).buffered_amount(),
+        "early data should be acknowledged"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_writes_queued_during_handshake() -> Result<()> {
+    //let _guard = subscribe();
+
+    let si: u16 = 1;
+    let mut sbuf = vec![0u8; 1000];
+    for (i, b) in sbuf.iter_mut().enumerate() {
+        *b = (i & 0xff) as u8;
+    }
+
+    let mut pair = Pair::default();
+    let client_ch = pair.begin_connect(client_config());
+
+    let _ = pair
+        .client_conn_mut(client_ch)
+        .open_stream(si, PayloadProtocolIdentifier::Binary)?;
+    pair.client_stream(client_ch, si)?.set_reliability_params(
+        true,
+        ReliabilityType::Reliable,
+        0,
+    )?;
+    for i in 0..3u8 {
+        sbuf[0] = i;
+        pair.client_stream(client_ch, si)?.write_sctp(
+            &Bytes::from(sbuf.clone()),
+            PayloadProtocolIdentifier::Binary,
+        )?;
+    }
+    assert_eq!(3000, pair.client_conn_mut(client_ch).buffered_amount());
+
+    pair.drive();
+    let server_ch = pair.server.assert_accept();
+    pair.finish_connect(client_ch, server_ch);
+    pair.drive();
+
+    let _ = pair.server_conn_mut(server_ch).accept_stream().unwrap();
+    let mut rbuf = vec![0u8; 2000];
+    let mut received = vec![];
+    while let Some(chunks) = pair.server_stream(server_ch, si)?.read_sctp()? {
+        let n = chunks.read(&mut rbuf)?;
+        assert_eq!(n, sbuf.len(), "unexpected length of received data");
+        assert_eq!(&rbuf[1..n], &sbuf[1..], "received data mismatch");
+        received.push(rbuf[0]);
+    }
+    received.sort();
+    assert_eq!(received, vec![0, 1, 2], "all queued messages should arrive");
+    assert_eq!(0, pair.client_conn_mut(client_ch).buffered_amount());
+
+    Ok(())
+}
+
 #[test]
 fn test_assoc_reliable_ordered_reordered() -> Result<()> {
     // let _guard = subscribe();
@@ -1647,10 +1778,10 @@ fn test_assoc_congestion_control_slow_reader() -> Result<()> {
     let mut rbuf = vec![0u8; 3000];
 
     // 1. First forward packets to receiver until rwnd becomes 0
-    // 2. Wait until the sender's cwnd becomes 1*MTU (RTO occurred)
+    // 2. Wait until the sender has sent a zero window probe
     // 3. Stat reading a1's data
     let mut n_packets_received = 0u32;
-    let mut has_rtoed = false;
+    let mut has_probed = false;
     while pair.client_conn_mut(client_ch).buffered_amount() > 0
         && n_packets_received < n_packets_to_send
     {
@@ -1662,19 +1793,21 @@ fn test_assoc_congestion_control_slow_reader() -> Result<()> {
             n_packets_to_send
         );*/
 
-        if !has_rtoed {
+        if !has_probed {
             let rwnd = pair
                 .server_conn_mut(server_ch)
                 .get_my_receiver_window_credit();
-            let cwnd = pair.client_conn_mut(client_ch).cwnd;
-            let cmtu = pair.client_conn_mut(client_ch).mtu;
-            if cwnd > cmtu || rwnd > 0 {
+            let n_probes = pair
+                .client_conn_mut(client_ch)
+                .stats
+                .get_num_zero_window_probes();
+            if n_probes == 0 || rwnd > 0 {
                 // Do not read until a1.getMyReceiverWindowCredit() becomes zero
                 pair.step();
                 continue;
             }
 
-            has_rtoed = true;
+            has_probed = true;
         }
 
         while let Some(chunks) = pair.server_stream(server_ch, si)?.read_sctp()? {
@@ -1874,6 +2007,186 @@ fn test_assoc_reset_close_one_way() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_assoc_reset_without_reconfig() -> Result<()> {
+    //let _guard = subscribe();
+
+    let si: u16 = 1;
+
+    // The client does not advertise RECONFIG
+    let mut pair = Pair::default();
+    let (client_ch, server_ch) = pair.connect_with(ClientConfig {
+        transport: Arc::new(TransportConfig::default().with_reconfig(false)),
+        ..Default::default()
+    });
+
+    for a in [
+        pair.client_conn_mut(client_ch).negotiated().cloned(),
+        pair.server_conn_mut(server_ch).negotiated().cloned(),
+    ] {
+        let n = a.expect("should be negotiated");
+        assert!(n.use_forward_tsn, "both sides support FORWARD TSN");
+        assert!(!n.use_reconfig, "only the server supports RECONFIG");
+    }
+
+    establish_session_pair(&mut pair, client_ch, server_ch, si)?;
+
+    assert_eq!(
+        Err(Error::ErrReconfigNotSupported),
+        pair.client_stream(client_ch, si)?.stop()
+    );
+    assert_eq!(
+        Err(Error::ErrReconfigNotSupported),
+        pair.server_stream(server_ch, si)?.stop()
+    );
+    assert!(
+        pair.client_stream(client_ch, si)?.is_readable(),
+        "stream should stay open"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_sctp_ports() -> Result<()> {
+    //let _guard = subscribe();
+
+    let mut pair = Pair::new(
+        Arc::new(EndpointConfig::default()),
+        ServerConfig::default().with_listening_ports(vec![5001, 5002]),
+    );
+
+    // Two services share the same pair of UDP endpoints
+    let mut handles = vec![];
+    for (local_port, remote_port) in [(6001, 5001), (6002, 5002)] {
+        let (client_ch, server_ch) =
+            pair.connect_with(ClientConfig::default().with_ports(local_port, remote_port));
+
+        let n = pair.client_conn_mut(client_ch).negotiated().unwrap();
+        assert_eq!(local_port, n.local_port, "client local port");
+        assert_eq!(remote_port, n.peer_port, "client peer port");
+        let n = pair.server_conn_mut(server_ch).negotiated().unwrap();
+        assert_eq!(remote_port, n.local_port, "server local port");
+        assert_eq!(local_port, n.peer_port, "server peer port");
+
+        handles.push((client_ch, server_ch));
+    }
+    assert_eq!(2, pair.server.endpoint.associations.len());
+
+    // Nobody listens on 5003, so the INIT is answered with an ABORT
+    let client_ch = pair.begin_connect(ClientConfig::default().with_ports(6003, 5003));
+    pair.drive();
+    assert!(
+        pair.server.accepted.is_none(),
+        "server should refuse the INIT"
+    );
+    assert_eq!(2, pair.server.endpoint.associations.len());
+    assert_eq!(
+        AssociationState::Closed,
+        pair.client_conn_mut(client_ch).state(),
+        "client should be aborted"
+    );
+
+    // Both services still carry data on their own port
+    for (client_ch, server_ch) in handles {
+        establish_session_pair(&mut pair, client_ch, server_ch, 1)?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_packet_validation() -> Result<()> {
+    //let _guard = subscribe();
+
+    assert_eq!(
+        PacketValidation::Tag,
+        EndpointConfig::default().packet_validation,
+        "address checks should be opt-in"
+    );
+
+    for (validation, wrong_port_accepted, wrong_addr_accepted) in [
+        (PacketValidation::Tag, true, true),
+        (PacketValidation::Ports, false, true),
+        (PacketValidation::Strict, false, false),
+    ] {
+        let mut endpoint_config = EndpointConfig::default();
+        endpoint_config.packet_validation(validation);
+        let mut pair = Pair::new(Arc::new(endpoint_config), server_config());
+        let (client_ch, _) = pair.connect();
+
+        let tag = pair
+            .client_conn_mut(client_ch)
+            .negotiated()
+            .unwrap()
+            .local_verification_tag;
+        let server_addr = pair.server.addr;
+        let spoofed_addr = SocketAddr::new(Ipv6Addr::LOCALHOST.into(), server_addr.port() + 1);
+
+        // A spoofed ABORT carrying the right verification tag
+        let abort = |source_port: u16, destination_port: u16| -> Result<Bytes> {
+            Packet {
+                common_header: CommonHeader {
+                    source_port,
+                    destination_port,
+                    verification_tag: tag,
+                },
+                chunks: vec![Box::new(ChunkAbort {
+                    error_causes: vec![],
+                })],
+            }
+            .marshal()
+        };
+
+        let now = pair.time;
+        let endpoint = &mut pair.client.endpoint;
+        assert!(
+            endpoint
+                .handle(now, server_addr, None, None, abort(5000, 5001)?)
+                .is_none(),
+            "{:?}: wrong destination port should be dropped",
+            validation
+        );
+        assert_eq!(
+            wrong_port_accepted,
+            endpoint
+                .handle(now, server_addr, None, None, abort(5001, 5000)?)
+                .is_some(),
+            "{:?}: wrong source port",
+            validation
+        );
+        assert_eq!(
+            wrong_addr_accepted,
+            endpoint
+                .handle(now, spoofed_addr, None, None, abort(5000, 5000)?)
+                .is_some(),
+            "{:?}: wrong remote address",
+            validation
+        );
+        assert!(
+            matches!(
+                endpoint.handle(now, server_addr, None, None, abort(5000, 5000)?),
+                Some((ch, DatagramEvent::AssociationEvent(_))) if ch == client_ch
+            ),
+            "{:?}: matching packet should reach the association",
+            validation
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_assoc_connect_zero_sctp_port() {
+    let mut pair = Pair::default();
+    let server_addr = pair.server.addr;
+    assert_matches!(
+        pair.client
+            .connect(ClientConfig::default().with_ports(0, 5000), server_addr),
+        Err(ConnectError::InvalidSctpPort)
+    );
+}
+
 #[test]
 fn test_assoc_reset_close_both_ways() -> Result<()> {
     //let _guard = subscribe();
@@ -1970,9 +2283,8 @@ fn test_assoc_abort() -> Result<()> {
 
     let transmit = {
         let abort = ChunkAbort {
-            error_causes: vec![ErrorCauseProtocolViolation {
-                code: PROTOCOL_VIOLATION,
-                ..Default::default()
+            error_causes: vec![ErrorCause::ProtocolViolation {
+                additional_information: Bytes::new(),
             }],
         };
 
@@ -1986,6 +2298,7 @@ fn test_assoc_abort() -> Result<()> {
             remote: pair.server.addr,
             ecn: None,
             local_ip: None,
+            earliest_send: None,
             payload: Payload::RawEncode(vec![packet]),
         }
     };
@@ -2020,6 +2333,59 @@ fn test_assoc_abort() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_assoc_graceful_shutdown() -> Result<()> {
+    //let _guard = subscribe();
+
+    let si: u16 = 1;
+    let msg: Bytes = Bytes::from_static(b"ABC");
+
+    let (mut pair, client_ch, server_ch) = create_association_pair(AckMode::NoDelay, 0)?;
+
+    establish_session_pair(&mut pair, client_ch, server_ch, si)?;
+    while pair.client_conn_mut(client_ch).poll().is_some() {}
+    while pair.server_conn_mut(server_ch).poll().is_some() {}
+
+    pair.client_stream(client_ch, si)?
+        .write_sctp(&msg, PayloadProtocolIdentifier::Binary)?;
+    pair.client_conn_mut(client_ch).shutdown()?;
+    pair.drive();
+
+    // The message written before the shutdown was acked, not reported as failed
+    let client_events: Vec<Event> =
+        std::iter::from_fn(|| pair.client_conn_mut(client_ch).poll()).collect();
+    assert!(
+        matches!(client_events.last(), Some(Event::ShutdownComplete))
+            && !client_events
+                .iter()
+                .any(|e| matches!(e, Event::SendFailed { .. })),
+        "unexpected client events {:?}",
+        client_events
+    );
+    let server_events: Vec<Event> =
+        std::iter::from_fn(|| pair.server_conn_mut(server_ch).poll()).collect();
+    assert!(
+        server_events
+            .iter()
+            .any(|e| matches!(e, Event::ShutdownComplete)),
+        "unexpected server events {:?}",
+        server_events
+    );
+
+    assert!(pair.client_conn_mut(client_ch).is_closed());
+    assert!(pair.server_conn_mut(server_ch).is_closed());
+    assert!(
+        pair.client.endpoint.associations.is_empty(),
+        "client endpoint should drop the association"
+    );
+    assert!(
+        pair.server.endpoint.associations.is_empty(),
+        "server endpoint should drop the association"
+    );
+
+    Ok(())
+}
+
 #[test]
 fn test_association_handle_packet_before_init() -> Result<()> {
     //let _guard = subscribe();
@@ -2187,7 +2553,17 @@ fn test_association_handle_packet_before_init() -> Result<()> {
 
         //let (a_conn, charlie_conn) = pipe();
         let config = Arc::new(TransportConfig::default());
-        let mut a = Association::new(None, config, 1400, 0, remote, None, Instant::now());
+        let mut a = Association::new(
+            None,
+            config,
+            1400,
+            0,
+            remote,
+            None,
+            5000,
+            5000,
+            Instant::now(),
+        );
 
         let packet = packet.marshal()?;
         a.handle_event(AssociationEvent(AssociationEventInner::Datagram(
@@ -2196,6 +2572,7 @@ fn test_association_handle_packet_before_init() -> Result<()> {
                 remote,
                 ecn: None,
                 local_ip: None,
+                earliest_send: None,
                 payload: Payload::RawEncode(vec![packet]),
             },
         )));
@@ -11,9 +11,12 @@ use std::{
 };
 
 use crate::association::Association;
+use crate::chunk::chunk_abort::ChunkAbort;
 use crate::chunk::chunk_type::CT_INIT;
-use crate::config::{ClientConfig, EndpointConfig, ServerConfig, TransportConfig};
-use crate::packet::PartialDecode;
+use crate::config::{
+    ClientConfig, EndpointConfig, PacketValidation, ServerConfig, TransportConfig,
+};
+use crate::packet::{CommonHeader, Packet, PartialDecode};
 use crate::shared::{
     AssociationEvent, AssociationEventInner, AssociationId, EndpointEvent, EndpointEventInner,
 };
@@ -155,6 +158,23 @@ impl Endpoint {
         };
 
         if let Some(ch) = known_ch {
+            // The verification tag alone picks the association, so a stray or spoofed
+            // packet carrying a guessed tag must still match its address and ports.
+            if !self.associations[ch.0].matches(
+                &partial_decode.common_header,
+                remote,
+                self.config.packet_validation,
+            ) {
+                trace!(
+                    "dropping packet from {} ports {}->{} not matching association {:?}",
+                    remote,
+                    partial_decode.common_header.source_port,
+                    partial_decode.common_header.destination_port,
+                    ch
+                );
+                return None;
+            }
+
             return Some((
                 ch,
                 DatagramEvent::AssociationEvent(AssociationEvent(AssociationEventInner::Datagram(
@@ -164,6 +184,7 @@ impl Endpoint {
                         ecn,
                         payload: Payload::PartialDecode(partial_decode),
                         local_ip,
+                        earliest_send: None,
                     },
                 ))),
             ));
@@ -188,6 +209,9 @@ impl Endpoint {
         if remote.port() == 0 {
             return Err(ConnectError::InvalidRemoteAddress(remote));
         }
+        if config.local_port == 0 || config.remote_port == 0 {
+            return Err(ConnectError::InvalidSctpPort);
+        }
 
         let remote_aid = RandomAssociationIdGenerator::new().generate_aid();
         let local_aid = self.new_aid();
@@ -197,6 +221,8 @@ impl Endpoint {
             local_aid,
             remote,
             None,
+            config.local_port,
+            config.remote_port,
             Instant::now(),
             None,
             config.transport,
@@ -228,7 +254,22 @@ impl Endpoint {
             return None;
         }
 
-        let server_config = self.server_config.as_ref().unwrap();
+        // RFC 9260 sec 8.4: an INIT addressed to a port nobody listens on is
+        // answered with an ABORT carrying the Initiate Tag of the INIT.
+        let local_port = partial_decode.common_header.destination_port;
+        let server_config = match self.server_config.as_ref() {
+            Some(server_config)
+                if server_config.listening_ports.is_empty()
+                    || server_config.listening_ports.contains(&local_port) =>
+            {
+                server_config
+            }
+            _ => {
+                debug!("refusing INIT to closed port {}", local_port);
+                self.send_abort(now, remote, local_ip, &partial_decode);
+                return None;
+            }
+        };
 
         if self.associations.len() >= server_config.concurrent_associations as usize
             || self.reject_new_associations
@@ -250,6 +291,8 @@ impl Endpoint {
             local_aid,
             remote,
             local_ip,
+            local_port,
+            partial_decode.common_header.source_port,
             now,
             Some(server_config),
             transport_config,
@@ -262,12 +305,49 @@ impl Endpoint {
                 ecn,
                 payload: Payload::PartialDecode(partial_decode),
                 local_ip,
+                earliest_send: None,
             },
         )));
 
         Some((ch, conn))
     }
 
+    /// Answer an INIT that does not create an association with an ABORT
+    fn send_abort(
+        &mut self,
+        now: Instant,
+        remote: SocketAddr,
+        local_ip: Option<IpAddr>,
+        partial_decode: &PartialDecode,
+    ) {
+        let Some(initiate_tag) = partial_decode.initiate_tag else {
+            return;
+        };
+
+        let abort = Packet {
+            common_header: CommonHeader {
+                source_port: partial_decode.common_header.destination_port,
+                destination_port: partial_decode.common_header.source_port,
+                verification_tag: initiate_tag,
+            },
+            chunks: vec![Box::new(ChunkAbort {
+                error_causes: vec![],
+            })],
+        };
+
+        match abort.marshal() {
+            Ok(raw) => self.transmits.push_back(Transmit {
+                now,
+                remote,
+                ecn: None,
+                payload: Payload::RawEncode(vec![raw]),
+                local_ip,
+                earliest_send: None,
+            }),
+            Err(err) => debug!("failed to marshal ABORT: {}", err),
+        }
+    }
+
     #[allow(clippy::too_many_arguments)]
     fn add_association(
         &mut self,
@@ -275,6 +355,8 @@ impl Endpoint {
         local_aid: AssociationId,
         remote_addr: SocketAddr,
         local_ip: Option<IpAddr>,
+        local_port: u16,
+        remote_port: u16,
         now: Instant,
         server_config: Option<Arc<ServerConfig>>,
         transport_config: Arc<TransportConfig>,
@@ -286,6 +368,8 @@ impl Endpoint {
             local_aid,
             remote_addr,
             local_ip,
+            local_port,
+            remote_port,
             now,
         );
 
@@ -294,6 +378,8 @@ impl Endpoint {
             cids_issued: 0,
             loc_cids: iter::once((0, local_aid)).collect(),
             initial_remote: remote_addr,
+            local_port,
+            remote_port,
         });
 
         let ch = AssociationHandle(id);
@@ -326,9 +412,33 @@ pub(crate) struct AssociationMeta {
     loc_cids: FxHashMap<u64, AssociationId>,
     /// Remote address the association began with
     ///
-    /// Only needed to support associations with zero-length AIDs, which cannot migrate, so we don't
-    /// bother keeping it up to date.
+    /// Associations never migrate, so this is also the only address their packets may come from.
     initial_remote: SocketAddr,
+    /// Local SCTP port the association is bound to
+    local_port: u16,
+    /// SCTP port of the peer
+    remote_port: u16,
+}
+
+impl AssociationMeta {
+    /// Whether a packet received from `remote` may belong to this association
+    fn matches(
+        &self,
+        common_header: &CommonHeader,
+        remote: SocketAddr,
+        validation: PacketValidation,
+    ) -> bool {
+        if common_header.destination_port != self.local_port {
+            return false;
+        }
+        match validation {
+            PacketValidation::Tag => true,
+            PacketValidation::Ports => common_header.source_port == self.remote_port,
+            PacketValidation::Strict => {
+                common_header.source_port == self.remote_port && remote == self.initial_remote
+            }
+        }
+    }
 }
 
 /// Internal identifier for an `Association` currently associated with an endpoint
@@ -391,4 +501,7 @@ pub enum ConnectError {
     /// Use `Endpoint::connect_with` to specify a client configuration.
     #[error("no default client config")]
     NoDefaultClientConfig,
+    /// The local or remote SCTP port supplied in the client configuration was zero
+    #[error("invalid SCTP port")]
+    InvalidSctpPort,
 }
@@ -109,6 +109,8 @@ pub enum Error {
 
     #[error("packet is smaller than the header size")]
     ErrChunkPayloadSmall,
+    #[error("DATA chunk has no user data")]
+    ErrNoUserData,
     #[error("ChunkType is not of type PayloadData")]
     ErrChunkTypeNotPayloadData,
     #[error("ChunkType is not of type Reconfig")]
@@ -186,6 +188,8 @@ pub enum Error {
     ErrInitAckNoCookie,
     #[error("there already exists a stream with identifier")]
     ErrStreamAlreadyExist,
+    #[error("stream identifier exceeds the number of outbound streams")]
+    ErrStreamIdentifierOutOfRange,
     #[error("Failed to create a stream with identifier")]
     ErrStreamCreateFailed,
     #[error("unable to be popped from inflight queue TSN")]
@@ -207,6 +211,20 @@ pub enum Error {
 
     #[error("outbound packet larger than maximum message size")]
     ErrOutboundPacketTooLarge,
+    #[error("a message is being written in pieces on the stream")]
+    ErrMessageInProgress,
+    #[error("a message is being read in pieces on the stream")]
+    ErrPartialDeliveryInProgress,
+    #[error("send buffer is full")]
+    ErrSendBufferFull,
+    #[error("inbound message larger than maximum receive message size")]
+    ErrInboundMessageTooLarge,
+    #[error("too many partially received messages")]
+    ErrTooManyPartialMessages,
+    #[error("partial reliability was not negotiated with the peer")]
+    ErrForwardTsnNotSupported,
+    #[error("stream reconfiguration was not negotiated with the peer")]
+    ErrReconfigNotSupported,
     #[error("Stream closed")]
     ErrStreamClosed,
     #[error("Stream not existed")]
@@ -27,18 +27,21 @@ use std::{
 mod association;
 pub use crate::association::{
     stats::AssociationStats,
-    stream::{ReliabilityType, Stream, StreamEvent, StreamId, StreamState},
-    Association, AssociationError, Event,
+    stream::{MessageHandle, ReliabilityType, Stream, StreamEvent, StreamId, StreamState},
+    Association, AssociationError, Event, FailedMessage, NegotiatedParameters, ReceivedMessage,
+    SupportedExtensions,
 };
 
 pub(crate) mod chunk;
 pub use crate::chunk::{
     chunk_payload_data::{ChunkPayloadData, PayloadProtocolIdentifier},
-    ErrorCauseCode,
+    error_cause::{ErrorCause, ErrorCauseCode},
 };
 
 mod config;
-pub use crate::config::{ClientConfig, EndpointConfig, ServerConfig, TransportConfig};
+pub use crate::config::{
+    ClientConfig, EndpointConfig, PacketValidation, ServerConfig, TransportConfig,
+};
 
 mod endpoint;
 pub use crate::endpoint::{AssociationHandle, ConnectError, DatagramEvent, Endpoint};
@@ -54,7 +57,7 @@ pub use crate::shared::{AssociationEvent, AssociationId, EcnCodepoint, EndpointE
 pub(crate) mod param;
 
 pub(crate) mod queue;
-pub use crate::queue::reassembly_queue::{Chunk, Chunks};
+pub use crate::queue::reassembly_queue::{Chunk, Chunks, Fragments, MessagePayload, PartialChunks};
 
 pub(crate) mod util;
 
@@ -122,6 +125,9 @@ pub struct Transmit {
     pub ecn: Option<EcnCodepoint>,
     /// Optional local IP address for the datagram
     pub local_ip: Option<IpAddr>,
+    /// Earliest time the datagram should be put on the wire, set on DATA packets when
+    /// pacing is enabled. Packets carrying only control chunks are never paced.
+    pub earliest_send: Option<Instant>,
     /// Payload of the datagram
     pub payload: Payload,
 }
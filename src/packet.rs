@@ -13,6 +13,7 @@ use crate::chunk::chunk_shutdown::ChunkShutdown;
 use crate::chunk::chunk_shutdown_ack::ChunkShutdownAck;
 use crate::chunk::chunk_shutdown_complete::ChunkShutdownComplete;
 use crate::chunk::chunk_type::*;
+use crate::chunk::chunk_unknown::ChunkUnknown;
 use crate::chunk::Chunk;
 use crate::error::{Error, Result};
 use crate::util::*;
@@ -20,6 +21,8 @@ use crate::util::*;
 use bytes::{Buf, BufMut, Bytes, BytesMut};
 use std::fmt;
 
+pub(crate) const PACKET_HEADER_SIZE: usize = 12;
+
 ///Packet represents an SCTP packet, defined in https://tools.ietf.org/html/rfc4960#section-3
 ///An SCTP packet is composed of a common header and chunks.  A chunk
 ///contains either control information or user data.
@@ -50,10 +53,7 @@ use std::fmt;
 ///+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
 ///|                           Checksum                            |
 ///+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
-
-pub(crate) const PACKET_HEADER_SIZE: usize = 12;
-
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
 pub(crate) struct CommonHeader {
     pub(crate) source_port: u16,
     pub(crate) destination_port: u16,
@@ -165,7 +165,7 @@ impl PartialDecode {
                 CT_SHUTDOWN_COMPLETE => Box::new(ChunkShutdownComplete::unmarshal(
                     &self.remaining.slice(offset..),
                 )?),
-                _ => return Err(Error::ErrUnmarshalUnknownChunkType),
+                _ => Box::new(ChunkUnknown::unmarshal(&self.remaining.slice(offset..))?),
             };
 
             let chunk_value_padding = get_padding_size(c.value_length());
@@ -252,7 +252,7 @@ impl Packet {
                 CT_SHUTDOWN_COMPLETE => {
                     Box::new(ChunkShutdownComplete::unmarshal(&raw.slice(offset..))?)
                 }
-                _ => return Err(Error::ErrUnmarshalUnknownChunkType),
+                _ => Box::new(ChunkUnknown::unmarshal(&raw.slice(offset..))?),
             };
 
             let chunk_value_padding = get_padding_size(c.value_length());
@@ -356,6 +356,124 @@ impl Packet {
     }
 }
 
+/// PacketBuilder bundles outgoing chunks of an association into as few packets as
+/// the path MTU allows.
+///
+/// RFC 4960 sec 6.10.  Bundling
+///   An endpoint bundles chunks by simply including multiple chunks in one
+///   outbound SCTP packet.  The total size of the resultant IP datagram,
+///   including the SCTP packet and IP headers, MUST be less than or equal
+///   to the current Path MTU.
+///
+///   When bundling control chunks with DATA chunks, an endpoint MUST place
+///   control chunks first in the outbound SCTP packet.  The transmitter
+///   MUST transmit DATA chunks within an SCTP packet in increasing order
+///   of TSN.
+pub(crate) struct PacketBuilder {
+    common_header: CommonHeader,
+    mtu: usize,
+    unbundled: Vec<Packet>,
+    control_chunks: Vec<Box<dyn Chunk + Send + Sync>>,
+    data_chunks: Vec<Box<dyn Chunk + Send + Sync>>,
+}
+
+impl PacketBuilder {
+    pub(crate) fn new(common_header: CommonHeader, mtu: u32) -> Self {
+        PacketBuilder {
+            common_header,
+            mtu: mtu as usize,
+            unbundled: vec![],
+            control_chunks: vec![],
+            data_chunks: vec![],
+        }
+    }
+
+    /// push_packet adds an already built packet. Its chunks are bundled with the
+    /// others unless the packet carries a different common header or a chunk that
+    /// must travel alone.
+    pub(crate) fn push_packet(&mut self, p: Packet) {
+        if p.common_header != self.common_header
+            || p.chunks
+                .iter()
+                .any(|c| !PacketBuilder::is_bundleable(c.as_ref()))
+        {
+            self.unbundled.push(p);
+            return;
+        }
+
+        for c in p.chunks {
+            if c.header().typ == CT_PAYLOAD_DATA {
+                self.data_chunks.push(c);
+            } else {
+                self.control_chunks.push(c);
+            }
+        }
+    }
+
+    /// push_control adds a control chunk, which is placed ahead of any DATA chunk.
+    pub(crate) fn push_control(&mut self, c: Box<dyn Chunk + Send + Sync>) {
+        if PacketBuilder::is_bundleable(c.as_ref()) {
+            self.control_chunks.push(c);
+        } else {
+            self.unbundled.push(Packet {
+                common_header: self.common_header,
+                chunks: vec![c],
+            });
+        }
+    }
+
+    /// push_data adds a DATA chunk. DATA chunks keep the order they are pushed in.
+    pub(crate) fn push_data(&mut self, c: ChunkPayloadData) {
+        self.data_chunks.push(Box::new(c));
+    }
+
+    /// build returns the packets: unbundled ones first, followed by the bundled
+    /// control and DATA chunks packed up to the MTU.
+    pub(crate) fn build(self) -> Vec<Packet> {
+        let mut packets = self.unbundled;
+        let mut chunks: Vec<Box<dyn Chunk + Send + Sync>> = vec![];
+        let mut bytes_in_packet = PACKET_HEADER_SIZE;
+
+        for c in self.control_chunks.into_iter().chain(self.data_chunks) {
+            let chunk_size = CHUNK_HEADER_SIZE + c.value_length();
+            let chunk_size = chunk_size + get_padding_size(chunk_size);
+            if !chunks.is_empty() && bytes_in_packet + chunk_size > self.mtu {
+                packets.push(Packet {
+                    common_header: self.common_header,
+                    chunks,
+                });
+                chunks = vec![];
+                bytes_in_packet = PACKET_HEADER_SIZE;
+            }
+
+            bytes_in_packet += chunk_size;
+            chunks.push(c);
+        }
+
+        if !chunks.is_empty() {
+            packets.push(Packet {
+                common_header: self.common_header,
+                chunks,
+            });
+        }
+
+        packets
+    }
+
+    fn is_bundleable(c: &(dyn Chunk + Send + Sync)) -> bool {
+        // INIT, INIT ACK and SHUTDOWN COMPLETE MUST NOT be bundled with any other
+        // chunk. ABORT is kept alone so that no chunk is processed after it.
+        // COOKIE ECHO MUST be the first chunk of its packet, which is built with
+        // the DATA bundled with it.
+        let typ = c.header().typ;
+        typ != CT_INIT
+            && typ != CT_INIT_ACK
+            && typ != CT_SHUTDOWN_COMPLETE
+            && typ != CT_ABORT
+            && typ != CT_COOKIE_ECHO
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -471,4 +589,115 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_packet_builder_bundles_control_before_data() -> Result<()> {
+        let common_header = CommonHeader {
+            source_port: 5000,
+            destination_port: 5000,
+            verification_tag: 1234,
+        };
+        let mut builder = PacketBuilder::new(common_header, 1228);
+
+        builder.push_data(ChunkPayloadData {
+            tsn: 1,
+            user_data: Bytes::from_static(b"ABC"),
+            ..Default::default()
+        });
+        builder.push_control(Box::new(ChunkSelectiveAck::default()));
+        builder.push_packet(Packet {
+            common_header: CommonHeader {
+                verification_tag: 0,
+                ..common_header
+            },
+            chunks: vec![Box::new(ChunkInit::default())],
+        });
+        builder.push_control(Box::new(ChunkShutdownComplete {}));
+
+        let packets = builder.build();
+        assert_eq!(3, packets.len(), "INIT and SHUTDOWN-COMPLETE travel alone");
+        assert_eq!(CT_INIT, packets[0].chunks[0].header().typ);
+        assert_eq!(CT_SHUTDOWN_COMPLETE, packets[1].chunks[0].header().typ);
+        assert_eq!(
+            2,
+            packets[2].chunks.len(),
+            "SACK and DATA should be bundled"
+        );
+        assert_eq!(CT_SACK, packets[2].chunks[0].header().typ, "control first");
+        assert_eq!(CT_PAYLOAD_DATA, packets[2].chunks[1].header().typ);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_packet_builder_respects_mtu() -> Result<()> {
+        let mtu = 1228;
+        let mut builder = PacketBuilder::new(CommonHeader::default(), mtu);
+        for tsn in 0..5 {
+            builder.push_data(ChunkPayloadData {
+                tsn,
+                user_data: Bytes::from(vec![0u8; 500]),
+                ..Default::default()
+            });
+        }
+
+        let packets = builder.build();
+        assert_eq!(3, packets.len(), "two 500 byte chunks fit per packet");
+        for p in &packets {
+            assert!(p.marshal()?.len() <= mtu as usize, "packet exceeds MTU");
+        }
+        let tsns: Vec<u32> = packets
+            .iter()
+            .flat_map(|p| p.chunks.iter())
+            .filter_map(|c| c.as_any().downcast_ref::<ChunkPayloadData>())
+            .map(|c| c.tsn)
+            .collect();
+        assert_eq!(vec![0, 1, 2, 3, 4], tsns, "DATA should keep TSN order");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_packet_unmarshal_unknown_chunk() -> Result<()> {
+        let p = Packet {
+            common_header: CommonHeader {
+                source_port: 5000,
+                destination_port: 5000,
+                verification_tag: 1,
+            },
+            chunks: vec![
+                Box::new(ChunkUnknown {
+                    typ: ChunkType(0x41),
+                    flags: 0x01,
+                    value: Bytes::from_static(b"ABCDE"),
+                }),
+                Box::new(ChunkCookieAck {}),
+            ],
+        };
+
+        let raw = p.marshal()?;
+        for pkt in [
+            Packet::unmarshal(&raw)?,
+            PartialDecode::unmarshal(&raw)?.finish()?,
+        ] {
+            assert_eq!(2, pkt.chunks.len(), "should parse past the unknown chunk");
+            let c = pkt.chunks[0]
+                .as_any()
+                .downcast_ref::<ChunkUnknown>()
+                .expect("should be ChunkUnknown");
+            assert_eq!(ChunkType(0x41), c.typ);
+            assert_eq!(0x01, c.flags);
+            assert_eq!(Bytes::from_static(b"ABCDE"), c.value);
+            assert!(
+                c.stop_processing() && c.report(),
+                "action bits should be 01"
+            );
+            assert!(pkt.chunks[1]
+                .as_any()
+                .downcast_ref::<ChunkCookieAck>()
+                .is_some());
+        }
+
+        Ok(())
+    }
 }
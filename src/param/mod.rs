@@ -13,6 +13,7 @@ pub(crate) mod param_state_cookie;
 pub(crate) mod param_supported_extensions;
 pub(crate) mod param_type;
 pub(crate) mod param_uknown;
+pub(crate) mod param_unrecognized;
 
 use crate::error::{Error, Result};
 use crate::param::{
@@ -22,6 +23,7 @@ use crate::param::{
     param_reconfig_response::ParamReconfigResponse,
     param_requested_hmac_algorithm::ParamRequestedHmacAlgorithm,
     param_state_cookie::ParamStateCookie, param_supported_extensions::ParamSupportedExtensions,
+    param_unrecognized::ParamUnrecognized,
 };
 use param_header::*;
 use param_type::*;
@@ -72,16 +74,11 @@ pub(crate) fn build_param(raw_param: &Bytes) -> Result<Box<dyn Param + Send + Sy
         ParamType::HeartbeatInfo => Ok(Box::new(ParamHeartbeatInfo::unmarshal(raw_param)?)),
         ParamType::OutSsnResetReq => Ok(Box::new(ParamOutgoingResetRequest::unmarshal(raw_param)?)),
         ParamType::ReconfigResp => Ok(Box::new(ParamReconfigResponse::unmarshal(raw_param)?)),
+        ParamType::UnrecognizedParam => Ok(Box::new(ParamUnrecognized::unmarshal(raw_param)?)),
         _ => {
-            // According to RFC https://datatracker.ietf.org/doc/html/rfc4960#section-3.2.1
-            let stop_processing = ((raw_type >> 15) & 0x01) == 0;
-            if stop_processing {
-                Err(Error::ErrParamTypeUnhandled { typ: raw_type })
-            } else {
-                // We still might need to report this param as unrecognized.
-                // This depends on the context though.
-                Ok(Box::new(ParamUnknown::unmarshal(raw_param)?))
-            }
+            // The action to take depends on the highest-order 2 bits of the type,
+            // see ParamUnknown, and is up to the receiver of the chunk.
+            Ok(Box::new(ParamUnknown::unmarshal(raw_param)?))
         }
     }
 }
@@ -63,3 +63,36 @@ impl Param for ParamUnknown {
         Box::new(self.clone())
     }
 }
+
+impl ParamUnknown {
+    /// is_unrecognized returns whether the parameter type is unknown to us, as opposed
+    /// to a known type that we do not process.
+    pub(crate) fn is_unrecognized(&self) -> bool {
+        matches!(ParamType::from(self.typ), ParamType::Unknown { .. })
+    }
+
+    /// RFC 4960 sec 3.2.1
+    ///   The Parameter Types are encoded such that the highest-order 2 bits
+    ///   specify the action that must be taken if the processing endpoint
+    ///   does not recognize the Parameter Type.
+    ///
+    ///   00 - Stop processing this parameter; do not process any further
+    ///        parameters within this chunk.
+    ///   01 - Stop processing this parameter, do not process any further
+    ///        parameters within this chunk, and report the unrecognized
+    ///        parameter in an 'Unrecognized Parameter', as described in
+    ///        Section 3.2.2.
+    ///   10 - Skip this parameter and continue processing.
+    ///   11 - Skip this parameter and continue processing but report the
+    ///        unrecognized parameter in an 'Unrecognized Parameter', as
+    ///        described in Section 3.2.2.
+    pub(crate) fn stop_processing(&self) -> bool {
+        self.typ & 0x8000 == 0
+    }
+
+    /// report returns whether the parameter must be reported to the sender.
+    /// See stop_processing.
+    pub(crate) fn report(&self) -> bool {
+        self.typ & 0x4000 != 0
+    }
+}
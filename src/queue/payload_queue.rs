@@ -66,7 +66,9 @@ impl PayloadQueue {
             self.sorted.remove(0);
             if let Some(c) = self.chunk_map.remove(&tsn) {
                 //self.length -= 1;
-                self.n_bytes -= c.user_data.len();
+                if !c.acked {
+                    self.n_bytes -= c.user_data.len();
+                }
                 return Some(c);
             }
         }
@@ -131,9 +133,10 @@ impl PayloadQueue {
         let n_bytes_acked = if let Some(c) = self.chunk_map.get_mut(&tsn) {
             c.acked = true;
             c.retransmit = false;
+            // The payload is kept until the cumulative ack passes the chunk, so
+            // that a message failing to be sent is still reported whole.
             let n = c.user_data.len();
             self.n_bytes -= n;
-            c.user_data.clear();
             n
         } else {
             0
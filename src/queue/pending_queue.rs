@@ -1,5 +1,7 @@
 use crate::chunk::chunk_payload_data::ChunkPayloadData;
+use crate::StreamId;
 
+use fxhash::FxHashMap;
 use std::collections::VecDeque;
 
 /// pendingBaseQueue
@@ -99,6 +101,48 @@ impl PendingQueue {
         popped
     }
 
+    /// remove_messages removes the messages matching the predicate, see
+    /// [`remove_messages`]. Returns the removed chunks.
+    pub(crate) fn remove_messages<F>(
+        &mut self,
+        should_remove: &mut F,
+        n_removed: &mut FxHashMap<StreamId, u16>,
+    ) -> Vec<ChunkPayloadData>
+    where
+        F: FnMut(&ChunkPayloadData) -> bool,
+    {
+        let mut removed = remove_messages(&mut self.unordered_queue, should_remove, n_removed);
+        removed.extend(remove_messages(
+            &mut self.ordered_queue,
+            should_remove,
+            n_removed,
+        ));
+
+        for c in &removed {
+            self.n_bytes -= c.user_data.len();
+            self.queue_len -= 1;
+        }
+
+        removed
+    }
+
+    pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item = &mut ChunkPayloadData> {
+        self.unordered_queue
+            .iter_mut()
+            .chain(self.ordered_queue.iter_mut())
+    }
+
+    /// drain removes all the queued chunks, unordered ones first.
+    pub(crate) fn drain(&mut self) -> Vec<ChunkPayloadData> {
+        self.queue_len = 0;
+        self.n_bytes = 0;
+        self.selected = false;
+        self.unordered_queue
+            .drain(..)
+            .chain(self.ordered_queue.drain(..))
+            .collect()
+    }
+
     pub(crate) fn get_num_bytes(&self) -> usize {
         self.n_bytes
     }
@@ -111,3 +155,64 @@ impl PendingQueue {
         self.len() == 0
     }
 }
+
+/// remove_messages removes from the queue the messages whose fragments are all
+/// queued, none having been sent, and whose first fragment matches the
+/// predicate. Since the ordered messages left behind them in the queue have
+/// not been sent either, their stream sequence numbers are renumbered to
+/// close the gaps, `n_removed` counting the ordered messages removed so far
+/// per stream. Returns the removed chunks.
+pub(crate) fn remove_messages<F>(
+    queue: &mut PendingBaseQueue,
+    should_remove: &mut F,
+    n_removed: &mut FxHashMap<StreamId, u16>,
+) -> Vec<ChunkPayloadData>
+where
+    F: FnMut(&ChunkPayloadData) -> bool,
+{
+    // Leave the queue untouched when there is nothing to remove or renumber
+    if n_removed.is_empty()
+        && !queue
+            .iter()
+            .any(|c| c.beginning_fragment && should_remove(c))
+    {
+        return vec![];
+    }
+
+    let mut kept = PendingBaseQueue::with_capacity(queue.len());
+    let mut removed = vec![];
+    let mut message: Vec<ChunkPayloadData> = vec![];
+    for mut c in queue.drain(..) {
+        if message.is_empty() && !c.beginning_fragment {
+            // The rest of a message being sent
+            kept.push_back(c);
+            continue;
+        }
+
+        // Chunks without user data are stream reset requests
+        let is_reset_request = c.user_data.is_empty();
+        if !c.unordered && !is_reset_request {
+            if let Some(n) = n_removed.get(&c.stream_identifier) {
+                c.stream_sequence_number = c.stream_sequence_number.wrapping_sub(*n);
+            }
+        }
+
+        let ending_fragment = c.ending_fragment;
+        message.push(c);
+        if ending_fragment {
+            if !is_reset_request && should_remove(&message[0]) {
+                if !message[0].unordered {
+                    *n_removed.entry(message[0].stream_identifier).or_default() += 1;
+                }
+                removed.append(&mut message);
+            } else {
+                kept.extend(message.drain(..));
+            }
+        }
+    }
+    // The message being written in pieces
+    kept.extend(message);
+
+    *queue = kept;
+    removed
+}
@@ -192,6 +192,7 @@ fn test_payload_queue_reset_retransmit_flag_on_ack() -> Result<()> {
 //pending_queue_test
 ///////////////////////////////////////////////////////////////////
 use super::pending_queue::*;
+use fxhash::FxHashMap;
 
 const NO_FRAGMENT: usize = 0;
 const FRAG_BEGIN: usize = 1;
@@ -425,6 +426,48 @@ fn test_pending_queue_selection_persistence() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_pending_queue_remove_messages() -> Result<()> {
+    let mut pq = PendingQueue::new();
+    let chunk = |tsn: u32, stream_identifier: u16, ssn: u16, frag: usize| ChunkPayloadData {
+        stream_identifier,
+        stream_sequence_number: ssn,
+        ..make_data_chunk(tsn, false, frag)
+    };
+    pq.push(chunk(0, 1, 0, FRAG_BEGIN));
+    pq.push(chunk(1, 1, 0, FRAG_END));
+    pq.push(chunk(2, 1, 1, NO_FRAGMENT));
+    pq.push(chunk(3, 2, 0, NO_FRAGMENT));
+    pq.push(chunk(4, 1, 2, NO_FRAGMENT));
+    // being written in pieces
+    pq.push(chunk(5, 1, 3, FRAG_BEGIN));
+
+    // The first message is being sent
+    let c = pq.pop(true, false).expect("should pop");
+    assert_eq!(0, c.tsn);
+
+    let mut n_removed = FxHashMap::default();
+    let removed = pq.remove_messages(&mut |c| c.stream_identifier == 1, &mut n_removed);
+    assert_eq!(
+        vec![2, 4],
+        removed.iter().map(|c| c.tsn).collect::<Vec<u32>>(),
+        "should remove the messages not sent"
+    );
+    assert_eq!(Some(&2), n_removed.get(&1));
+    assert_eq!(3, pq.len());
+    assert_eq!(30, pq.get_num_bytes());
+
+    for (tsn, ssn) in [(1, 0), (3, 0), (5, 1)] {
+        let c = pq.peek().expect("should peek");
+        assert_eq!(tsn, c.tsn);
+        assert_eq!(ssn, c.stream_sequence_number, "should renumber");
+        let (b, u) = (c.beginning_fragment, c.unordered);
+        pq.pop(b, u);
+    }
+
+    Ok(())
+}
+
 ///////////////////////////////////////////////////////////////////
 //reassembly_queue_test
 ///////////////////////////////////////////////////////////////////
@@ -1036,3 +1079,269 @@ fn test_reassembly_queue_ssn_overflow_in_forward_tsn_for_ordered() -> Result<()>
 
     Ok(())
 }
+
+#[test]
+fn test_reassembly_queue_message_len_and_partial_messages() -> Result<()> {
+    let mut rq = ReassemblyQueue::new(0);
+
+    let fragment = |tsn: u32, ssn: u16, unordered: bool, b: bool, e: bool, data: &'static [u8]| {
+        ChunkPayloadData {
+            unordered,
+            beginning_fragment: b,
+            ending_fragment: e,
+            tsn,
+            stream_sequence_number: ssn,
+            user_data: Bytes::from_static(data),
+            ..Default::default()
+        }
+    };
+
+    // ordered message 0: first fragment only
+    let c = fragment(1, 0, false, true, false, b"ABC");
+    rq.push(c.clone());
+    assert_eq!(3, rq.message_len(&c));
+    assert_eq!(1, rq.num_partial_messages());
+
+    // unordered message: first and middle fragments
+    let c = fragment(10, 0, true, true, false, b"AB");
+    rq.push(c.clone());
+    let c = fragment(11, 0, true, false, false, b"CDE");
+    rq.push(c.clone());
+    assert_eq!(5, rq.message_len(&c));
+    assert_eq!(2, rq.num_partial_messages());
+
+    // unordered fragment of another message, behind a gap
+    let c = fragment(13, 0, true, false, false, b"XYZW");
+    rq.push(c.clone());
+    assert_eq!(4, rq.message_len(&c));
+    assert_eq!(3, rq.num_partial_messages());
+
+    // completing the ordered message
+    let c = fragment(2, 0, false, false, true, b"DEFG");
+    rq.push(c.clone());
+    assert_eq!(7, rq.message_len(&c));
+    assert_eq!(2, rq.num_partial_messages());
+
+    // completing the first unordered message
+    let c = fragment(12, 0, true, false, true, b"F");
+    rq.push(c.clone());
+    assert_eq!(6, rq.message_len(&c), "completed message");
+    assert_eq!(1, rq.num_partial_messages());
+    assert_eq!(rq.count_partial_messages(), rq.num_partial_messages());
+
+    Ok(())
+}
+
+#[test]
+fn test_reassembly_queue_partial_messages_running_count() -> Result<()> {
+    let mut rq = ReassemblyQueue::new(0);
+
+    let fragment = |tsn: u32, ssn: u16, unordered: bool, b: bool, e: bool| ChunkPayloadData {
+        unordered,
+        beginning_fragment: b,
+        ending_fragment: e,
+        tsn,
+        stream_sequence_number: ssn,
+        user_data: Bytes::from_static(b"AB"),
+        ..Default::default()
+    };
+
+    // (chunk, partial messages once it is pushed)
+    let steps = [
+        // two middle fragments apart count as two messages
+        (fragment(20, 0, true, false, false), 1),
+        (fragment(22, 0, true, false, false), 2),
+        // filling the gap joins them
+        (fragment(21, 0, true, false, false), 1),
+        // a beginning fragment in front joins them too
+        (fragment(19, 0, true, true, false), 1),
+        // a message ending right before splits nothing
+        (fragment(18, 0, true, false, true), 2),
+        // completing the message
+        (fragment(23, 0, true, false, true), 1),
+        // a duplicate of a complete ordered message
+        (fragment(30, 0, false, true, true), 1),
+        (fragment(30, 0, false, true, true), 1),
+        // ordered fragments
+        (fragment(32, 2, false, false, true), 2),
+        (fragment(31, 2, false, true, false), 1),
+    ];
+    for (i, (c, n)) in steps.into_iter().enumerate() {
+        rq.push(c);
+        assert_eq!(n, rq.num_partial_messages(), "step {}", i);
+        assert_eq!(rq.count_partial_messages(), rq.num_partial_messages());
+    }
+
+    rq.forward_tsn_for_unordered(20);
+    assert_eq!(0, rq.num_partial_messages());
+    assert_eq!(rq.count_partial_messages(), rq.num_partial_messages());
+
+    Ok(())
+}
+
+#[test]
+fn test_reassembly_queue_partial_delivery_ordered() -> Result<()> {
+    let mut rq = ReassemblyQueue::new(0);
+
+    let fragment = |tsn: u32, b: bool, e: bool, data: &'static [u8]| ChunkPayloadData {
+        beginning_fragment: b,
+        ending_fragment: e,
+        tsn,
+        user_data: Bytes::from_static(data),
+        ..Default::default()
+    };
+
+    rq.push(fragment(1, true, false, b"ABC"));
+    assert!(
+        !rq.start_partial_delivery(4),
+        "below the partial delivery point"
+    );
+    rq.push(fragment(3, false, false, b"GHI"));
+    assert!(!rq.start_partial_delivery(4), "fragment 3 is behind a gap");
+    rq.push(fragment(2, false, false, b"DEF"));
+    assert!(
+        rq.start_partial_delivery(4),
+        "should start partial delivery"
+    );
+    assert!(!rq.start_partial_delivery(4), "already started");
+    assert!(rq.is_readable(), "should be readable");
+
+    let p = rq.read_partial().expect("should read a piece");
+    assert!(!p.end_of_record, "message is not complete");
+    let mut buf = [0u8; 16];
+    let n = p.chunks.read(&mut buf)?;
+    assert_eq!(&buf[..n], b"ABCDEFGHI");
+    assert_eq!(0, rq.get_num_bytes());
+    assert!(!rq.is_readable(), "nothing more to read");
+    assert!(rq.read_partial().is_none(), "nothing more to read");
+
+    rq.push(fragment(4, false, true, b"J"));
+    let p = rq.read_partial().expect("should read the last piece");
+    assert!(p.end_of_record, "message is complete");
+    let mut buf = [0u8; 16];
+    let n = p.chunks.read(&mut buf)?;
+    assert_eq!(&buf[..n], b"J");
+    assert_eq!(1, rq.next_ssn, "next_ssn should advance");
+    assert!(rq.ordered.is_empty(), "should be empty");
+
+    // Without partial delivery, complete messages are read whole
+    rq.push(ChunkPayloadData {
+        stream_sequence_number: 1,
+        ..fragment(5, true, true, b"KL")
+    });
+    let p = rq.read_partial().expect("should read a message");
+    assert!(p.end_of_record, "message is complete");
+    let mut buf = [0u8; 16];
+    let n = p.chunks.read(&mut buf)?;
+    assert_eq!(&buf[..n], b"KL");
+
+    Ok(())
+}
+
+#[test]
+fn test_reassembly_queue_partial_delivery_aborted_by_forward_tsn() -> Result<()> {
+    let mut rq = ReassemblyQueue::new(0);
+
+    rq.push(ChunkPayloadData {
+        beginning_fragment: true,
+        tsn: 1,
+        user_data: Bytes::from_static(b"ABC"),
+        ..Default::default()
+    });
+    rq.push(ChunkPayloadData {
+        unordered: true,
+        beginning_fragment: true,
+        tsn: 10,
+        user_data: Bytes::from_static(b"ABC"),
+        ..Default::default()
+    });
+    assert!(
+        rq.start_partial_delivery(1),
+        "should start partial delivery"
+    );
+    assert!(rq.partial.unwrap().unordered, "unordered goes first");
+    rq.read_partial().expect("should read a piece");
+
+    assert!(
+        !rq.forward_tsn_for_ordered(0),
+        "ordered message not in delivery"
+    );
+    assert!(
+        !rq.forward_tsn_for_unordered(10),
+        "only the delivered fragment is covered"
+    );
+    assert!(
+        rq.forward_tsn_for_unordered(11),
+        "should abort partial delivery"
+    );
+    assert!(rq.partial.is_none(), "partial delivery should end");
+    assert!(!rq.forward_tsn_for_unordered(12), "already aborted");
+
+    Ok(())
+}
+
+#[test]
+fn test_chunks_into_payload() -> Result<()> {
+    let fragment = |tsn: u32, b: bool, e: bool, data: &Bytes| ChunkPayloadData {
+        beginning_fragment: b,
+        ending_fragment: e,
+        tsn,
+        user_data: data.clone(),
+        ..Default::default()
+    };
+    let abc = Bytes::from_static(b"ABC");
+    let def = Bytes::from_static(b"DEF");
+
+    let chunks = Chunks::new(
+        0,
+        PayloadProtocolIdentifier::Binary,
+        vec![fragment(1, true, true, &abc)],
+    );
+    match chunks.into_payload() {
+        MessagePayload::Bytes(b) => assert_eq!(abc.as_ptr(), b.as_ptr(), "should share the buffer"),
+        MessagePayload::Fragments(_) => panic!("should not be fragmented"),
+    }
+
+    let chunks = Chunks::new(
+        0,
+        PayloadProtocolIdentifier::Binary,
+        vec![
+            fragment(1, true, false, &abc),
+            fragment(2, false, true, &def),
+        ],
+    );
+    match chunks.into_payload() {
+        MessagePayload::Fragments(fragments) => {
+            assert_eq!(2, fragments.len());
+            let fragments: Vec<Bytes> = fragments.collect();
+            assert_eq!(
+                abc.as_ptr(),
+                fragments[0].as_ptr(),
+                "should share the buffer"
+            );
+            assert_eq!(
+                def.as_ptr(),
+                fragments[1].as_ptr(),
+                "should share the buffer"
+            );
+        }
+        MessagePayload::Bytes(_) => panic!("should be fragmented"),
+    }
+
+    // Data already read is skipped
+    let mut chunks = Chunks::new(
+        0,
+        PayloadProtocolIdentifier::Binary,
+        vec![
+            fragment(1, true, false, &abc),
+            fragment(2, false, true, &def),
+        ],
+    );
+    chunks.next(4).expect("should read a chunk");
+    match chunks.into_payload() {
+        MessagePayload::Bytes(b) => assert_eq!(Bytes::from_static(b"EF"), b),
+        MessagePayload::Fragments(_) => panic!("should have one fragment left"),
+    }
+
+    Ok(())
+}
@@ -3,7 +3,7 @@ use crate::error::{Error, Result};
 use crate::util::*;
 use crate::StreamId;
 
-use bytes::{Bytes, BytesMut};
+use bytes::{Buf, Bytes, BytesMut};
 use std::cmp::Ordering;
 
 fn sort_chunks_by_tsn(c: &mut [ChunkPayloadData]) {
@@ -33,6 +33,45 @@ pub struct Chunk {
     pub bytes: Bytes,
 }
 
+/// A piece of a message read through partial delivery
+#[derive(Debug)]
+pub struct PartialChunks {
+    /// The fragments of the message that could be read
+    pub chunks: Chunks,
+    /// Whether these fragments end the message
+    pub end_of_record: bool,
+}
+
+/// The payload of a message, sharing the buffers of the packets it was
+/// received in
+#[derive(Debug)]
+pub enum MessagePayload {
+    /// The message was received in a single fragment
+    Bytes(Bytes),
+    /// The fragments of the message, in order
+    Fragments(Fragments),
+}
+
+/// Fragments iterates over the fragments of a message
+#[derive(Debug)]
+pub struct Fragments {
+    chunks: std::vec::IntoIter<ChunkPayloadData>,
+}
+
+impl Iterator for Fragments {
+    type Item = Bytes;
+
+    fn next(&mut self) -> Option<Bytes> {
+        self.chunks.next().map(|c| c.user_data)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.chunks.size_hint()
+    }
+}
+
+impl ExactSizeIterator for Fragments {}
+
 /// Chunks is a set of chunks that share the same SSN
 #[derive(Default, Debug, Clone)]
 pub struct Chunks {
@@ -100,6 +139,23 @@ impl Chunks {
         })
     }
 
+    /// into_payload returns the data not read yet without copying it
+    pub fn into_payload(self) -> MessagePayload {
+        let mut chunks = self.chunks;
+        chunks.drain(..self.index);
+        if let Some(c) = chunks.first_mut() {
+            c.user_data.advance(self.offset);
+        }
+
+        if chunks.len() == 1 {
+            MessagePayload::Bytes(chunks.remove(0).user_data)
+        } else {
+            MessagePayload::Fragments(Fragments {
+                chunks: chunks.into_iter(),
+            })
+        }
+    }
+
     pub(crate) fn new(
         ssn: u16,
         ppi: PayloadProtocolIdentifier,
@@ -176,6 +232,24 @@ impl Chunks {
     }
 }
 
+/// starts_message tells whether `c`, following `prev` in TSN order, cannot be a
+/// fragment of the same message as `prev`.
+fn starts_message(prev: &ChunkPayloadData, c: &ChunkPayloadData) -> bool {
+    c.beginning_fragment || prev.ending_fragment || prev.tsn.wrapping_add(1) != c.tsn
+}
+
+/// PartialDelivery tracks the message being delivered in pieces
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PartialDelivery {
+    pub(crate) unordered: bool,
+    pub(crate) ssn: u16,
+    pub(crate) ppi: PayloadProtocolIdentifier,
+    /// TSN of the next fragment to deliver
+    pub(crate) next_tsn: u32,
+    /// Bytes of the message delivered so far
+    pub(crate) delivered: usize,
+}
+
 #[derive(Default, Debug)]
 pub(crate) struct ReassemblyQueue {
     pub(crate) si: StreamId,
@@ -185,6 +259,10 @@ pub(crate) struct ReassemblyQueue {
     pub(crate) unordered: Vec<Chunks>,
     pub(crate) unordered_chunks: Vec<ChunkPayloadData>,
     pub(crate) n_bytes: usize,
+    pub(crate) partial: Option<PartialDelivery>,
+    /// Number of messages of which fragments have been received but that are
+    /// not complete yet, kept up to date as chunks are pushed
+    n_partial_messages: usize,
 }
 
 impl ReassemblyQueue {
@@ -201,10 +279,18 @@ impl ReassemblyQueue {
             unordered: vec![],
             unordered_chunks: vec![],
             n_bytes: 0,
+            partial: None,
+            n_partial_messages: 0,
         }
     }
 
     pub(crate) fn push(&mut self, chunk: ChunkPayloadData) -> bool {
+        let complete = self.push_chunk(chunk);
+        debug_assert_eq!(self.count_partial_messages(), self.n_partial_messages);
+        complete
+    }
+
+    fn push_chunk(&mut self, chunk: ChunkPayloadData) -> bool {
         if chunk.stream_identifier != self.si {
             return false;
         }
@@ -213,13 +299,28 @@ impl ReassemblyQueue {
             // First, insert into unordered_chunks array
             //atomic.AddUint64(&r.n_bytes, uint64(len(chunk.userData)))
             self.n_bytes += chunk.user_data.len();
-            self.unordered_chunks.push(chunk);
-            sort_chunks_by_tsn(&mut self.unordered_chunks);
+
+            // Inserting the chunk may start a new message and may split the
+            // message it is inserted in front of from its preceding fragments.
+            let i = self
+                .unordered_chunks
+                .partition_point(|c| sna32lt(c.tsn, chunk.tsn));
+            let next_started = i < self.unordered_chunks.len()
+                && (i == 0
+                    || starts_message(&self.unordered_chunks[i - 1], &self.unordered_chunks[i]));
+            self.unordered_chunks.insert(i, chunk);
+            let chunks = &self.unordered_chunks;
+            let started = i == 0 || starts_message(&chunks[i - 1], &chunks[i]);
+            let next_starts = i + 1 < chunks.len() && starts_message(&chunks[i], &chunks[i + 1]);
+            self.n_partial_messages =
+                (self.n_partial_messages + started as usize + next_starts as usize)
+                    .saturating_sub(next_started as usize);
 
             // Scan unordered_chunks that are contiguous (in TSN)
             // If found, append the complete set to the unordered array
             if let Some(cset) = self.find_complete_unordered_chunk_set() {
                 self.unordered.push(cset);
+                self.n_partial_messages = self.n_partial_messages.saturating_sub(1);
                 return true;
             }
 
@@ -235,7 +336,16 @@ impl ReassemblyQueue {
             // Check if a chunkSet with the SSN already exists
             for s in &mut self.ordered {
                 if s.ssn == chunk.stream_sequence_number {
-                    return s.push(chunk);
+                    let was_complete = s.is_complete();
+                    let ok = s.push(chunk);
+                    match (was_complete, s.is_complete()) {
+                        (false, true) => {
+                            self.n_partial_messages = self.n_partial_messages.saturating_sub(1)
+                        }
+                        (true, false) => self.n_partial_messages += 1,
+                        _ => {}
+                    }
+                    return ok;
                 }
             }
 
@@ -243,6 +353,9 @@ impl ReassemblyQueue {
             let mut cset = Chunks::new(chunk.stream_sequence_number, chunk.payload_type, vec![]);
             let unordered = chunk.unordered;
             let ok = cset.push(chunk);
+            if !ok {
+                self.n_partial_messages += 1;
+            }
             self.ordered.push(cset);
             if !unordered {
                 sort_chunks_by_ssn(&mut self.ordered);
@@ -304,6 +417,15 @@ impl ReassemblyQueue {
     }
 
     pub(crate) fn is_readable(&self) -> bool {
+        self.is_partially_readable() || self.has_complete_message()
+    }
+
+    /// has_complete_message tells whether read would return a message.
+    pub(crate) fn has_complete_message(&self) -> bool {
+        if self.is_partially_read() {
+            return false;
+        }
+
         // Check unordered first
         if !self.unordered.is_empty() {
             // The chunk sets in r.unordered should all be complete.
@@ -321,6 +443,10 @@ impl ReassemblyQueue {
     }
 
     pub(crate) fn read(&mut self) -> Option<Chunks> {
+        if self.is_partially_read() {
+            return None;
+        }
+
         // Check unordered first
         let chunks = if !self.unordered.is_empty() {
             self.unordered.remove(0)
@@ -343,12 +469,161 @@ impl ReassemblyQueue {
 
         self.subtract_num_bytes(chunks.len());
 
+        // The message in partial delivery may have been completed before any
+        // of it was read
+        let first_tsn = chunks.chunks.first().map(|c| c.tsn);
+        if self.partial.map(|p| p.next_tsn) == first_tsn {
+            self.partial = None;
+        }
+
         Some(chunks)
     }
 
+    /// is_partially_read tells whether part of a message has been read with
+    /// read_partial, in which case the rest of it must be read the same way.
+    pub(crate) fn is_partially_read(&self) -> bool {
+        self.partial.is_some_and(|p| p.delivered > 0)
+    }
+
+    /// start_partial_delivery puts the head message in partial delivery once at
+    /// least `pd_point` bytes of it can be read in order, unless a complete
+    /// message is available. Returns whether partial delivery started.
+    pub(crate) fn start_partial_delivery(&mut self, pd_point: usize) -> bool {
+        if self.partial.is_some() || self.is_readable() {
+            return false;
+        }
+
+        // Check unordered first
+        let mut i = 0;
+        while i < self.unordered_chunks.len() {
+            let c = &self.unordered_chunks[i];
+            let mut n = c.user_data.len();
+            let mut j = i + 1;
+            while j < self.unordered_chunks.len()
+                && !starts_message(&self.unordered_chunks[j - 1], &self.unordered_chunks[j])
+            {
+                n += self.unordered_chunks[j].user_data.len();
+                j += 1;
+            }
+            if c.beginning_fragment && n >= pd_point {
+                self.partial = Some(PartialDelivery {
+                    unordered: true,
+                    ssn: 0,
+                    ppi: c.payload_type,
+                    next_tsn: c.tsn,
+                    delivered: 0,
+                });
+                return true;
+            }
+            i = j;
+        }
+
+        // Then the ordered head message
+        if let Some(cset) = self.ordered.first() {
+            let first = match cset.chunks.first() {
+                Some(c) if c.beginning_fragment && cset.ssn == self.next_ssn => c,
+                _ => return false,
+            };
+            let mut n = 0;
+            for (i, c) in cset.chunks.iter().enumerate() {
+                if i > 0 && starts_message(&cset.chunks[i - 1], c) {
+                    break;
+                }
+                n += c.user_data.len();
+            }
+            if n >= pd_point {
+                self.partial = Some(PartialDelivery {
+                    unordered: false,
+                    ssn: cset.ssn,
+                    ppi: cset.ppi,
+                    next_tsn: first.tsn,
+                    delivered: 0,
+                });
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// is_partially_readable tells whether the next fragment of the message in
+    /// partial delivery has been received.
+    fn is_partially_readable(&self) -> bool {
+        let p = match &self.partial {
+            Some(p) => p,
+            None => return false,
+        };
+        let chunks = if p.unordered {
+            &self.unordered_chunks
+        } else {
+            match self.ordered.first() {
+                Some(cset) if cset.ssn == p.ssn => &cset.chunks,
+                _ => return false,
+            }
+        };
+        chunks.iter().any(|c| c.tsn == p.next_tsn)
+    }
+
+    /// read_partial reads the fragments received in order of the message in
+    /// partial delivery, or a complete message if none has been partly read.
+    pub(crate) fn read_partial(&mut self) -> Option<PartialChunks> {
+        let mut p = match self.partial {
+            Some(p) if p.delivered > 0 || !self.has_complete_message() => p,
+            _ => {
+                return self.read().map(|chunks| PartialChunks {
+                    chunks,
+                    end_of_record: true,
+                })
+            }
+        };
+
+        let chunks = if p.unordered {
+            &mut self.unordered_chunks
+        } else {
+            match self.ordered.first_mut() {
+                Some(cset) if cset.ssn == p.ssn => &mut cset.chunks,
+                _ => return None,
+            }
+        };
+        let start = chunks.iter().position(|c| c.tsn == p.next_tsn)?;
+        let mut end = start;
+        while !chunks[end].ending_fragment
+            && end + 1 < chunks.len()
+            && chunks[end + 1].tsn == chunks[end].tsn.wrapping_add(1)
+        {
+            end += 1;
+        }
+        let fragments: Vec<ChunkPayloadData> = chunks.drain(start..=end).collect();
+
+        let last = &fragments[fragments.len() - 1];
+        let end_of_record = last.ending_fragment;
+        p.next_tsn = last.tsn.wrapping_add(1);
+        if end_of_record {
+            self.partial = None;
+            if !p.unordered {
+                self.ordered.remove(0);
+                self.next_ssn = self.next_ssn.wrapping_add(1);
+            }
+        } else {
+            self.partial = Some(p);
+        }
+
+        let chunks = Chunks::new(p.ssn, p.ppi, fragments);
+        self.subtract_num_bytes(chunks.len());
+        self.n_partial_messages = self.count_partial_messages();
+        if let Some(partial) = &mut self.partial {
+            partial.delivered += chunks.len();
+        }
+
+        Some(PartialChunks {
+            chunks,
+            end_of_record,
+        })
+    }
+
     /// Use last_ssn to locate a chunkSet then remove it if the set has
-    /// not been complete
-    pub(crate) fn forward_tsn_for_ordered(&mut self, last_ssn: u16) {
+    /// not been complete. Returns whether this aborted a partial delivery.
+    pub(crate) fn forward_tsn_for_ordered(&mut self, last_ssn: u16) -> bool {
         let num_bytes = self
             .ordered
             .iter()
@@ -365,14 +640,23 @@ impl ReassemblyQueue {
         if sna16lte(self.next_ssn, last_ssn) {
             self.next_ssn = last_ssn.wrapping_add(1);
         }
+        self.n_partial_messages = self.count_partial_messages();
+
+        match self.partial {
+            Some(p) if !p.unordered && sna16lte(p.ssn, last_ssn) => {
+                self.partial = None;
+                true
+            }
+            _ => false,
+        }
     }
 
     /// Remove all fragments in the unordered sets that contains chunks
     /// equal to or older than `new_cumulative_tsn`.
     /// We know all sets in the r.unordered are complete ones.
     /// Just remove chunks that are equal to or older than new_cumulative_tsn
-    /// from the unordered_chunks
-    pub(crate) fn forward_tsn_for_unordered(&mut self, new_cumulative_tsn: u32) {
+    /// from the unordered_chunks. Returns whether this aborted a partial delivery.
+    pub(crate) fn forward_tsn_for_unordered(&mut self, new_cumulative_tsn: u32) -> bool {
         let mut last_idx: isize = -1;
         for (i, c) in self.unordered_chunks.iter().enumerate() {
             if sna32gt(c.tsn, new_cumulative_tsn) {
@@ -385,7 +669,85 @@ impl ReassemblyQueue {
                 self.subtract_num_bytes(self.unordered_chunks[i].user_data.len());
             }
             self.unordered_chunks.drain(..(last_idx + 1) as usize);
+            self.n_partial_messages = self.count_partial_messages();
+        }
+
+        match self.partial {
+            Some(p) if p.unordered && sna32lte(p.next_tsn, new_cumulative_tsn) => {
+                self.partial = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// message_len returns the number of bytes received so far of the message
+    /// `chunk` belongs to, whether it has been completed or not, including the
+    /// bytes already delivered through read_partial.
+    pub(crate) fn message_len(&self, chunk: &ChunkPayloadData) -> usize {
+        if chunk.beginning_fragment && chunk.ending_fragment {
+            return chunk.user_data.len();
+        }
+
+        if !chunk.unordered {
+            let delivered = match self.partial {
+                Some(p) if !p.unordered && p.ssn == chunk.stream_sequence_number => p.delivered,
+                _ => 0,
+            };
+            return delivered
+                + self
+                    .ordered
+                    .iter()
+                    .find(|s| s.ssn == chunk.stream_sequence_number)
+                    .map_or(0, |s| s.len());
+        }
+
+        if let Some(s) = self
+            .unordered
+            .iter()
+            .find(|s| s.chunks.iter().any(|c| c.tsn == chunk.tsn))
+        {
+            return s.len();
         }
+
+        let chunks = &self.unordered_chunks;
+        let i = match chunks.iter().position(|c| c.tsn == chunk.tsn) {
+            Some(i) => i,
+            None => return 0,
+        };
+        let (mut first, mut last) = (i, i);
+        while first > 0 && !starts_message(&chunks[first - 1], &chunks[first]) {
+            first -= 1;
+        }
+        while last + 1 < chunks.len() && !starts_message(&chunks[last], &chunks[last + 1]) {
+            last += 1;
+        }
+        // The fragments left of the message in partial delivery start at next_tsn
+        let delivered = match self.partial {
+            Some(p) if p.unordered && p.next_tsn == chunks[first].tsn => p.delivered,
+            _ => 0,
+        };
+        chunks[first..=last]
+            .iter()
+            .fold(delivered, |n, c| n + c.user_data.len())
+    }
+
+    /// num_partial_messages returns the number of messages of which fragments have
+    /// been received but that are not complete yet.
+    pub(crate) fn num_partial_messages(&self) -> usize {
+        self.n_partial_messages
+    }
+
+    /// count_partial_messages counts the partial messages by scanning the queue.
+    pub(crate) fn count_partial_messages(&self) -> usize {
+        let ordered = self.ordered.iter().filter(|s| !s.is_complete()).count();
+        let unordered = self
+            .unordered_chunks
+            .iter()
+            .enumerate()
+            .filter(|(i, c)| *i == 0 || starts_message(&self.unordered_chunks[i - 1], c))
+            .count();
+        ordered + unordered
     }
 
     pub(crate) fn subtract_num_bytes(&mut self, n_bytes: usize) {